@@ -0,0 +1,707 @@
+//! `#[derive(Block)]`, see the `field` module of `egui-block-programming` for the attributes.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse::Parse, parse_macro_input, Data, DeriveInput, Expr, ExprLit, Fields, Ident, Lit,
+    LitFloat, LitStr, Result, Token,
+};
+
+#[proc_macro_derive(Block, attributes(block))]
+pub fn derive_block(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct BlockAttributes {
+    opcode: Option<String>,
+    shape: Option<LitStr>,
+    branches: Option<usize>,
+    category: Option<LitStr>,
+    color: Option<LitStr>,
+    output: Option<LitStr>,
+    labels: Vec<LitStr>,
+    rust: Option<LitStr>,
+}
+
+#[derive(Default)]
+struct FieldAttributes {
+    default: Option<Expr>,
+    min: Option<LitFloat>,
+    max: Option<LitFloat>,
+    step: Option<LitFloat>,
+    slider: bool,
+    options: Option<Vec<LitStr>>,
+    variable: bool,
+    list: bool,
+    slot: bool,
+    skip: bool,
+    /// the attributes which depend on the type of the field, to check they apply to it
+    typed: Vec<syn::Path>,
+}
+
+struct Field {
+    ident: Ident,
+    ty: syn::Type,
+    attributes: FieldAttributes,
+}
+
+enum LabelToken {
+    Text(String),
+    Field(Ident),
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let attributes = block_attributes(&input)?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input,
+            "Block can only be derived for structs",
+        ));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &data.fields,
+            "Block can only be derived for structs with named fields",
+        ));
+    };
+    let fields = named
+        .named
+        .iter()
+        .map(|field| {
+            Ok(Field {
+                ident: field.ident.clone().unwrap(),
+                ty: field.ty.clone(),
+                attributes: field_attributes(field)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let opcode = attributes
+        .opcode
+        .clone()
+        .unwrap_or_else(|| snake_case(&name.to_string()));
+    let (shape, branches) = shape(&attributes)?;
+    let category = match &attributes.category {
+        Some(category) => Ident::new(&camel_case(&category.value()), category.span()),
+        None => Ident::new("Other", Span::call_site()),
+    };
+    let output = match &attributes.output {
+        Some(output) => quote! {
+            ::egui_block_programming::value::ValueType::from(#output)
+        },
+        None => quote!(::egui_block_programming::value::ValueType::Any),
+    };
+    let color = match &attributes.color {
+        Some(color) => {
+            let [r, g, b] = parse_color(color)?;
+            quote!(::std::option::Option::Some(
+                ::egui_block_programming::__private::Color32::from_rgb(#r, #g, #b)
+            ))
+        }
+        None => quote!(::std::option::Option::None),
+    };
+    let rust = match &attributes.rust {
+        Some(rust) => quote!(::std::option::Option::Some(#rust)),
+        None => quote!(::std::option::Option::None),
+    };
+
+    if attributes.labels.len() != branches + 1 {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            format!(
+                "expected {} labels, one per part, found {}",
+                branches + 1,
+                attributes.labels.len()
+            ),
+        ));
+    }
+
+    let mut used = Vec::new();
+    let mut parts = Vec::new();
+    for label in &attributes.labels {
+        let mut widgets = Vec::new();
+        for token in label_tokens(label)? {
+            match token {
+                LabelToken::Text(text) => widgets.push(quote! {
+                    ::egui_block_programming::block::BlockWidget::Label {
+                        text: ::std::borrow::Cow::Borrowed(#text),
+                    }
+                }),
+                LabelToken::Field(ident) => {
+                    let Some(field) = fields
+                        .iter()
+                        .find(|field| field.ident == ident && !field.attributes.skip)
+                    else {
+                        return Err(syn::Error::new(
+                            label.span(),
+                            format!("no field named `{ident}`"),
+                        ));
+                    };
+                    if used.contains(&ident) {
+                        return Err(syn::Error::new(
+                            label.span(),
+                            format!("`{ident}` is used more than once"),
+                        ));
+                    }
+
+                    widgets.push(field_widget(field));
+                    used.push(ident);
+                }
+            }
+        }
+
+        parts.push(quote!(::std::vec![#(#widgets),*]));
+    }
+
+    if let Some(field) = fields
+        .iter()
+        .find(|field| !field.attributes.skip && !used.contains(&field.ident))
+    {
+        return Err(syn::Error::new_spanned(
+            &field.ident,
+            "field is not part of any label, use `#[block(skip)]` to exclude it",
+        ));
+    }
+
+    let reads: Vec<_> = fields
+        .iter()
+        .filter(|field| !field.attributes.skip)
+        .map(|field| {
+            let ident = &field.ident;
+            let ty = &field.ty;
+            let key = ident.to_string();
+            quote! {
+                self.#ident =
+                    <#ty as ::egui_block_programming::field::BlockField>::read(fields, #key);
+            }
+        })
+        .collect();
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::egui_block_programming::block::Block for #name #ty_generics
+        #where_clause
+        {
+            fn opcode(&self) -> &str {
+                #opcode
+            }
+
+            fn describe(&mut self) -> ::egui_block_programming::block::BlockDescription {
+                ::egui_block_programming::block::BlockDescription {
+                    shape: #shape,
+                    category: ::egui_block_programming::block::BlockCategory::#category,
+                    color: #color,
+                    content: ::std::vec![#(#parts),*],
+                    output: #output,
+                }
+            }
+
+            fn dropdown_options(&mut self, key: &str) -> ::std::vec::Vec<::std::string::String> {
+                ::egui_block_programming::field::DerivedBlock::dropdown_options(self, key)
+            }
+
+            fn run(
+                &mut self,
+                fields: &::egui_block_programming::block::BlockFields,
+            ) -> ::std::result::Result<(), ::egui_block_programming::block::RunError> {
+                #(#reads)*
+                ::egui_block_programming::field::DerivedBlock::run(self, fields)
+            }
+
+            fn report(
+                &mut self,
+                fields: &::egui_block_programming::block::BlockFields,
+            ) -> ::std::result::Result<
+                ::egui_block_programming::value::Value,
+                ::egui_block_programming::block::RunError,
+            > {
+                #(#reads)*
+                ::egui_block_programming::field::DerivedBlock::report(self, fields)
+            }
+
+            fn rust(&self) -> ::std::option::Option<&str> {
+                #rust
+            }
+        }
+    })
+}
+
+fn block_attributes(input: &DeriveInput) -> Result<BlockAttributes> {
+    let mut attributes = BlockAttributes {
+        opcode: None,
+        shape: None,
+        branches: None,
+        category: None,
+        color: None,
+        output: None,
+        labels: Vec::new(),
+        rust: None,
+    };
+
+    for attribute in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("block"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("opcode") {
+                attributes.opcode = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("shape") {
+                attributes.shape = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("branches") {
+                let branches = meta.value()?.parse::<syn::LitInt>()?;
+                attributes.branches = Some(branches.base10_parse()?);
+            } else if meta.path.is_ident("category") {
+                attributes.category = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("color") {
+                attributes.color = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("output") {
+                attributes.output = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("label") {
+                attributes.labels.push(meta.value()?.parse()?);
+            } else if meta.path.is_ident("rust") {
+                attributes.rust = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("unknown block attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(attributes)
+}
+
+fn field_attributes(field: &syn::Field) -> Result<FieldAttributes> {
+    let mut attributes = FieldAttributes::default();
+    for attribute in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("block"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            let number = |meta: &syn::meta::ParseNestedMeta| -> Result<LitFloat> {
+                // accept integers like `max = 10` as well
+                let lit = meta.value()?.parse::<Lit>()?;
+                match lit {
+                    Lit::Float(float) => Ok(float),
+                    Lit::Int(int) => Ok(LitFloat::new(
+                        &format!("{}.0", int.base10_digits()),
+                        int.span(),
+                    )),
+                    _ => Err(syn::Error::new_spanned(lit, "expected a number")),
+                }
+            };
+
+            let typed = !["default", "slot", "skip"]
+                .iter()
+                .any(|name| meta.path.is_ident(name));
+            if typed {
+                attributes.typed.push(meta.path.clone());
+            }
+
+            if meta.path.is_ident("default") {
+                attributes.default = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("min") {
+                attributes.min = Some(number(&meta)?);
+            } else if meta.path.is_ident("max") {
+                attributes.max = Some(number(&meta)?);
+            } else if meta.path.is_ident("step") {
+                attributes.step = Some(number(&meta)?);
+            } else if meta.path.is_ident("slider") {
+                attributes.slider = true;
+            } else if meta.path.is_ident("dropdown") {
+                attributes.options.get_or_insert_with(Vec::new);
+            } else if meta.path.is_ident("options") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let options = content.parse_terminated(<LitStr as Parse>::parse, Token![,])?;
+                attributes
+                    .options
+                    .get_or_insert_with(Vec::new)
+                    .extend(options);
+            } else if meta.path.is_ident("variable") {
+                attributes.variable = true;
+            } else if meta.path.is_ident("list") {
+                attributes.list = true;
+            } else if meta.path.is_ident("slot") {
+                attributes.slot = true;
+            } else if meta.path.is_ident("skip") {
+                attributes.skip = true;
+            } else {
+                return Err(meta.error("unknown field attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    check_field_attributes(&field.ty, &attributes)?;
+    Ok(attributes)
+}
+
+/// Rejects attributes which don't apply to the type of a field. Only the types implementing
+/// `BlockField` in the `field` module are known, other types accept every attribute.
+fn check_field_attributes(ty: &syn::Type, attributes: &FieldAttributes) -> Result<()> {
+    // `dropdown` and `options` both make a dropdown of options
+    let mut dropdown = None;
+    for path in &attributes.typed {
+        let kind = match path.get_ident().map(Ident::to_string).as_deref() {
+            Some("dropdown" | "options") => "options",
+            Some("variable") => "variable",
+            Some("list") => "list",
+            _ => continue,
+        };
+        if dropdown.is_some_and(|dropdown| dropdown != kind) {
+            return Err(syn::Error::new_spanned(
+                path,
+                "only one of `options`, `variable` and `list` can be used",
+            ));
+        }
+        dropdown = Some(kind);
+    }
+
+    let syn::Type::Path(path) = ty else {
+        return Ok(());
+    };
+    let Some(name) = path
+        .path
+        .segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+    else {
+        return Ok(());
+    };
+    let allowed: &[&str] = match name.as_str() {
+        "f64" => &["min", "max", "step", "slider"],
+        "i64" => &["min", "max", "step"],
+        "Angle" => &["step"],
+        "String" | "Value" => &["dropdown", "options", "variable", "list"],
+        "bool" | "Color32" => &[],
+        _ => return Ok(()),
+    };
+
+    match attributes
+        .typed
+        .iter()
+        .find(|path| !allowed.iter().any(|name| path.is_ident(name)))
+    {
+        Some(path) => Err(syn::Error::new_spanned(
+            path,
+            format!(
+                "`{}` doesn't apply to fields of type `{name}`",
+                path.get_ident().unwrap()
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn field_widget(field: &Field) -> TokenStream2 {
+    let ty = &field.ty;
+    let key = field.ident.to_string();
+    let attributes = &field.attributes;
+
+    let default = match &attributes.default {
+        Some(Expr::Lit(ExprLit {
+            lit: Lit::Str(text),
+            ..
+        })) => quote!(::std::option::Option::Some(::std::convert::From::from(#text))),
+        Some(default) => quote!(::std::option::Option::Some(#default)),
+        None => quote!(::std::option::Option::None),
+    };
+    let optional = |value: &Option<LitFloat>| match value {
+        Some(value) => quote!(::std::option::Option::Some(#value)),
+        None => quote!(::std::option::Option::None),
+    };
+    let (min, max, step) = (
+        optional(&attributes.min),
+        optional(&attributes.max),
+        optional(&attributes.step),
+    );
+    let slider = attributes.slider;
+    let options = match &attributes.options {
+        _ if attributes.variable => quote! {
+            ::std::option::Option::Some(
+                ::egui_block_programming::block::DropdownOptions::Variables
+            )
+        },
+        _ if attributes.list => quote! {
+            ::std::option::Option::Some(
+                ::egui_block_programming::block::DropdownOptions::Lists
+            )
+        },
+        Some(options) if options.is_empty() => quote! {
+            ::std::option::Option::Some(
+                ::egui_block_programming::block::DropdownOptions::Dynamic
+            )
+        },
+        Some(options) => quote! {
+            ::std::option::Option::Some(
+                ::egui_block_programming::block::DropdownOptions::Static(
+                    ::std::vec![#(::std::borrow::Cow::Borrowed(#options)),*]
+                )
+            )
+        },
+        None => quote!(::std::option::Option::None),
+    };
+
+    let into_slot = if attributes.slot {
+        quote!(.into_slot())
+    } else {
+        TokenStream2::new()
+    };
+
+    quote! {
+        <#ty as ::egui_block_programming::field::BlockField>::widget(
+            ::std::borrow::Cow::Borrowed(#key),
+            #default,
+            ::egui_block_programming::field::FieldAttributes {
+                min: #min,
+                max: #max,
+                step: #step,
+                slider: #slider,
+                options: #options,
+            },
+        )
+        #into_slot
+    }
+}
+
+fn shape(attributes: &BlockAttributes) -> Result<(TokenStream2, usize)> {
+    let shape = attributes
+        .shape
+        .as_ref()
+        .map(LitStr::value)
+        .unwrap_or_else(|| "stack".to_string());
+    if attributes.branches.is_some() && shape != "c" {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`branches` only applies to `shape = \"c\"`",
+        ));
+    }
+
+    let variant = match shape.as_str() {
+        "hat" => quote!(Hat),
+        "stack" => quote!(Stack),
+        "cap" => quote!(Cap),
+        "reporter" => quote!(Reporter),
+        "boolean" => quote!(Boolean),
+        "c" => {
+            let branches = attributes.branches.unwrap_or(1);
+            if branches == 0 {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "C blocks need at least one branch",
+                ));
+            }
+
+            return Ok((
+                quote! {
+                    ::egui_block_programming::block::BlockShape::C {
+                        branches: ::std::num::NonZeroUsize::new(#branches).unwrap(),
+                    }
+                },
+                branches,
+            ));
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                attributes.shape.as_ref().unwrap(),
+                "expected \"hat\", \"stack\", \"c\", \"cap\", \"reporter\" or \"boolean\"",
+            ))
+        }
+    };
+
+    Ok((
+        quote!(::egui_block_programming::block::BlockShape::#variant),
+        0,
+    ))
+}
+
+fn label_tokens(label: &LitStr) -> Result<Vec<LabelToken>> {
+    let value = label.value();
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let push_text = |text: &mut String, tokens: &mut Vec<LabelToken>| {
+        let trimmed = text.trim();
+        if !trimmed.is_empty() {
+            tokens.push(LabelToken::Text(trimmed.to_string()));
+        }
+        text.clear();
+    };
+
+    let mut chars = value.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let ident = syn::parse_str::<Ident>(name.trim()).map_err(|_| {
+                    syn::Error::new(label.span(), format!("invalid field name `{name}`"))
+                })?;
+
+                push_text(&mut text, &mut tokens);
+                tokens.push(LabelToken::Field(ident));
+            }
+            '}' => return Err(syn::Error::new(label.span(), "unmatched `}`")),
+            _ => text.push(char),
+        }
+    }
+
+    push_text(&mut text, &mut tokens);
+    Ok(tokens)
+}
+
+fn parse_color(color: &LitStr) -> Result<[u8; 3]> {
+    let value = color.value();
+    value
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .map(|rgb| {
+            let [_, r, g, b] = rgb.to_be_bytes();
+            [r, g, b]
+        })
+        .ok_or_else(|| syn::Error::new(color.span(), "expected \"#RRGGBB\""))
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, char) in name.chars().enumerate() {
+        if char.is_uppercase() {
+            if i != 0 {
+                snake.push('_');
+            }
+            snake.extend(char.to_lowercase());
+        } else {
+            snake.push(char);
+        }
+    }
+
+    snake
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    /// Whether the expansion contains `code`, ignoring whitespace.
+    fn contains(expanded: &TokenStream2, code: &str) -> bool {
+        let strip = |code: &str| code.split_whitespace().collect::<String>();
+        strip(&expanded.to_string()).contains(&strip(code))
+    }
+
+    #[test]
+    fn expansion() {
+        let expanded = expand(parse_quote! {
+            #[block(
+                shape = "c",
+                branches = 2,
+                category = "control",
+                color = "#102030",
+                label = "if {condition} then",
+                label = "else if {{maybe}}",
+                label = "wait {seconds}"
+            )]
+            struct IfElseIf {
+                #[block(slot)]
+                condition: bool,
+                #[block(default = 1, min = 0, slider)]
+                seconds: f64,
+                #[block(skip)]
+                runs: usize,
+            }
+        })
+        .unwrap();
+
+        assert!(contains(
+            &expanded,
+            r#"fn opcode(&self) -> &str { "if_else_if" }"#
+        ));
+        assert!(contains(
+            &expanded,
+            "::std::num::NonZeroUsize::new(2usize).unwrap()"
+        ));
+        assert!(contains(
+            &expanded,
+            "::egui_block_programming::block::BlockCategory::Control"
+        ));
+        assert!(contains(
+            &expanded,
+            "::egui_block_programming::__private::Color32::from_rgb(16u8, 32u8, 48u8)"
+        ));
+        assert!(contains(
+            &expanded,
+            r#"text: ::std::borrow::Cow::Borrowed("else if {maybe}")"#
+        ));
+        assert!(contains(&expanded, ".into_slot()"));
+        assert!(contains(
+            &expanded,
+            "min: ::std::option::Option::Some(0.0), max: ::std::option::Option::None"
+        ));
+        assert!(contains(&expanded, "slider: true"));
+        assert!(contains(
+            &expanded,
+            r#"BlockField>::read(fields, "seconds")"#
+        ));
+        assert!(!contains(&expanded, "self.runs"));
+    }
+
+    #[test]
+    fn defaults() {
+        let expanded = expand(parse_quote! {
+            #[block(label = "say {message}")]
+            struct SayHello {
+                #[block(default = "hello", options("hello", "bye"))]
+                message: String,
+            }
+        })
+        .unwrap();
+
+        assert!(contains(&expanded, r#""say_hello""#));
+        assert!(contains(
+            &expanded,
+            "::egui_block_programming::block::BlockShape::Stack"
+        ));
+        assert!(contains(
+            &expanded,
+            "::egui_block_programming::block::BlockCategory::Other"
+        ));
+        assert!(contains(&expanded, "color: ::std::option::Option::None"));
+        assert!(contains(
+            &expanded,
+            r#"::std::option::Option::Some(::std::convert::From::from("hello"))"#
+        ));
+        assert!(contains(
+            &expanded,
+            r#"DropdownOptions::Static(::std::vec![::std::borrow::Cow::Borrowed("hello"), ::std::borrow::Cow::Borrowed("bye")])"#
+        ));
+    }
+}
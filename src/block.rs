@@ -1,50 +1,287 @@
+use crate::{
+    connection::{ConnectionChecker, InputConnection, NextConnection, TypeChecker},
+    debugger::Debugger,
+    geometry::{triangulate, BlockGeometry, OutlineShape, PartOutline},
+    interpreter::{ScriptError, Thread},
+    limits::Limits,
+    monitor::Monitor,
+    procedure::{self, ProcedureDefinition, ProcedureError, Prototype},
+    random::Random,
+    registry::BlockRegistry,
+    save::{LoadError, SavedBlock, SavedValue, SavedVariable, SavedWorkspace},
+    style::BlockEditorStyle,
+    trace::{Recording, Replay},
+    value::{Value, ValueType},
+    variable::{VariableError, VariableKind, Variables},
+    widgets,
+};
 use eframe::{
-    egui::{DragValue, Layout, Response, Sense, TextEdit, Ui, Widget},
+    egui::{
+        color_picker::{self, Alpha},
+        Align2, ComboBox, DragValue, FontId, Layout, Response, Sense, Slider, TextEdit, Ui, Widget,
+    },
     epaint::{Color32, Mesh, Pos2, Rect, Shape, Vec2, Vertex, WHITE_UV},
 };
+pub use egui_block_programming_derive::Block;
 use itertools::Itertools;
-use std::{cmp::Ordering, collections::HashMap, iter, num::NonZeroUsize};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
 use thunderdome::{Arena, Index};
 
-const PART_PADDING: f32 = 10.0;
 const PART_HEIGHT_MIN: f32 = 40.0;
 const MULTIPART_INDENT: f32 = 15.0;
 const EMPTY_BRANCH_HEIGHT: f32 = 20.0;
-const NOTCH_TL: Vec2 = Vec2::new(10.0, 0.0);
-const NOTCH_BL: Vec2 = Vec2::new(20.0, 10.0);
-const NOTCH_BR: Vec2 = Vec2::new(30.0, 10.0);
-const NOTCH_TR: Vec2 = Vec2::new(40.0, 0.0);
-// todo: determine from implementation?
-const FILL_COLOR_LIGHT: Color32 = Color32::from_rgb(0xFF, 0xFF, 0xFF);
-const FILL_COLOR_DARK: Color32 = Color32::from_rgb(0x22, 0x22, 0x22);
-
-#[derive(Clone, Copy)]
+const REPORTER_HEIGHT_MIN: f32 = 28.0;
+const EMPTY_SLOT_SIZE: Vec2 = Vec2::new(40.0, 20.0);
+/// how close a dragged block has to be to snap
+const SNAP_DISTANCE: f32 = 30.0;
+/// of the marker on the left edge of blocks with a breakpoint
+const BREAKPOINT_RADIUS: f32 = 5.0;
+/// of the badge on the top right corner of blocks which failed
+const ERROR_BADGE_RADIUS: f32 = 7.0;
+/// how long blocks light up after being run
+const FLASH_DURATION: Duration = Duration::from_millis(400);
+
+/// Configures a [`BlockWidget::NumberEdit`].
+#[derive(Clone)]
+pub struct NumberFormat {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// values are rounded to multiples of this, 0 allows any value
+    pub step: f64,
+    pub prefix: Cow<'static, str>,
+    /// e.g. a unit
+    pub suffix: Cow<'static, str>,
+    /// only allows whole numbers, which are also stored as such
+    pub integer: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            min: None,
+            max: None,
+            step: 0.0,
+            prefix: Cow::Borrowed(""),
+            suffix: Cow::Borrowed(""),
+            integer: false,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn integer() -> Self {
+        NumberFormat {
+            integer: true,
+            ..Default::default()
+        }
+    }
+
+    /// Applies the bounds, step and integer-only mode to `value`.
+    pub fn apply(&self, mut value: f64) -> f64 {
+        if self.step > 0.0 {
+            value = (value / self.step).round() * self.step;
+        }
+
+        if self.integer {
+            value = value.round();
+        }
+
+        value
+            .max(self.min.unwrap_or(f64::NEG_INFINITY))
+            .min(self.max.unwrap_or(f64::INFINITY))
+    }
+
+    fn drag_value<'a>(&self, value: &'a mut f64) -> DragValue<'a> {
+        let mut drag_value = DragValue::new(value)
+            .clamp_range(self.min.unwrap_or(f64::NEG_INFINITY)..=self.max.unwrap_or(f64::INFINITY))
+            .prefix(&self.prefix)
+            .suffix(&self.suffix);
+
+        if self.step > 0.0 {
+            drag_value = drag_value.speed(self.step);
+        }
+
+        if self.integer {
+            drag_value = drag_value.max_decimals(0).speed(self.step.max(1.0));
+        }
+
+        drag_value
+    }
+}
+
+#[derive(Clone)]
+pub enum DropdownOptions {
+    Static(Vec<Cow<'static, str>>),
+    /// asks [`Block::dropdown_options`] whenever the dropdown is open
+    Dynamic,
+    /// names of the variables of the editor, kept up to date when they're renamed
+    Variables,
+    /// names of the lists of the editor, like [`DropdownOptions::Variables`]
+    Lists,
+}
+
+#[derive(Clone)]
 pub enum BlockWidget {
     Label {
-        text: &'static str,
+        text: Cow<'static, str>,
     },
     TextEdit {
-        key: &'static str,
-        default: &'static str,
+        key: Cow<'static, str>,
+        default: Cow<'static, str>,
     },
     NumberEdit {
-        key: &'static str,
-        default: i32,
+        key: Cow<'static, str>,
+        default: f64,
+        format: NumberFormat,
+    },
+    Dropdown {
+        key: Cow<'static, str>,
+        options: DropdownOptions,
+        default: Cow<'static, str>,
+    },
+    Checkbox {
+        key: Cow<'static, str>,
+        default: bool,
+    },
+    /// RGBA
+    ColorPicker {
+        key: Cow<'static, str>,
+        default: Color32,
+    },
+    /// direction in degrees, 0 pointing up and 90 pointing right
+    Angle {
+        key: Cow<'static, str>,
+        default: f32,
+        /// snaps to multiples of this, 0 disables snapping
+        snap: f32,
+    },
+    Slider {
+        key: Cow<'static, str>,
+        default: f64,
+        min: f64,
+        max: f64,
+        /// 0 allows any value
+        step: f64,
+    },
+    /// empty space for a reporter, e.g. the condition of an if block
+    Slot {
+        key: Cow<'static, str>,
+        accepts: ValueType,
     },
 }
 
+impl BlockWidget {
+    /// `None` for labels
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            BlockWidget::Label { .. } => None,
+            BlockWidget::TextEdit { key, .. }
+            | BlockWidget::NumberEdit { key, .. }
+            | BlockWidget::Dropdown { key, .. }
+            | BlockWidget::Checkbox { key, .. }
+            | BlockWidget::ColorPicker { key, .. }
+            | BlockWidget::Angle { key, .. }
+            | BlockWidget::Slider { key, .. }
+            | BlockWidget::Slot { key, .. } => Some(key),
+        }
+    }
+
+    /// type of reporters which can be put in place of the widget, `None` for labels
+    pub fn accepts(&self) -> Option<ValueType> {
+        Some(match self {
+            BlockWidget::Label { .. } => return None,
+            BlockWidget::TextEdit { .. } | BlockWidget::Dropdown { .. } => ValueType::Any,
+            BlockWidget::NumberEdit { .. }
+            | BlockWidget::Angle { .. }
+            | BlockWidget::Slider { .. } => ValueType::Number,
+            BlockWidget::Checkbox { .. } => ValueType::Bool,
+            BlockWidget::ColorPicker { .. } => ValueType::Color,
+            BlockWidget::Slot { accepts, .. } => accepts.clone(),
+        })
+    }
+
+    /// Replaces the widget with an empty slot accepting the same type, labels stay as they are.
+    pub fn into_slot(self) -> BlockWidget {
+        match (self.key(), self.accepts()) {
+            (Some(key), Some(accepts)) => BlockWidget::Slot {
+                key: key.to_string().into(),
+                accepts,
+            },
+            _ => self,
+        }
+    }
+
+    /// `None` for labels
+    pub fn default_value(&self) -> Option<Value> {
+        let value = match self {
+            BlockWidget::Label { .. } => return None,
+            BlockWidget::TextEdit { default, .. } | BlockWidget::Dropdown { default, .. } => {
+                Value::Text(default.to_string())
+            }
+            BlockWidget::NumberEdit { default, .. } | BlockWidget::Slider { default, .. } => {
+                Value::Number(*default)
+            }
+            BlockWidget::Checkbox { default, .. } => Value::Bool(*default),
+            BlockWidget::ColorPicker { default, .. } => Value::Color(*default),
+            BlockWidget::Angle { default, .. } => Value::Number(*default as f64),
+            BlockWidget::Slot { accepts, .. } => accepts.default_value(),
+        };
+
+        Some(self.constrain(value))
+    }
+
+    /// Converts `value` to the type of the widget and applies its bounds.
+    pub fn constrain(&self, mut value: Value) -> Value {
+        match self {
+            BlockWidget::Label { .. } | BlockWidget::Slot { .. } => (),
+            BlockWidget::TextEdit { .. } | BlockWidget::Dropdown { .. } => {
+                value.text_mut();
+            }
+            BlockWidget::NumberEdit { format, .. } => {
+                let number = value.number_mut();
+                *number = format.apply(*number);
+            }
+            BlockWidget::Checkbox { .. } => {
+                value.bool_mut();
+            }
+            BlockWidget::ColorPicker { .. } => {
+                value.color_mut();
+            }
+            BlockWidget::Angle { snap, .. } => {
+                let number = value.number_mut();
+                *number = widgets::normalize_angle(*number as f32, *snap) as f64;
+            }
+            BlockWidget::Slider { min, max, .. } => {
+                let number = value.number_mut();
+                *number = number.clamp(*min, *max);
+            }
+        }
+
+        value
+    }
+}
+
 // Terminology taken from https://en.scratch-wiki.info/wiki/Blocks#Block_Shapes
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BlockShape {
     /// no top notch
     Hat,
     /// top & bottom notch
     Stack,
-    // TODO: Implement
     /// boolean value
-    // Boolean,
+    Boolean,
     /// "normal" value
-    // Reporter,
+    Reporter,
     /// top notch, `branches` branch notches, bottom notch
     C { branches: NonZeroUsize },
     /// no bottom notch
@@ -53,14 +290,19 @@ pub enum BlockShape {
 
 impl BlockShape {
     fn top_notch(&self) -> bool {
-        !matches!(self, BlockShape::Hat)
+        !matches!(self, BlockShape::Hat) && !self.is_output()
     }
 
     fn bottom_notch(&self) -> bool {
-        !matches!(self, BlockShape::Cap)
+        !matches!(self, BlockShape::Cap) && !self.is_output()
+    }
+
+    /// reporters and booleans, which are put into input slots instead of stacks
+    pub fn is_output(&self) -> bool {
+        matches!(self, BlockShape::Boolean | BlockShape::Reporter)
     }
 
-    fn branches(&self) -> usize {
+    pub fn branches(&self) -> usize {
         match self {
             BlockShape::C { branches } => branches.get(),
             _ => 0,
@@ -68,31 +310,236 @@ impl BlockShape {
     }
 }
 
+// Colors taken from https://en.scratch-wiki.info/wiki/Blocks#Block_Categories
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockCategory {
+    Motion,
+    Looks,
+    Sound,
+    Events,
+    Control,
+    Sensing,
+    Operators,
+    Variables,
+    Lists,
+    MyBlocks,
+    /// the blocks of [`standard`](crate::standard)
+    Standard,
+    /// uses the plain editor fill
+    Other,
+}
+
+impl BlockCategory {
+    pub const ALL: [BlockCategory; 12] = [
+        BlockCategory::Motion,
+        BlockCategory::Looks,
+        BlockCategory::Sound,
+        BlockCategory::Events,
+        BlockCategory::Control,
+        BlockCategory::Sensing,
+        BlockCategory::Operators,
+        BlockCategory::Variables,
+        BlockCategory::Lists,
+        BlockCategory::MyBlocks,
+        BlockCategory::Standard,
+        BlockCategory::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockCategory::Motion => "Motion",
+            BlockCategory::Looks => "Looks",
+            BlockCategory::Sound => "Sound",
+            BlockCategory::Events => "Events",
+            BlockCategory::Control => "Control",
+            BlockCategory::Sensing => "Sensing",
+            BlockCategory::Operators => "Operators",
+            BlockCategory::Variables => "Variables",
+            BlockCategory::Lists => "Lists",
+            BlockCategory::MyBlocks => "My Blocks",
+            BlockCategory::Standard => "Standard",
+            BlockCategory::Other => "Other",
+        }
+    }
+
+    pub fn color(&self) -> Option<Color32> {
+        Some(match self {
+            BlockCategory::Motion => Color32::from_rgb(0x4C, 0x97, 0xFF),
+            BlockCategory::Looks => Color32::from_rgb(0x99, 0x66, 0xFF),
+            BlockCategory::Sound => Color32::from_rgb(0xCF, 0x63, 0xCF),
+            BlockCategory::Events => Color32::from_rgb(0xFF, 0xBF, 0x00),
+            BlockCategory::Control => Color32::from_rgb(0xFF, 0xAB, 0x19),
+            BlockCategory::Sensing => Color32::from_rgb(0x5C, 0xB1, 0xD6),
+            BlockCategory::Operators => Color32::from_rgb(0x59, 0xC0, 0x59),
+            BlockCategory::Variables => Color32::from_rgb(0xFF, 0x8C, 0x1A),
+            BlockCategory::Lists => Color32::from_rgb(0xFF, 0x66, 0x1A),
+            BlockCategory::MyBlocks => Color32::from_rgb(0xFF, 0x66, 0x80),
+            // like the extensions
+            BlockCategory::Standard => Color32::from_rgb(0x0F, 0xBD, 0x8C),
+            BlockCategory::Other => return None,
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct BlockDescription {
     pub shape: BlockShape,
+    pub category: BlockCategory,
+    /// overrides the category color, tinted like it by the [`BlockEditorStyle`]
+    pub color: Option<Color32>,
     /// parts -> widgets
     pub content: Vec<Vec<BlockWidget>>,
+    /// type of the value of [`BlockShape::Reporter`] blocks
+    pub output: ValueType,
+}
+
+impl BlockDescription {
+    /// `None` for blocks which don't report a value
+    pub fn output(&self) -> Option<ValueType> {
+        match self.shape {
+            BlockShape::Boolean => Some(ValueType::Bool),
+            BlockShape::Reporter => Some(self.output.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Why a block failed to run, shown on the block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunError {
+    pub message: String,
+}
+
+impl RunError {
+    pub fn new(message: impl Into<String>) -> Self {
+        RunError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for RunError {}
+
+impl From<String> for RunError {
+    fn from(message: String) -> Self {
+        RunError::new(message)
+    }
+}
+
+impl From<&str> for RunError {
+    fn from(message: &str) -> Self {
+        RunError::new(message)
+    }
 }
 
 pub trait Block {
+    /// Identifies the kind of block in saved workspaces, e.g. `"motion_movesteps"`.
+    fn opcode(&self) -> &str;
+
     fn describe(&mut self) -> BlockDescription;
 
-    fn run(&mut self);
+    /// options of [`DropdownOptions::Dynamic`] dropdowns, e.g. a list of sprites
+    fn dropdown_options(&mut self, _key: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// An error stops the script the block is part of.
+    fn run(&mut self, fields: &BlockFields) -> Result<(), RunError>;
+
+    /// Value of reporter and boolean blocks, evaluated whenever the block they're in runs.
+    fn report(&mut self, fields: &BlockFields) -> Result<Value, RunError> {
+        self.run(fields)?;
+        Ok(Value::default())
+    }
+
+    /// State deciding what the block looks like besides its opcode, e.g. the prototype of a
+    /// procedure call. It's saved with the block.
+    fn mutation(&self) -> Option<String> {
+        None
+    }
+
+    /// Restores a [`Block::mutation`], the block is described again afterwards.
+    fn set_mutation(&mut self, _mutation: &str) {}
+
+    /// [Template](crate::codegen) of the Rust code generated for the block, `None` leaves it
+    /// to [`Host::run`](crate::codegen::Host::run) and [`Host::report`](crate::codegen::Host::report).
+    fn rust(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Current values of the widgets of a block, and the editor it runs in.
+///
+/// The getters panic if the block has no widget with the given key.
+pub struct BlockFields<'a> {
+    pub(crate) values: &'a HashMap<Cow<'static, str>, Value>,
+    pub(crate) random: &'a RefCell<Random>,
+}
+
+impl BlockFields<'_> {
+    pub fn value(&self, key: &str) -> &Value {
+        &self.values[key]
+    }
+
+    /// [`BlockWidget::TextEdit`] and [`BlockWidget::Dropdown`]
+    pub fn text(&self, key: &str) -> Cow<'_, str> {
+        self.value(key).to_text()
+    }
+
+    /// [`BlockWidget::NumberEdit`]
+    pub fn number(&self, key: &str) -> f64 {
+        self.value(key).to_number()
+    }
+
+    /// [`BlockWidget::NumberEdit`], rounded for fields which aren't integer-only
+    pub fn integer(&self, key: &str) -> i64 {
+        self.number(key).round() as i64
+    }
+
+    /// [`BlockWidget::Checkbox`]
+    pub fn bool(&self, key: &str) -> bool {
+        self.value(key).to_bool()
+    }
+
+    /// [`BlockWidget::ColorPicker`]
+    pub fn color(&self, key: &str) -> Color32 {
+        self.value(key).to_color()
+    }
+
+    /// [`BlockWidget::Angle`], in degrees
+    pub fn angle(&self, key: &str) -> f32 {
+        self.number(key) as f32
+    }
+
+    /// [`BlockWidget::Slider`]
+    pub fn slider(&self, key: &str) -> f64 {
+        self.number(key)
+    }
+
+    /// Uniformly distributed in `0.0..1.0`, see [`BlockEditor::seed`].
+    pub fn random(&self) -> f64 {
+        self.random.borrow_mut().next_f64()
+    }
 }
 
-enum Next {
+pub(crate) enum Next {
     NotApplicable,
     None,
-    Hovering { index: Index },
     Some { index: Index, height: f32 },
 }
 
-struct BlockPart {
+pub(crate) struct BlockPart {
     top_offset: Vec2,
     bottom_offset: Vec2,
     width: f32,
-    next: Next,
+    pub(crate) next: Next,
 }
 
 impl BlockPart {
@@ -108,153 +555,391 @@ impl BlockPart {
     }
 }
 
-struct BlockInstance {
+enum SnapTarget {
+    Next { block: Index, part: usize },
+    Input { block: Index, key: String },
+}
+
+struct Snap {
+    target: SnapTarget,
+    /// passed the [`ConnectionChecker`]
+    accepted: bool,
+}
+
+/// Only recalculated when the layout or geometry changes.
+struct OutlineCache {
+    geometry: BlockGeometry,
+    parts: Vec<PartOutline>,
+    points: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+pub(crate) struct BlockInstance {
     position: Pos2,
     last_touched_frame: u64,
-    snap_target: Option<Index>,
-    parts: Vec<BlockPart>,
-    _implementation: Box<dyn Block>,
-    description: BlockDescription,
-    text_data: HashMap<&'static str, String>,
-    number_data: HashMap<&'static str, i32>,
+    snap: Option<Snap>,
+    pub(crate) parts: Vec<BlockPart>,
+    outline_cache: Option<OutlineCache>,
+    pub(crate) implementation: Box<dyn Block>,
+    pub(crate) description: BlockDescription,
+    /// current values of the widgets, by key
+    pub(crate) values: HashMap<Cow<'static, str>, Value>,
+    /// reporters put in place of widgets, by key
+    pub(crate) inputs: HashMap<Cow<'static, str>, Index>,
+    /// where the widgets were last painted, relative to the block position
+    slots: Vec<(String, Rect)>,
 }
 
 impl BlockInstance {
-    fn total_height(&self) -> f32 {
-        self.parts.last().unwrap().bottom_offset.y
+    /// The reporters in inputs in the order of the widgets, which is the order they're
+    /// evaluated in.
+    pub(crate) fn ordered_inputs(&self) -> impl Iterator<Item = (&Cow<'static, str>, Index)> {
+        self.description
+            .content
+            .iter()
+            .flatten()
+            .filter_map(|widget| self.inputs.get_key_value(widget.key()?))
+            .map(|(key, index)| (key, *index))
     }
 
-    fn paint(&mut self, mut uis: Vec<Ui>, response: &Response) {
-        let widget_visuals = uis[0].style().interact(response);
-        let fill_color = if uis[0].visuals().dark_mode {
-            FILL_COLOR_DARK
-        } else {
-            FILL_COLOR_LIGHT
-        };
+    /// keys of the dropdowns listing variables or lists
+    fn variable_keys(&self, kind: VariableKind) -> impl Iterator<Item = &str> {
+        self.description
+            .content
+            .iter()
+            .flatten()
+            .filter_map(move |widget| match (widget, kind) {
+                (
+                    BlockWidget::Dropdown {
+                        key,
+                        options: DropdownOptions::Variables,
+                        ..
+                    },
+                    VariableKind::Variable,
+                )
+                | (
+                    BlockWidget::Dropdown {
+                        key,
+                        options: DropdownOptions::Lists,
+                        ..
+                    },
+                    VariableKind::List,
+                ) => Some(key.as_ref()),
+                _ => None,
+            })
+    }
 
-        let mut vertices = Vec::with_capacity(12 * self.parts.len());
-        let mut vertex = |pos: Pos2| {
-            let index = vertices.len();
-            vertices.push(Vertex {
-                pos,
-                uv: WHITE_UV,
-                color: fill_color,
-            });
+    fn size(&self) -> Vec2 {
+        Vec2::new(
+            self.parts
+                .iter()
+                .map(|part| part.top_offset.x + part.width)
+                .fold(0.0, f32::max),
+            self.parts.last().unwrap().bottom_offset.y,
+        )
+    }
 
-            index as u32
+    /// `sizes` are the sizes of all blocks, for making room for inputs
+    fn paint(
+        &mut self,
+        mut uis: Vec<Ui>,
+        response: &Response,
+        style: &BlockEditorStyle,
+        sizes: &HashMap<Index, Vec2>,
+        variables: &Variables,
+        highlight: Highlight,
+    ) {
+        let outline = if highlight.running {
+            style.running
+        } else if response.hovered() || response.dragged() {
+            style.outline_active
+        } else {
+            style.outline
         };
+        let fill_color = style.block_fill(&self.description);
+        let outline_key = self
+            .parts
+            .iter()
+            .map(|part| PartOutline {
+                left: part.top_offset.x,
+                top: part.top_offset.y,
+                right: part.top_offset.x + part.width,
+                bottom: part.bottom_offset.y,
+            })
+            .collect::<Vec<_>>();
+        let cache = match self.outline_cache.take() {
+            Some(cache) if cache.geometry == style.geometry && cache.parts == outline_key => cache,
+            _ => {
+                let shape = &self.description.shape;
+                let points = if shape.is_output() {
+                    let part = &outline_key[0];
+                    style.geometry.output_outline(
+                        Vec2::new(part.right, part.bottom),
+                        matches!(shape, BlockShape::Boolean),
+                    )
+                } else {
+                    style.geometry.outline(
+                        &OutlineShape {
+                            hat: matches!(shape, BlockShape::Hat),
+                            top_notch: shape.top_notch(),
+                            bottom_notch: shape.bottom_notch(),
+                        },
+                        &outline_key,
+                    )
+                };
+                let indices = triangulate(&points);
 
-        let mut indices = Vec::with_capacity(6 * 5 * self.parts.len());
-        let mut quad = |tl: u32, tr: u32, br: u32, bl: u32| {
-            indices.extend_from_slice(&[tl, tr, bl, bl, br, tr]);
+                OutlineCache {
+                    geometry: style.geometry,
+                    parts: outline_key,
+                    points,
+                    indices,
+                }
+            }
         };
 
-        let mut top_notch_multiplier = Vec2::splat(1.0);
-        let mut bottom_notch_multiplier = Vec2::splat(1.0);
+        let paint_position = uis[0].max_rect().min;
+        let points: Vec<Pos2> = cache
+            .points
+            .iter()
+            .map(|point| paint_position + *point)
+            .collect();
 
-        if !self.description.shape.top_notch() {
-            top_notch_multiplier.y = 0.0;
+        let mesh = |color| Mesh {
+            indices: cache.indices.clone(),
+            vertices: points
+                .iter()
+                .map(|&pos| Vertex {
+                    pos,
+                    uv: WHITE_UV,
+                    color,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        uis[0].painter().add(mesh(fill_color));
+        if highlight.flash > 0.0 {
+            uis[0]
+                .painter()
+                .add(mesh(style.flash.gamma_multiply(highlight.flash)));
         }
-
-        if !self.description.shape.bottom_notch() {
-            bottom_notch_multiplier.y = 0.0;
+        uis[0].painter().add(Shape::closed_line(points, outline));
+        if highlight.breakpoint {
+            let center = paint_position + Vec2::new(0.0, self.parts[0].height() / 2.0);
+            uis[0]
+                .painter()
+                .circle_filled(center, BREAKPOINT_RADIUS, style.breakpoint);
         }
-
-        let mut side_top = None;
-        let mut side_bottom = None;
-        let mut block_part = |tl: Pos2, tr: Pos2, bl: Pos2, br: Pos2| {
-            let index_tl = vertex(tl);
-            vertex(tl + NOTCH_TL * top_notch_multiplier);
-            vertex(tl + NOTCH_BL * top_notch_multiplier);
-            vertex(tl + NOTCH_BR * top_notch_multiplier);
-            vertex(tl + NOTCH_TR * top_notch_multiplier);
-            vertex(tr);
-            vertex(br);
-            vertex(bl + NOTCH_TR * bottom_notch_multiplier);
-            vertex(bl + NOTCH_BR * bottom_notch_multiplier);
-            vertex(bl + NOTCH_BL * bottom_notch_multiplier);
-            vertex(bl + NOTCH_TL * bottom_notch_multiplier);
-            let index_bl = vertex(bl);
-
-            side_bottom = Some((index_bl, index_tl));
-            if side_top.is_none() {
-                side_top = Some((index_tl, index_bl));
-            }
-
-            for i in 0..5 {
-                quad(
-                    index_tl + i,
-                    index_tl + 1 + i,
-                    index_bl - 1 - i,
-                    index_bl - i,
-                );
-            }
-        };
-
-        let paint_position = uis[0].max_rect().min;
-        for part in &self.parts {
-            block_part(
-                paint_position + part.top_offset,
-                paint_position + part.top_offset + Vec2::new(part.width, 0.0),
-                paint_position + part.bottom_offset,
-                paint_position + part.top_offset + Vec2::new(part.width, part.height()),
+        if highlight.error.is_some() {
+            let center = paint_position + Vec2::new(self.parts[0].width, 0.0);
+            let painter = uis[0].painter();
+            painter.circle_filled(center, ERROR_BADGE_RADIUS, style.error);
+            painter.text(
+                center,
+                Align2::CENTER_CENTER,
+                "!",
+                FontId::proportional(ERROR_BADGE_RADIUS * 1.6),
+                Color32::WHITE,
             );
         }
+        self.outline_cache = Some(cache);
 
-        if self.parts.len() > 1 {
-            let (tl, tr) = side_top.unwrap();
-            let (bl, br) = side_bottom.unwrap();
-            quad(tl, tr, br, bl);
-        }
-
-        let shape = Shape::line(
-            (0..vertices.len())
-                .chain(iter::once(0))
-                .map(|i| vertices[i].pos)
-                .collect(),
-            widget_visuals.fg_stroke,
-        );
-
-        uis[0].painter().add(Mesh {
-            vertices,
-            indices,
-            ..Default::default()
-        });
-        uis[0].painter().add(shape);
+        let geometry = &style.geometry;
+        let is_output = self.description.shape.is_output();
+        let (min_width, min_height) = if is_output {
+            (REPORTER_HEIGHT_MIN, REPORTER_HEIGHT_MIN)
+        } else {
+            (
+                MULTIPART_INDENT + geometry.notch.offset + geometry.notch.width + geometry.rounding,
+                PART_HEIGHT_MIN,
+            )
+        };
 
+        self.slots.clear();
         for (i, (part, ui)) in self.parts.iter_mut().zip(uis.iter_mut()).enumerate() {
+            // keep the widgets clear of the pointed or round ends
+            let padding = if is_output {
+                style.padding.max(part.height() / 2.0)
+            } else {
+                style.padding
+            };
+
+            ui.visuals_mut().override_text_color = Some(style.text_color);
+            let mut content_height: f32 = 0.0;
             let content = ui
                 .horizontal_centered(|ui| {
-                    ui.add_space(PART_PADDING);
+                    ui.add_space(padding);
 
                     for widget in &self.description.content[i] {
-                        let _response = match widget {
-                            BlockWidget::Label { text } => ui.label(*text),
+                        let input = widget.key().and_then(|key| self.inputs.get(key));
+                        if let (Some(key), Some(input)) = (widget.key(), input) {
+                            let (rect, _response) =
+                                ui.allocate_exact_size(sizes[input], Sense::hover());
+                            content_height = content_height.max(rect.height());
+                            self.slots.push((key.to_string(), rect));
+                            continue;
+                        }
+
+                        let response = match widget {
+                            BlockWidget::Label { text } => ui.label(text.as_ref()),
                             BlockWidget::TextEdit { key, default: _ } => ui.add(
-                                TextEdit::singleline(self.text_data.get_mut(key).unwrap())
+                                TextEdit::singleline(self.values.get_mut(key).unwrap().text_mut())
                                     .desired_width(24.0)
                                     .clip_text(false),
                             ),
-                            BlockWidget::NumberEdit { key, default: _ } => {
-                                ui.add(DragValue::new(self.number_data.get_mut(key).unwrap()))
+                            BlockWidget::NumberEdit {
+                                key,
+                                default: _,
+                                format,
+                            } => {
+                                let value = self.values.get_mut(key).unwrap().number_mut();
+                                let response = ui.add(format.drag_value(value));
+                                if response.changed() {
+                                    *value = format.apply(*value);
+                                }
+
+                                response
+                            }
+                            BlockWidget::Dropdown {
+                                key,
+                                options,
+                                default: _,
+                            } => {
+                                let selected = self.values.get_mut(key).unwrap().text_mut();
+                                ComboBox::from_id_source(key)
+                                    .selected_text(selected.as_str())
+                                    .show_ui(ui, |ui| {
+                                        let options = match options {
+                                            DropdownOptions::Static(options) => options
+                                                .iter()
+                                                .map(|option| option.to_string())
+                                                .collect(),
+                                            DropdownOptions::Dynamic => {
+                                                self.implementation.dropdown_options(key)
+                                            }
+                                            DropdownOptions::Variables => {
+                                                variables.names().map(String::from).collect()
+                                            }
+                                            DropdownOptions::Lists => {
+                                                variables.list_names().map(String::from).collect()
+                                            }
+                                        };
+
+                                        for option in options {
+                                            let text = option.clone();
+                                            ui.selectable_value(selected, option, text);
+                                        }
+                                    })
+                                    .response
+                            }
+                            BlockWidget::Checkbox { key, default: _ } => {
+                                ui.checkbox(self.values.get_mut(key).unwrap().bool_mut(), "")
+                            }
+                            BlockWidget::ColorPicker { key, default: _ } => {
+                                color_picker::color_edit_button_srgba(
+                                    ui,
+                                    self.values.get_mut(key).unwrap().color_mut(),
+                                    Alpha::OnlyBlend,
+                                )
+                            }
+                            BlockWidget::Angle {
+                                key,
+                                default: _,
+                                snap,
+                            } => {
+                                let value = self.values.get_mut(key).unwrap().number_mut();
+                                let mut degrees = *value as f32;
+                                let response = widgets::angle_dial(ui, &mut degrees, *snap);
+                                *value = degrees as f64;
+                                response
+                            }
+                            BlockWidget::Slider {
+                                key,
+                                default: _,
+                                min,
+                                max,
+                                step,
+                            } => ui.add(
+                                Slider::new(
+                                    self.values.get_mut(key).unwrap().number_mut(),
+                                    *min..=*max,
+                                )
+                                .step_by(*step),
+                            ),
+                            BlockWidget::Slot { accepts, .. } => {
+                                let (rect, response) =
+                                    ui.allocate_exact_size(EMPTY_SLOT_SIZE, Sense::hover());
+                                let points = geometry
+                                    .output_outline(rect.size(), *accepts == ValueType::Bool)
+                                    .into_iter()
+                                    .map(|point| rect.min + point)
+                                    .collect();
+                                ui.painter().add(Shape::convex_polygon(
+                                    points,
+                                    style.slot_fill,
+                                    outline,
+                                ));
+
+                                response
                             }
                         };
+
+                        content_height = content_height.max(response.rect.height());
+                        if let Some(key) = widget.key() {
+                            self.slots.push((key.to_string(), response.rect));
+                        }
                     }
 
-                    ui.add_space(PART_PADDING);
+                    ui.add_space(padding);
                 })
                 .response;
 
-            let size = content.rect.size();
-            part.width = size.x;
-            part.bottom_offset.y = part.top_offset.y + size.y;
+            part.width = content.rect.width().max(min_width);
+            part.bottom_offset.y =
+                part.top_offset.y + (content_height + style.padding).max(min_height);
+        }
+
+        for (_key, rect) in &mut self.slots {
+            *rect = rect.translate(-paint_position.to_vec2());
         }
     }
 }
 
+/// How a block is highlighted while scripts run.
+struct Highlight {
+    /// part of a thread's current position, see [`BlockEditor::running_blocks`]
+    running: bool,
+    /// from 1 right after the block ran to 0
+    flash: f32,
+    breakpoint: bool,
+    /// message of the error the block failed with, see [`BlockEditor::errors`]
+    error: Option<String>,
+}
+
 pub struct BlockEditor {
     offset: Vec2,
-    blocks: Arena<BlockInstance>,
+    pub(crate) blocks: Arena<BlockInstance>,
+    /// derived from the egui visuals if not set
+    style: Option<BlockEditorStyle>,
+    checker: Box<dyn ConnectionChecker>,
+    pub(crate) variables: Variables,
+    monitors: Vec<Monitor>,
+    /// running scripts, see [`BlockEditor::tick`]
+    pub(crate) threads: Vec<Thread>,
+    /// id of the next thread to be started
+    pub(crate) next_thread: u64,
+    /// when blocks last ran or reported a value, for flashing them
+    pub(crate) evaluated: HashMap<Index, Instant>,
+    pub(crate) debugger: Debugger,
+    /// see [`BlockEditor::errors`]
+    pub(crate) errors: Vec<ScriptError>,
+    /// see [`BlockEditor::start_recording`]
+    pub(crate) recording: Option<Recording>,
+    /// see [`BlockEditor::replay`]
+    pub(crate) replay: Option<Replay>,
+    pub(crate) limits: Limits,
+    /// see [`BlockEditor::seed`]
+    pub(crate) random: RefCell<Random>,
 }
 
 impl Default for BlockEditor {
@@ -262,12 +947,311 @@ impl Default for BlockEditor {
         BlockEditor {
             offset: Vec2::ZERO,
             blocks: Arena::new(),
+            style: None,
+            checker: Box::new(TypeChecker),
+            variables: Variables::default(),
+            monitors: Vec::new(),
+            threads: Vec::new(),
+            next_thread: 0,
+            evaluated: HashMap::new(),
+            debugger: Debugger::default(),
+            errors: Vec::new(),
+            recording: None,
+            replay: None,
+            limits: Limits::default(),
+            random: RefCell::new(Random::default()),
         }
     }
 }
 
 impl BlockEditor {
-    pub fn add_block<B: Block + 'static>(&mut self, position: Pos2, mut block: B) {
+    pub fn style(&self) -> Option<&BlockEditorStyle> {
+        self.style.as_ref()
+    }
+
+    pub fn set_style(&mut self, style: Option<BlockEditorStyle>) {
+        self.style = style;
+    }
+
+    /// Decides which snaps are allowed, [`TypeChecker`] by default.
+    pub fn set_connection_checker(&mut self, checker: impl ConnectionChecker + 'static) {
+        self.checker = Box::new(checker);
+    }
+
+    /// how far the workspace has been scrolled
+    pub fn offset(&self) -> Vec2 {
+        self.offset
+    }
+
+    /// Scrolls the workspace so the block is near its top left corner.
+    pub fn focus_block(&mut self, index: Index) {
+        if let Some(block) = self.blocks.get(index) {
+            self.offset = Vec2::new(40.0, 40.0) - block.position.to_vec2();
+        }
+    }
+
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    pub fn variables_mut(&mut self) -> &mut Variables {
+        &mut self.variables
+    }
+
+    /// Renames a variable and every reference to it.
+    pub fn rename_variable(&mut self, name: &str, new_name: &str) -> Result<(), VariableError> {
+        self.rename(VariableKind::Variable, name, new_name)
+    }
+
+    /// Deletes a variable and every block using it.
+    pub fn delete_variable(&mut self, name: &str) -> Result<(), VariableError> {
+        self.delete(VariableKind::Variable, name)
+    }
+
+    /// Renames a list and every reference to it.
+    pub fn rename_list(&mut self, name: &str, new_name: &str) -> Result<(), VariableError> {
+        self.rename(VariableKind::List, name, new_name)
+    }
+
+    /// Deletes a list and every block using it.
+    pub fn delete_list(&mut self, name: &str) -> Result<(), VariableError> {
+        self.delete(VariableKind::List, name)
+    }
+
+    fn rename(
+        &mut self,
+        kind: VariableKind,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), VariableError> {
+        self.variables.rename(kind, name, new_name)?;
+        let new_name = new_name.trim();
+        for index in self.variable_references(kind, name) {
+            let block = &mut self.blocks[index];
+            let keys: Vec<String> = block.variable_keys(kind).map(String::from).collect();
+            for key in keys {
+                let value = block.values.get_mut(key.as_str()).unwrap();
+                if value.to_text() == name {
+                    *value = Value::Text(new_name.to_string());
+                }
+            }
+        }
+
+        for monitor in &mut self.monitors {
+            if monitor.kind == kind && monitor.name == name {
+                monitor.name = new_name.to_string();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn delete(&mut self, kind: VariableKind, name: &str) -> Result<(), VariableError> {
+        self.variables.remove(kind, name)?;
+        for index in self.variable_references(kind, name) {
+            self.remove_block(index);
+        }
+
+        self.monitors
+            .retain(|monitor| !(monitor.kind == kind && monitor.name == name));
+        Ok(())
+    }
+
+    /// Blocks with a variable or list dropdown set to `name`.
+    fn variable_references(&self, kind: VariableKind, name: &str) -> Vec<Index> {
+        self.blocks
+            .iter()
+            .filter(|(_index, block)| {
+                block
+                    .variable_keys(kind)
+                    .any(|key| block.values[key].to_text() == name)
+            })
+            .map(|(index, _block)| index)
+            .collect()
+    }
+
+    /// Adds the `define` block of a new procedure.
+    pub fn define_procedure(
+        &mut self,
+        position: Pos2,
+        prototype: &Prototype,
+    ) -> Result<Index, ProcedureError> {
+        let prototype = normalize(prototype)?;
+        if self.definition(&prototype.to_string()).is_some() {
+            return Err(ProcedureError::Exists(prototype.to_string()));
+        }
+
+        Ok(self.add_block(position, ProcedureDefinition { prototype }))
+    }
+
+    /// Changes the prototype of a procedure and updates its calls, `renamed` maps old parameter
+    /// names to new ones. Arguments of parameters which don't exist anymore are dropped.
+    pub fn edit_procedure(
+        &mut self,
+        prototype: &Prototype,
+        new_prototype: &Prototype,
+        renamed: &HashMap<String, String>,
+    ) -> Result<(), ProcedureError> {
+        let (old, new) = (prototype.to_string(), normalize(new_prototype)?.to_string());
+        let definition = self
+            .definition(&old)
+            .ok_or_else(|| ProcedureError::Unknown(old.clone()))?;
+        if new != old && self.definition(&new).is_some() {
+            return Err(ProcedureError::Exists(new));
+        }
+
+        let calls: Vec<Index> = self
+            .blocks
+            .iter()
+            .filter(|(_index, block)| {
+                block.implementation.opcode() == procedure::CALL
+                    && block.implementation.mutation().as_deref() == Some(&old)
+            })
+            .map(|(index, _block)| index)
+            .collect();
+        for index in calls {
+            let block = &mut self.blocks[index];
+            for (name, new_name) in renamed {
+                if let Some(value) = block.values.remove(name.as_str()) {
+                    block.values.insert(new_name.clone().into(), value);
+                }
+
+                if let Some(input) = block.inputs.remove(name.as_str()) {
+                    block.inputs.insert(new_name.clone().into(), input);
+                }
+            }
+
+            self.mutate(index, &new)?;
+        }
+
+        for index in self.descendants(definition) {
+            let block = &self.blocks[index];
+            let opcode = block.implementation.opcode();
+            if opcode != procedure::PARAMETER && opcode != procedure::BOOLEAN_PARAMETER {
+                continue;
+            }
+
+            let name = block.implementation.mutation().unwrap_or_default();
+            if let Some(new_name) = renamed.get(&name) {
+                self.mutate(index, new_name)?;
+            }
+        }
+
+        self.mutate(definition, &new)
+    }
+
+    /// Changes the [`Block::mutation`] of a block and describes it again, values and inputs of
+    /// widgets which are still there are kept. Mutations changing the number of parts are undone.
+    fn mutate(&mut self, index: Index, mutation: &str) -> Result<(), ProcedureError> {
+        let block = &mut self.blocks[index];
+        let old = block.implementation.mutation();
+        block.implementation.set_mutation(mutation);
+        let description = block.implementation.describe();
+        if description.content.len() != block.parts.len() {
+            if let Some(old) = old {
+                block.implementation.set_mutation(&old);
+            }
+
+            return Err(ProcedureError::PartCount {
+                opcode: block.implementation.opcode().to_string(),
+                expected: block.parts.len(),
+                found: description.content.len(),
+            });
+        }
+
+        let widgets = || description.content.iter().flatten();
+        block.values = widgets()
+            .filter_map(|widget| {
+                let key = widget.key()?;
+                let value = match block.values.remove(key) {
+                    Some(value) => widget.constrain(value),
+                    None => widget.default_value()?,
+                };
+
+                Some((key.to_string().into(), value))
+            })
+            .collect();
+        block
+            .inputs
+            .retain(|key, _input| widgets().any(|widget| widget.key() == Some(key)));
+        block.description = description;
+        Ok(())
+    }
+
+    /// Whether the current value of a variable or list is shown on the workspace.
+    pub fn has_monitor(&self, kind: VariableKind, name: &str) -> bool {
+        self.monitors
+            .iter()
+            .any(|monitor| monitor.kind == kind && monitor.name == name)
+    }
+
+    /// Shows or hides the monitor of a variable or list, new monitors are put into the top left
+    /// corner of the visible part of the workspace.
+    pub fn set_monitor(&mut self, kind: VariableKind, name: &str, visible: bool) {
+        if visible && !self.has_monitor(kind, name) {
+            self.monitors.push(Monitor {
+                kind,
+                name: name.to_string(),
+                position: Pos2::new(10.0, 10.0) - self.offset,
+            });
+        } else if !visible {
+            self.monitors
+                .retain(|monitor| !(monitor.kind == kind && monitor.name == name));
+        }
+    }
+
+    /// Removes a block together with its inputs and the stacks in its branches, the blocks
+    /// below it move up.
+    pub fn remove_block(&mut self, index: Index) -> Option<Box<dyn Block>> {
+        let block = self.blocks.get(index)?;
+        let (branches, last) = block.parts.split_at(block.parts.len() - 1);
+        let below = match last[0].next {
+            Next::Some { index, .. } => Some(index),
+            _ => None,
+        };
+        let attached: Vec<Index> = branches
+            .iter()
+            .filter_map(|part| match part.next {
+                Next::Some { index, .. } => Some(index),
+                _ => None,
+            })
+            .chain(block.inputs.values().copied())
+            .collect();
+
+        let mut removed = vec![index];
+        for index in attached {
+            removed.extend(self.descendants(index));
+        }
+        for index in &removed {
+            self.debugger.breakpoints.remove(index);
+        }
+        self.errors.retain(|error| !removed.contains(&error.block));
+        for &index in &removed[1..] {
+            self.blocks.remove(index);
+        }
+        let block = self.blocks.remove(index).unwrap();
+
+        for (_index, other) in &mut self.blocks {
+            for part in &mut other.parts {
+                if matches!(part.next, Next::Some { index: next, .. } if next == index) {
+                    part.next = match below {
+                        Some(index) => Next::Some { index, height: 0.0 },
+                        None => Next::None,
+                    };
+                }
+            }
+
+            other.inputs.retain(|_key, input| *input != index);
+        }
+
+        Some(block.implementation)
+    }
+
+    pub fn add_block<B: Block + 'static>(&mut self, position: Pos2, block: B) -> Index {
+        self.add_boxed(position, Box::new(block))
+    }
+
+    pub fn add_boxed(&mut self, position: Pos2, mut block: Box<dyn Block>) -> Index {
         let description = block.describe();
         assert_eq!(
             description.content.len(),
@@ -275,21 +1259,27 @@ impl BlockEditor {
             "number of parts does not match number of branches"
         );
 
-        let mut text_data = HashMap::new();
-        let mut number_data = HashMap::new();
-        for part in &description.content {
-            for widget in part {
-                match widget {
-                    BlockWidget::TextEdit { key, default } => {
-                        text_data.insert(*key, String::from(*default));
-                    }
-                    BlockWidget::NumberEdit { key, default } => {
-                        number_data.insert(*key, *default);
+        let values = description
+            .content
+            .iter()
+            .flatten()
+            .filter_map(|widget| {
+                let mut value = widget.default_value()?;
+                // start out with the first variable or list instead of none
+                if let BlockWidget::Dropdown { options, .. } = widget {
+                    let mut names = match options {
+                        DropdownOptions::Variables => self.variables.names().collect(),
+                        DropdownOptions::Lists => self.variables.list_names().collect(),
+                        _ => Vec::new(),
+                    };
+                    if value.to_text().is_empty() && !names.is_empty() {
+                        value = names.swap_remove(0).into();
                     }
-                    _ => (),
                 }
-            }
-        }
+
+                Some((widget.key()?.to_string().into(), value))
+            })
+            .collect();
 
         let parts = description
             .content
@@ -310,16 +1300,476 @@ impl BlockEditor {
         self.blocks.insert(BlockInstance {
             position,
             last_touched_frame: 0,
-            snap_target: None,
+            snap: None,
             parts,
-            _implementation: Box::new(block),
+            outline_cache: None,
+            implementation: block,
             description,
-            text_data,
-            number_data,
-        });
+            values,
+            inputs: HashMap::new(),
+            slots: Vec::new(),
+        })
+    }
+
+    /// Sets the value of a widget, converted to its type. Does nothing if the block has no
+    /// widget with the given key.
+    pub fn set_field(&mut self, index: Index, key: &str, value: Value) {
+        let block = &mut self.blocks[index];
+        let widget = block
+            .description
+            .content
+            .iter()
+            .flatten()
+            .find(|widget| widget.key() == Some(key));
+        if let Some(widget) = widget {
+            *block.values.get_mut(key).unwrap() = widget.constrain(value);
+        }
+    }
+
+    pub fn save(&self) -> SavedWorkspace {
+        let positions: HashMap<Index, usize> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(position, (index, _block))| (index, position))
+            .collect();
+
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|(_index, block)| {
+                let fields = block
+                    .description
+                    .content
+                    .iter()
+                    .flatten()
+                    .filter_map(|widget| {
+                        let key = widget.key()?;
+                        let value = match (widget, &block.values[key]) {
+                            (BlockWidget::NumberEdit { format, .. }, value) if format.integer => {
+                                SavedValue::Integer(value.to_number() as i64)
+                            }
+                            (_, value) => SavedValue::from_value(value)?,
+                        };
+
+                        Some((key.to_string(), value))
+                    })
+                    .collect();
+
+                SavedBlock {
+                    opcode: block.implementation.opcode().to_string(),
+                    mutation: block.implementation.mutation(),
+                    position: [block.position.x, block.position.y],
+                    fields,
+                    next: block
+                        .parts
+                        .iter()
+                        .map(|part| match part.next {
+                            Next::Some { index, .. } => Some(positions[&index]),
+                            _ => None,
+                        })
+                        .collect(),
+                    inputs: block
+                        .inputs
+                        .iter()
+                        .map(|(key, index)| (key.to_string(), positions[index]))
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let monitor = |kind, name: &str| {
+            self.monitors
+                .iter()
+                .find(|monitor| monitor.kind == kind && monitor.name == name)
+                .map(|monitor| [monitor.position.x, monitor.position.y])
+        };
+        let variables = self
+            .variables
+            .iter()
+            .map(|(name, scope, value)| SavedVariable {
+                name: name.to_string(),
+                scope,
+                // custom values can't be saved
+                value: SavedValue::from_value(value)
+                    .unwrap_or_else(|| SavedValue::Text(value.to_text().into_owned())),
+                monitor: monitor(VariableKind::Variable, name),
+            })
+            .collect();
+        let lists = self
+            .variables
+            .lists()
+            .map(|(name, scope, items)| SavedVariable {
+                name: name.to_string(),
+                scope,
+                value: SavedValue::List(items.iter().filter_map(SavedValue::from_value).collect()),
+                monitor: monitor(VariableKind::List, name),
+            })
+            .collect();
+
+        SavedWorkspace {
+            offset: [self.offset.x, self.offset.y],
+            blocks,
+            variables,
+            lists,
+            seed: self.seed(),
+        }
+    }
+
+    /// Values which don't fit the widgets of a block anymore are converted to their new type.
+    pub fn load(
+        saved: &SavedWorkspace,
+        registry: &BlockRegistry,
+    ) -> Result<BlockEditor, LoadError> {
+        let mut editor = BlockEditor {
+            offset: Vec2::from(saved.offset),
+            random: RefCell::new(Random::new(saved.seed)),
+            ..Default::default()
+        };
+
+        for variable in &saved.variables {
+            editor
+                .variables
+                .insert(&variable.name, variable.scope, variable.value.to_value())
+                .map_err(LoadError::Variable)?;
+        }
+
+        for list in &saved.lists {
+            editor
+                .variables
+                .insert_list(&list.name, list.scope, list.value.to_value().to_list())
+                .map_err(LoadError::Variable)?;
+        }
+
+        let monitors = saved
+            .variables
+            .iter()
+            .map(|variable| (VariableKind::Variable, variable))
+            .chain(saved.lists.iter().map(|list| (VariableKind::List, list)));
+        for (kind, variable) in monitors {
+            if let Some(position) = variable.monitor {
+                editor.monitors.push(Monitor {
+                    kind,
+                    name: variable.name.clone(),
+                    position: Pos2::from(position),
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity(saved.blocks.len());
+        for saved_block in &saved.blocks {
+            let mut implementation = registry
+                .create(&saved_block.opcode)
+                .ok_or_else(|| LoadError::UnknownBlock(saved_block.opcode.clone()))?;
+            if let Some(mutation) = &saved_block.mutation {
+                implementation.set_mutation(mutation);
+            }
+
+            let index = editor.add_boxed(Pos2::from(saved_block.position), implementation);
+            indices.push(index);
+
+            let block = &mut editor.blocks[index];
+            for widget in block.description.content.iter().flatten() {
+                if let Some((key, saved)) = widget
+                    .key()
+                    .and_then(|key| Some((key, saved_block.fields.get(key)?)))
+                {
+                    let value = widget.constrain(saved.to_value());
+                    *block.values.get_mut(key).unwrap() = value;
+                }
+            }
+        }
+
+        // whether a block is attached to another one
+        let mut attached = vec![false; saved.blocks.len()];
+        for (i, saved_block) in saved.blocks.iter().enumerate() {
+            let mut attach = |next: usize| {
+                let index = *indices
+                    .get(next)
+                    .ok_or(LoadError::InvalidConnection { block: i, next })?;
+                if std::mem::replace(&mut attached[next], true) {
+                    return Err(LoadError::MultipleParents { block: next });
+                }
+
+                Ok(index)
+            };
+
+            let block = &editor.blocks[indices[i]];
+            let mut parts = Vec::new();
+            for (part, next) in saved_block.next.iter().enumerate() {
+                // the block doesn't have this part anymore
+                let (Some(next), Some(block_part)) = (*next, block.parts.get(part)) else {
+                    continue;
+                };
+
+                let index = attach(next)?;
+                let child = &editor.blocks[index];
+                if !matches!(block_part.next, Next::None)
+                    || !child.description.shape.top_notch()
+                    || !editor.checker.check_next(&NextConnection {
+                        parent: block.implementation.opcode(),
+                        part,
+                        child: child.implementation.opcode(),
+                    })
+                {
+                    return Err(LoadError::IncompatibleConnection { block: i, next });
+                }
+
+                parts.push((part, index));
+            }
+
+            let mut inputs = Vec::new();
+            for (key, &next) in &saved_block.inputs {
+                // the block doesn't have this input anymore
+                let Some(widget) = block
+                    .description
+                    .content
+                    .iter()
+                    .flatten()
+                    .find(|widget| widget.key() == Some(key))
+                else {
+                    continue;
+                };
+
+                let index = attach(next)?;
+                let child = &editor.blocks[index];
+                let accepts = widget.accepts().unwrap_or_default();
+                let accepted = child.description.output().is_some_and(|output| {
+                    editor.checker.check_input(&InputConnection {
+                        parent: block.implementation.opcode(),
+                        key,
+                        accepts: &accepts,
+                        child: child.implementation.opcode(),
+                        output: &output,
+                    })
+                });
+                if !accepted {
+                    return Err(LoadError::IncompatibleConnection { block: i, next });
+                }
+
+                inputs.push((key.clone(), index));
+            }
+
+            let block = &mut editor.blocks[indices[i]];
+            for (part, index) in parts {
+                block.parts[part].next = Next::Some { index, height: 0.0 };
+            }
+            for (key, index) in inputs {
+                block.inputs.insert(key.into(), index);
+            }
+        }
+
+        // blocks in a cycle can't be reached from the top of a script
+        let mut reachable = HashSet::new();
+        for (i, &index) in indices.iter().enumerate() {
+            if !attached[i] {
+                reachable.extend(editor.descendants(index));
+            }
+        }
+        if let Some(block) = indices.iter().position(|index| !reachable.contains(index)) {
+            return Err(LoadError::Cycle { block });
+        }
+
+        Ok(editor)
+    }
+
+    /// Maps every attached block to the block it's attached to.
+    fn parents(&self) -> HashMap<Index, Index> {
+        let mut parents = HashMap::new();
+        for (index, block) in &self.blocks {
+            for part in &block.parts {
+                if let Next::Some { index: next, .. } = part.next {
+                    parents.insert(next, index);
+                }
+            }
+
+            for input in block.inputs.values() {
+                parents.insert(*input, index);
+            }
+        }
+
+        parents
+    }
+
+    /// The block itself and everything attached to it, directly or indirectly.
+    fn descendants(&self, index: Index) -> Vec<Index> {
+        let mut descendants = vec![index];
+        let mut i = 0;
+        while let Some(&index) = descendants.get(i) {
+            let block = &self.blocks[index];
+            for part in &block.parts {
+                if let Next::Some { index: next, .. } = part.next {
+                    descendants.push(next);
+                }
+            }
+
+            descendants.extend(block.inputs.values());
+            i += 1;
+        }
+
+        descendants
+    }
+
+    /// Total height of the stack starting at `index`.
+    fn stack_height(&self, mut index: Index) -> f32 {
+        let mut height = 0.0;
+        loop {
+            let block = &self.blocks[index];
+            height += block.size().y;
+            match block.parts.last().unwrap().next {
+                Next::Some { index: next, .. } => index = next,
+                _ => return height,
+            }
+        }
+    }
+
+    /// Lets the branches of C blocks grow with the blocks inside of them, `true` if any changed.
+    fn update_branch_heights(&mut self) -> bool {
+        let heights: Vec<_> = self
+            .blocks
+            .iter()
+            .flat_map(|(index, block)| {
+                let branches = block.parts.len() - 1;
+                block.parts[..branches]
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(i, part)| match part.next {
+                        Next::Some { index: next, .. } => Some((index, i, next)),
+                        _ => None,
+                    })
+            })
+            .map(|(index, i, next)| (index, i, self.stack_height(next)))
+            .collect();
+
+        let mut changed = false;
+        for (index, i, new_height) in heights {
+            if let Next::Some { height, .. } = &mut self.blocks[index].parts[i].next {
+                changed |= *height != new_height;
+                *height = new_height;
+            }
+        }
+
+        changed
+    }
+
+    /// Removes the connection between `index` and the block it's attached to.
+    fn detach(&mut self, index: Index) {
+        for (_index, block) in &mut self.blocks {
+            for part in &mut block.parts {
+                if matches!(part.next, Next::Some { index: next, .. } if next == index) {
+                    part.next = Next::None;
+                }
+            }
+
+            block.inputs.retain(|_key, input| *input != index);
+        }
+    }
+
+    /// Closest free connection of the dragged block, if any is close enough.
+    fn find_snap(&self, dragging: Index, style: &BlockEditorStyle) -> Option<Snap> {
+        let block = &self.blocks[dragging];
+        let descendants = self.descendants(dragging);
+        let candidates = || {
+            self.blocks
+                .iter()
+                .filter(|(index, _block)| !descendants.contains(index))
+        };
+
+        if let Some(output) = block.description.output() {
+            // the left end of the reporter to the left end of the slot
+            let anchor = block.position + Vec2::new(0.0, block.size().y / 2.0);
+            let (index, key, _distance) = candidates()
+                .flat_map(|(index, other)| {
+                    other
+                        .slots
+                        .iter()
+                        .filter(|(key, _rect)| !other.inputs.contains_key(key.as_str()))
+                        .map(move |(key, rect)| {
+                            let slot = other.position + rect.left_center().to_vec2();
+                            (index, key, anchor.distance(slot))
+                        })
+                })
+                .filter(|(_index, _key, distance)| *distance < SNAP_DISTANCE)
+                .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))?;
+
+            let other = &self.blocks[index];
+            let accepts = other
+                .description
+                .content
+                .iter()
+                .flatten()
+                .find(|widget| widget.key() == Some(key))
+                .and_then(BlockWidget::accepts)
+                .unwrap_or_default();
+            let accepted = self.checker.check_input(&InputConnection {
+                parent: other.implementation.opcode(),
+                key,
+                accepts: &accepts,
+                child: block.implementation.opcode(),
+                output: &output,
+            });
+
+            Some(Snap {
+                target: SnapTarget::Input {
+                    block: index,
+                    key: key.clone(),
+                },
+                accepted,
+            })
+        } else if block.description.shape.top_notch() {
+            let notch_offset = style.geometry.notch.attachment();
+            let top = block.position + block.parts[0].top_offset + notch_offset;
+            let (index, part, _distance) = candidates()
+                .flat_map(|(index, other)| {
+                    other
+                        .parts
+                        .iter()
+                        .enumerate()
+                        .filter(|(_i, part)| matches!(part.next, Next::None))
+                        .map(move |(i, part)| {
+                            let attachment = other.position + part.bottom_offset + notch_offset;
+                            (index, i, top.distance(attachment))
+                        })
+                })
+                .filter(|(_index, _part, distance)| *distance < SNAP_DISTANCE)
+                .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))?;
+
+            let accepted = self.checker.check_next(&NextConnection {
+                parent: self.blocks[index].implementation.opcode(),
+                part,
+                child: block.implementation.opcode(),
+            });
+
+            Some(Snap {
+                target: SnapTarget::Next { block: index, part },
+                accepted,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn attach(&mut self, index: Index, target: SnapTarget) {
+        match target {
+            SnapTarget::Next { block, part } => {
+                let next = &mut self.blocks[block].parts[part].next;
+                if matches!(next, Next::None) {
+                    *next = Next::Some { index, height: 0.0 };
+                }
+            }
+            SnapTarget::Input { block, key } => {
+                self.blocks[block].inputs.entry(key.into()).or_insert(index);
+            }
+        }
     }
 }
 
+/// Prototypes are identified by their text, which merges adjacent labels.
+fn normalize(prototype: &Prototype) -> Result<Prototype, ProcedureError> {
+    prototype.validate()?;
+    Prototype::parse(&prototype.to_string())
+}
+
 impl Widget for &mut BlockEditor {
     fn ui(self, ui: &mut Ui) -> Response {
         ui.style_mut().interaction.selectable_labels = false;
@@ -333,39 +1783,83 @@ impl Widget for &mut BlockEditor {
         ui.painter()
             .rect_filled(editor_rect, 5.0, ui.style().visuals.extreme_bg_color);
 
-        /*
-        if let Some(snap_target) = self.snap_target {
-            let snap_target = &self.blocks[snap_target];
-
-            snap_target.paint(
-                ui.painter(),
-                ui.max_rect().min
-                    + snap_target.position.to_vec2()
-                    + Vec2::new(0.0, DEPRECATED_BLOCK_SIZE.y)
-                    + self.area_offset,
-                &snap_target.layout(),
-                ui.visuals().selection.bg_fill,
-                Stroke::NONE,
-            );
+        let style = self
+            .style
+            .clone()
+            .unwrap_or_else(|| BlockEditorStyle::from_visuals(ui.visuals()));
+
+        if self.update_branch_heights() {
+            ui.ctx().request_repaint();
+        }
+
+        // attached blocks are painted after the blocks they're attached to, so they end up on top
+        // and can be positioned in the same frame
+        let parents = self.parents();
+        let order: Vec<Index> = self
+            .blocks
+            .iter()
+            .map(|(index, _block)| {
+                let (mut root, mut depth) = (index, 0);
+                while let Some(&parent) = parents.get(&root) {
+                    root = parent;
+                    depth += 1;
+                }
+
+                (self.blocks[root].last_touched_frame, depth, index)
+            })
+            .sorted_unstable_by_key(|&(frame, depth, _index)| (frame, depth))
+            .map(|(_frame, _depth, index)| index)
+            .collect();
+        let sizes: HashMap<Index, Vec2> = self
+            .blocks
+            .iter()
+            .map(|(index, block)| (index, block.size()))
+            .collect();
 
-            self.snap_target = None;
+        let mut running = self.running_blocks();
+        if self.is_replaying() {
+            running = self
+                .replay_step()
+                .and_then(|(_step, index)| index)
+                .into_iter()
+                .collect();
+        }
+        // where the paused thread is going to continue
+        if self.debugger.paused {
+            let paused = self.paused_thread();
+            let thread = self
+                .threads
+                .iter()
+                .find(|thread| Some(thread.id()) == paused);
+            running.extend(thread.and_then(Thread::next_block));
         }
-        */
 
-        struct SetNext {
-            upper_block: Index,
-            upper_part: usize,
-            next_block: Index,
+        let now = Instant::now();
+        self.evaluated
+            .retain(|_index, instant| now.duration_since(*instant) < FLASH_DURATION);
+        if !self.evaluated.is_empty() {
+            ui.ctx().request_repaint();
         }
 
         let mut dragging = None;
-        let mut set_next = None;
-        for (index, block) in self
-            .blocks
-            .iter_mut()
-            .sorted_unstable_by_key(|(_index, block)| block.last_touched_frame)
-        {
-            let sense = Sense::drag();
+        let mut dropped = None;
+        let mut clicked = None;
+        let mut toggled_breakpoint = None;
+        for index in order {
+            let highlight = Highlight {
+                running: running.contains(&index),
+                breakpoint: self.debugger.breakpoints.contains(&index),
+                error: self
+                    .errors
+                    .iter()
+                    .find(|error| error.block == index)
+                    .map(|error| error.to_string()),
+                flash: self.evaluated.get(&index).map_or(0.0, |instant| {
+                    1.0 - now.duration_since(*instant).as_secs_f32() / FLASH_DURATION.as_secs_f32()
+                }),
+            };
+            let block = &mut self.blocks[index];
+            let sense = Sense::click_and_drag();
             let part_count = block.parts.len();
             let mut uis = Vec::with_capacity(block.parts.len());
             let mut y_offset = 0.0;
@@ -385,7 +1879,8 @@ impl Widget for &mut BlockEditor {
                     part.extent(),
                 );
 
-                let mut ui = ui.child_ui(child_ui_rect, Layout::default());
+                let mut ui =
+                    ui.child_ui_with_id_source(child_ui_rect, Layout::default(), (index, i));
                 ui.set_clip_rect(if part.width == 0.0 {
                     Rect::NOTHING
                 } else {
@@ -421,107 +1916,225 @@ impl Widget for &mut BlockEditor {
                 ));
             }
 
+            if let Some(message) = &highlight.error {
+                response = response.on_hover_text(message.clone());
+            }
+
             if response.dragged() {
                 block.position += response.drag_delta();
-                // TODO: propagate this through the linked list
                 block.last_touched_frame = ui.ctx().frame_nr();
                 dragging = Some(index);
             }
 
-            if response.drag_released() {
-                if let Some(upper_block) = block.snap_target {
-                    block.snap_target = None;
-                    set_next = Some(SetNext {
-                        upper_block,
-                        upper_part: 0,
-                        next_block: index,
-                    });
+            if response.clicked() {
+                clicked = Some(index);
+            }
+
+            response.context_menu(|ui| {
+                let text = if highlight.breakpoint {
+                    "Remove breakpoint"
+                } else {
+                    "Add breakpoint"
+                };
+                if ui.button(text).clicked() {
+                    toggled_breakpoint = Some(index);
+                    ui.close_menu();
+                }
+            });
+
+            if response.drag_stopped() {
+                if let Some(snap) = block.snap.take() {
+                    if snap.accepted {
+                        dropped = Some((index, snap.target));
+                    }
                 }
             }
 
-            block.paint(uis, &response);
+            block.paint(uis, &response, &style, &sizes, &self.variables, highlight);
+
+            let position = block.position;
+            let mut attached: Vec<_> = block
+                .parts
+                .iter()
+                .filter_map(|part| match part.next {
+                    Next::Some { index, .. } => Some((index, position + part.bottom_offset)),
+                    _ => None,
+                })
+                .collect();
+            attached.extend(block.slots.iter().filter_map(|(key, rect)| {
+                let input = block.inputs.get(key.as_str())?;
+                Some((*input, position + rect.min.to_vec2()))
+            }));
+
+            for (index, position) in attached {
+                self.blocks[index].position = position;
+            }
         }
 
-        if let Some(dragging) = dragging {
-            // if this block is the next of anything, clear it
-            for (_index, block) in &mut self.blocks {
-                for part in &mut block.parts {
-                    if matches!(part.next, Next::Some { index, .. } if index == dragging) {
-                        part.next = Next::None;
+        let origin = ui.max_rect().min + self.offset;
+        for (_index, block) in &self.blocks {
+            let Some(snap) = &block.snap else {
+                continue;
+            };
+
+            let stroke = if snap.accepted {
+                style.selection
+            } else {
+                style.rejected
+            };
+
+            match &snap.target {
+                SnapTarget::Next { block, part } => {
+                    let target = &self.blocks[*block];
+                    let part = &target.parts[*part];
+                    let left = origin + target.position.to_vec2() + part.bottom_offset;
+                    let right = part.top_offset.x + part.width;
+                    ui.painter().line_segment(
+                        [left, left + Vec2::new(right - part.bottom_offset.x, 0.0)],
+                        stroke,
+                    );
+                }
+                SnapTarget::Input { block, key } => {
+                    let target = &self.blocks[*block];
+                    if let Some((_key, rect)) = target.slots.iter().find(|(slot, _)| slot == key) {
+                        let rect = rect.translate(origin.to_vec2() + target.position.to_vec2());
+                        ui.painter()
+                            .rect_stroke(rect.expand(2.0), rect.height() / 2.0, stroke);
                     }
                 }
             }
+        }
 
-            let notch_offset = (NOTCH_BL + NOTCH_BR) / 2.0;
-            let dragging_top_attachment = self.blocks[dragging].position
-                + self.blocks[dragging].parts.first().unwrap().top_offset
-                + notch_offset;
-            // let dragging_bottom_attachment = self.blocks[dragging].position
-            //     + self.blocks[dragging].parts.last().unwrap().bottom_offset
-            //     + notch_offset;
+        for monitor in &mut self.monitors {
+            monitor.ui(ui, origin, editor_rect, &self.variables);
+        }
 
-            // todo: support multiple nexts
-            // TODO: add logic for "top" attaching
-            // TODO: add logic for "between" attaching
-            // TODO: add logic for "bottom of stack" attaching
-            // attaches dragging block to other block
+        if let Some(dragging) = dragging {
+            self.detach(dragging);
+            self.blocks[dragging].snap = self.find_snap(dragging, &style);
+        }
 
-            // for (index, block) in &mut self.blocks {}
+        if let Some((index, target)) = dropped {
+            self.attach(index, target);
+        }
 
-            self.blocks[dragging].snap_target = None;
-            if let Some((closest, _distance)) = self
-                .blocks
-                .iter()
-                .filter(|(index, _block)| *index != dragging)
-                .map(|(index, other_block)| {
-                    let other_attachment_position = other_block.position
-                        + other_block.parts.last().unwrap().bottom_offset
-                        + notch_offset;
-
-                    let dist = dragging_top_attachment.distance(other_attachment_position);
-                    (index, dist)
-                })
-                .filter(|(_index, distance)| *distance < 30.0)
-                .min_by(|(_index_a, a), (_index_b, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-            {
-                self.blocks[dragging].snap_target = Some(closest);
-            }
-        }
-
-        if let Some(SetNext {
-            upper_block,
-            upper_part,
-            next_block,
-        }) = set_next
-        {
-            let upper_next = &mut self.blocks[upper_block].parts[upper_part].next;
-            if matches!(upper_next, Next::None) {
-                *upper_next = Next::Some {
-                    index: next_block,
-                    height: 0.0,
-                };
-            }
+        if let Some(index) = toggled_breakpoint {
+            let breakpoint = self.has_breakpoint(index);
+            self.set_breakpoint(index, !breakpoint);
         }
 
-        // TODO: sort to avoid "tearing"
-        // TODO: support multiple nexts
-        let child_update: Vec<_> = self
-            .blocks
-            .iter()
-            .filter_map(|(index, block)| match block.parts[0].next {
-                Next::Some { index: next, .. } => Some((index, next)),
-                _ => None,
-            })
-            .collect();
+        // clicking a block starts the script it's part of
+        // nothing runs while replaying
+        if let Some(mut index) = clicked.filter(|_index| !self.is_replaying()) {
+            while let Some(&parent) = parents.get(&index) {
+                index = parent;
+            }
 
-        for (upper, next) in child_update {
-            self.blocks[next].position = self.blocks[upper].position
-                + self.blocks[upper].parts.last().unwrap().bottom_offset;
+            self.start_script(index);
         }
 
-        // TODO: recalculate height
-        // TODO: make this conditional?
+        self.tick();
+        if !self.threads.is_empty() && !self.debugger.paused {
+            ui.ctx().request_repaint();
+        }
 
         response
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::WHEN_GAME_STARTS,
+        save::tests::{number, text, Workspace},
+        standard::{REPEAT, STOP},
+        variable::{SET_VARIABLE, VARIABLE},
+    };
+
+    /// Has as many branches as its mutation says.
+    struct Branches(usize);
+
+    impl Block for Branches {
+        fn opcode(&self) -> &str {
+            "test_branches"
+        }
+
+        fn describe(&mut self) -> BlockDescription {
+            BlockDescription {
+                shape: BlockShape::C {
+                    branches: NonZeroUsize::new(self.0).unwrap(),
+                },
+                category: BlockCategory::Control,
+                color: None,
+                content: vec![Vec::new(); self.0 + 1],
+                output: ValueType::Any,
+            }
+        }
+
+        fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+            Ok(())
+        }
+
+        fn mutation(&self) -> Option<String> {
+            Some(self.0.to_string())
+        }
+
+        fn set_mutation(&mut self, mutation: &str) {
+            self.0 = mutation.parse().unwrap();
+        }
+    }
+
+    #[test]
+    fn remove_block() {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("x"))]);
+        let join = workspace.block("operator_join", &[]);
+        let variable = workspace.block(VARIABLE, &[("variable", text("x"))]);
+        workspace.input(set, "value", join);
+        workspace.input(join, "b", variable);
+        let repeat = workspace.c_block(REPEAT, &[("times", number(3.0))], &[&[set]]);
+        let stop = workspace.block(STOP, &[]);
+        workspace.stack(&[hat, repeat, stop]);
+
+        let mut editor = workspace.load();
+        // a fresh arena hands out its slots in order
+        let indices: Vec<Index> = editor.blocks.iter().map(|(index, _block)| index).collect();
+        editor.debugger.breakpoints.insert(indices[variable]);
+
+        assert!(editor.remove_block(indices[repeat]).is_some());
+        let opcodes: Vec<&str> = editor
+            .blocks
+            .iter()
+            .map(|(_index, block)| block.implementation.opcode())
+            .collect();
+        assert_eq!(opcodes, [WHEN_GAME_STARTS, STOP]);
+        assert!(matches!(
+            editor.blocks[indices[hat]].parts[0].next,
+            Next::Some { index, .. } if index == indices[stop]
+        ));
+        assert!(editor.debugger.breakpoints.is_empty());
+        assert!(editor.remove_block(indices[set]).is_none());
+    }
+
+    #[test]
+    fn mutate() {
+        let mut editor = BlockEditor::default();
+        let index = editor.add_block(Pos2::ZERO, Branches(1));
+        assert!(matches!(
+            editor.mutate(index, "2"),
+            Err(ProcedureError::PartCount {
+                expected: 2,
+                found: 3,
+                ..
+            })
+        ));
+        assert_eq!(
+            editor.blocks[index].implementation.mutation().as_deref(),
+            Some("1")
+        );
+        assert!(editor.mutate(index, "1").is_ok());
+    }
+}
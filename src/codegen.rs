@@ -0,0 +1,791 @@
+//! Generates Rust source code from the scripts of a [`BlockEditor`].
+//!
+//! Scripts and procedures become methods of a struct holding the variables and lists, the
+//! method `broadcast` runs the scripts receiving a message. Blocks supply their code through
+//! [`Block::rust`](crate::block::Block::rust), a template in which
+//!
+//! - `%{key}` is the widget or input `key` as a [`Value`], `%{key:number}`, `%{key:text}` and
+//!   `%{key:bool}` convert it, variable and list dropdowns are their fields
+//! - `%{0}`, `%{1}`, ... are the branches of C blocks
+//! - `%%` is a literal `%`
+//!
+//! Templates of hats name their methods. The code can use `Value`, `RunError`, `standard`,
+//! `variable`, `ListIndex` and `host`, the [`Host`] of the program. Reporters using `host` are
+//! evaluated in a `let` before the line they're in.
+
+use crate::{
+    block::{BlockEditor, BlockShape, BlockWidget, DropdownOptions, Next, RunError},
+    event,
+    procedure::{self, Prototype, PrototypeElement},
+    value::Value,
+};
+use std::{collections::HashSet, fmt::Write};
+use thunderdome::Index;
+
+/// What the generated code can't do by itself.
+pub trait Host {
+    /// Runs a block without a template, `fields` are the values of its widgets in order.
+    fn run(&mut self, opcode: &str, fields: &[(&str, Value)]) -> Result<(), RunError>;
+
+    /// Value of a reporter without a template.
+    fn report(&mut self, opcode: &str, fields: &[(&str, Value)]) -> Result<Value, RunError>;
+
+    /// Uniformly distributed in `0.0..1.0`, like
+    /// [`BlockFields::random`](crate::block::BlockFields::random).
+    fn random(&mut self) -> f64;
+
+    fn wait(&mut self, _seconds: f64) {}
+
+    /// `option` is an option of the stop block, the script or procedure returns afterwards.
+    /// An error also stops the scripts calling the procedure.
+    fn stop(&mut self, _option: &str) -> Result<(), RunError> {
+        Ok(())
+    }
+}
+
+/// Imported by the generated code, see the [module](self) documentation.
+const IMPORTS: &str = "\
+#[allow(unused_imports)]
+use egui_block_programming::{
+    block::RunError,
+    codegen::Host,
+    standard,
+    value::Value,
+    variable::{self, ListIndex},
+};
+";
+
+/// Names the generated code uses itself.
+const RESERVED: [&str; 6] = ["host", "standard", "variable", "default", "self", "super"];
+
+/// Strict and reserved keywords.
+const KEYWORDS: [&str; 49] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// Method running the scripts receiving a message.
+const BROADCAST: &str = "broadcast";
+
+/// A procedure the generated code calls.
+struct Method {
+    name: String,
+    /// names of the parameters and of their arguments
+    parameters: Vec<(String, String)>,
+}
+
+struct Generator<'a> {
+    editor: &'a BlockEditor,
+    /// variables and lists by name, with the names of their fields
+    variables: Vec<(String, String)>,
+    lists: Vec<(String, String)>,
+    fields: HashSet<String>,
+    methods: HashSet<String>,
+    procedures: Vec<(String, Method)>,
+    /// parameters of the procedure being generated
+    parameters: Vec<(String, String)>,
+    /// `let` statements of reporters using the host, which come before the current line
+    hoisted: Vec<String>,
+    temporaries: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Template {
+    /// identifiers take the place of the widgets
+    Hat,
+    Statement,
+    Expression,
+}
+
+impl BlockEditor {
+    /// Rust source code of a struct called `name` running the scripts of the editor, see the
+    /// [`codegen`](crate::codegen) module.
+    pub fn generate_rust(&self, name: &str) -> String {
+        let mut generator = Generator {
+            editor: self,
+            variables: Vec::new(),
+            lists: Vec::new(),
+            fields: HashSet::new(),
+            methods: HashSet::new(),
+            procedures: Vec::new(),
+            parameters: Vec::new(),
+            hoisted: Vec::new(),
+            temporaries: 0,
+        };
+        generator.methods.insert(BROADCAST.to_string());
+        for name in self.variables.names() {
+            generator.variable(name);
+        }
+        for name in self.variables.list_names() {
+            generator.list(name);
+        }
+
+        let definitions: Vec<(Index, Prototype)> = self
+            .blocks
+            .iter()
+            .filter(|(_index, block)| block.implementation.opcode() == procedure::DEFINITION)
+            .filter_map(|(index, block)| {
+                Some((
+                    index,
+                    Prototype::parse(&block.implementation.mutation()?).ok()?,
+                ))
+            })
+            .collect();
+        for (_index, prototype) in &definitions {
+            let key = prototype.to_string();
+            if generator
+                .procedures
+                .iter()
+                .any(|(other, _method)| *other == key)
+            {
+                continue;
+            }
+
+            let label: Vec<&str> = prototype
+                .elements
+                .iter()
+                .filter_map(|element| match element {
+                    PrototypeElement::Label(label) => Some(label.as_str()),
+                    PrototypeElement::Parameter { .. } => None,
+                })
+                .collect();
+            let name = unique(&mut generator.methods, identifier(&label.join(" ")));
+            let mut arguments = HashSet::new();
+            let parameters = prototype
+                .parameters()
+                .map(|(parameter, _kind)| {
+                    let argument = unique(&mut arguments, identifier(parameter));
+                    (parameter.to_string(), argument)
+                })
+                .collect();
+            generator
+                .procedures
+                .push((key, Method { name, parameters }));
+        }
+
+        let mut methods = Vec::new();
+        // lowercase messages with the methods of the scripts receiving them
+        let mut receivers: Vec<(String, String)> = Vec::new();
+        for (index, block) in &self.blocks {
+            if !matches!(block.description.shape, BlockShape::Hat)
+                || block.implementation.opcode() == procedure::DEFINITION
+            {
+                continue;
+            }
+
+            let name = match block.implementation.rust() {
+                Some(template) => generator.expand(index, template, Template::Hat),
+                None => identifier(block.implementation.opcode()),
+            };
+            let name = unique(&mut generator.methods, name);
+            if block.implementation.opcode() == event::WHEN_I_RECEIVE {
+                let message = block.values.get("message").map(Value::to_text);
+                let message = message.unwrap_or_default().to_lowercase();
+                receivers.push((message, name.clone()));
+            }
+
+            let body = generator.stack(generator.next(index, block.parts.len() - 1));
+            methods.push(method(&name, "", &body));
+        }
+
+        let mut body = String::from("let message = message.to_lowercase();");
+        for (message, name) in &receivers {
+            write!(
+                body,
+                "\nif message == {message:?} {{\n    self.{name}(host)?;\n}}"
+            )
+            .unwrap();
+        }
+        methods.push(method(BROADCAST, "message: &str, ", &body));
+
+        let mut generated = HashSet::new();
+        for (index, prototype) in definitions {
+            // only the first definition of a prototype is called
+            let key = prototype.to_string();
+            if !generated.insert(key.clone()) {
+                continue;
+            }
+
+            let (_key, procedure) = generator
+                .procedures
+                .iter()
+                .find(|(other, _method)| *other == key)
+                .unwrap();
+            let name = procedure.name.clone();
+            generator.parameters = procedure.parameters.clone();
+            let arguments: String = generator
+                .parameters
+                .iter()
+                .map(|(_parameter, argument)| format!("{argument}: Value, "))
+                .collect();
+            let part = self.blocks[index].parts.len() - 1;
+            let body = generator.stack(generator.next(index, part));
+            methods.push(method(&name, &arguments, &body));
+            generator.parameters.clear();
+        }
+
+        let mut code = format!("// Generated from a block workspace.\n\n{IMPORTS}\n");
+        writeln!(code, "#[derive(Clone, Debug)]\npub struct {name} {{").unwrap();
+        for (_name, field) in &generator.variables {
+            writeln!(code, "    pub {field}: Value,").unwrap();
+        }
+        for (_name, field) in &generator.lists {
+            writeln!(code, "    pub {field}: Vec<Value>,").unwrap();
+        }
+        code.push_str("}\n\n");
+
+        writeln!(code, "impl Default for {name} {{").unwrap();
+        writeln!(code, "    fn default() -> Self {{\n        {name} {{").unwrap();
+        for (variable, field) in &generator.variables {
+            let value = self.variables.get(variable).cloned().unwrap_or_default();
+            writeln!(code, "            {field}: {},", value_literal(&value)).unwrap();
+        }
+        for (list, field) in &generator.lists {
+            let items = self.variables.list(list).cloned().unwrap_or_default();
+            let items: Vec<String> = items.iter().map(value_literal).collect();
+            writeln!(code, "            {field}: vec![{}],", items.join(", ")).unwrap();
+        }
+        code.push_str("        }\n    }\n}\n\n");
+
+        writeln!(code, "#[allow(unreachable_code, unused_variables)]").unwrap();
+        writeln!(code, "impl {name} {{\n{}}}", methods.join("\n")).unwrap();
+        code
+    }
+}
+
+impl Generator<'_> {
+    /// Field of a variable, variables which don't exist get one as well.
+    fn variable(&mut self, name: &str) -> String {
+        if let Some((_name, field)) = self.variables.iter().find(|(other, _)| other == name) {
+            return field.clone();
+        }
+
+        let field = unique(&mut self.fields, identifier(name));
+        self.variables.push((name.to_string(), field.clone()));
+        field
+    }
+
+    fn list(&mut self, name: &str) -> String {
+        if let Some((_name, field)) = self.lists.iter().find(|(other, _)| other == name) {
+            return field.clone();
+        }
+
+        let field = unique(&mut self.fields, identifier(name));
+        self.lists.push((name.to_string(), field.clone()));
+        field
+    }
+
+    /// First block attached to a part of a block.
+    fn next(&self, index: Index, part: usize) -> Option<Index> {
+        match self.editor.blocks[index].parts.get(part)?.next {
+            Next::Some { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Statements of a stack of blocks, separated by newlines.
+    fn stack(&mut self, mut next: Option<Index>) -> String {
+        let mut statements = Vec::new();
+        while let Some(index) = next {
+            let statement = self.statement(index);
+            // the statement isn't a template placing them itself
+            statements.append(&mut self.hoisted);
+            statements.push(statement);
+            next = self.next(index, self.editor.blocks[index].parts.len() - 1);
+        }
+
+        statements.join("\n")
+    }
+
+    fn statement(&mut self, index: Index) -> String {
+        let block = &self.editor.blocks[index];
+        match block.implementation.opcode() {
+            procedure::CALL => {
+                let prototype = block.implementation.mutation().unwrap_or_default();
+                let Some((_key, procedure)) = self
+                    .procedures
+                    .iter()
+                    .find(|(key, _method)| *key == prototype)
+                else {
+                    let error = procedure::ProcedureError::Unknown(prototype).to_string();
+                    return format!("return Err(RunError::new({error:?}));");
+                };
+
+                let name = procedure.name.clone();
+                let parameters = procedure.parameters.clone();
+                let arguments: String = parameters
+                    .iter()
+                    .map(|(parameter, _argument)| self.operand(index, parameter, None) + ", ")
+                    .collect();
+                format!("self.{name}({arguments}host)?;")
+            }
+            procedure::DEFINITION => String::new(),
+            _ => match block.description.shape {
+                BlockShape::Reporter | BlockShape::Boolean => {
+                    format!("let _ = {};", self.expression(index))
+                }
+                _ => match block.implementation.rust() {
+                    Some(template) => self.expand(index, template, Template::Statement),
+                    None => format!("host.run({})?;", self.host_arguments(index)),
+                },
+            },
+        }
+    }
+
+    /// Expression of a reporter, a [`Value`].
+    fn expression(&mut self, index: Index) -> String {
+        let block = &self.editor.blocks[index];
+        match block.implementation.opcode() {
+            procedure::PARAMETER | procedure::BOOLEAN_PARAMETER => {
+                let name = block.implementation.mutation();
+                match self
+                    .parameters
+                    .iter()
+                    .find(|(parameter, _argument)| Some(parameter) == name.as_ref())
+                {
+                    Some((_parameter, argument)) => format!("{argument}.clone()"),
+                    // Scratch reports 0 outside of procedures
+                    None => "Value::from(0.0)".to_string(),
+                }
+            }
+            _ => match block.implementation.rust() {
+                Some(template) => self.expand(index, template, Template::Expression),
+                None => format!("host.report({})?", self.host_arguments(index)),
+            },
+        }
+    }
+
+    /// Arguments of [`Host::run`] and [`Host::report`].
+    fn host_arguments(&mut self, index: Index) -> String {
+        let block = &self.editor.blocks[index];
+        let keys: Vec<String> = block
+            .description
+            .content
+            .iter()
+            .flatten()
+            .filter_map(BlockWidget::key)
+            .map(str::to_string)
+            .collect();
+        let fields: Vec<String> = keys
+            .iter()
+            .map(|key| format!("({key:?}, {})", self.operand(index, key, None)))
+            .collect();
+        let opcode = block.implementation.opcode();
+        format!("{opcode:?}, &[{}]", fields.join(", "))
+    }
+
+    /// Code of `%{key:kind}` in the template of a block.
+    fn operand(&mut self, index: Index, key: &str, kind: Option<&str>) -> String {
+        let block = &self.editor.blocks[index];
+        let value = block.values.get(key).cloned().unwrap_or_default();
+        let dropdown = block
+            .description
+            .content
+            .iter()
+            .flatten()
+            .find_map(|widget| match widget {
+                BlockWidget::Dropdown {
+                    key: other,
+                    options,
+                    ..
+                } if other == key => Some(options),
+                _ => None,
+            });
+        match dropdown {
+            Some(DropdownOptions::Variables) if kind.is_none() => {
+                return format!("self.{}", self.variable(&value.to_text()));
+            }
+            Some(DropdownOptions::Lists) if kind.is_none() => {
+                return format!("self.{}", self.list(&value.to_text()));
+            }
+            _ => (),
+        }
+
+        if let Some(&input) = block.inputs.get(key) {
+            let mut expression = self.expression(input);
+            // the host can't be borrowed again in the arguments of a call of the host
+            if expression.contains("host.") {
+                self.temporaries += 1;
+                let temporary = format!("_reported_{}", self.temporaries);
+                self.hoisted
+                    .push(format!("let {temporary} = {expression};"));
+                expression = temporary;
+            }
+
+            return match kind {
+                Some("number") => format!("{expression}.to_number()"),
+                // a temporary can't be borrowed, e.g. in a `let`
+                Some("text") => format!("{expression}.to_text().into_owned()"),
+                Some("bool") => format!("{expression}.to_bool()"),
+                _ => expression,
+            };
+        }
+
+        match kind {
+            Some("number") => number_literal(value.to_number()),
+            Some("text") => format!("{:?}", value.to_text()),
+            Some("bool") => value.to_bool().to_string(),
+            _ => value_literal(&value),
+        }
+    }
+
+    /// Fills in the template of a block.
+    fn expand(&mut self, index: Index, template: &str, context: Template) -> String {
+        let mut code = String::new();
+        let mut rest = template;
+        while let Some(percent) = rest.find('%') {
+            code.push_str(&rest[..percent]);
+            rest = &rest[percent + 1..];
+
+            if let Some(after) = rest.strip_prefix('%') {
+                code.push('%');
+                rest = after;
+                continue;
+            }
+
+            let Some((placeholder, after)) =
+                rest.strip_prefix('{').and_then(|rest| rest.split_once('}'))
+            else {
+                code.push('%');
+                continue;
+            };
+            rest = after;
+
+            let (key, kind) = match placeholder.split_once(':') {
+                Some((key, kind)) => (key, Some(kind)),
+                None => (placeholder, None),
+            };
+            if context == Template::Hat {
+                let value = self.editor.blocks[index].values.get(key);
+                let text = value.map(|value| value.to_text().into_owned());
+                code.push_str(&identifier(&text.unwrap_or_default()));
+            } else if let Ok(branch) = key.parse::<usize>() {
+                let body = self.stack(self.next(index, branch));
+                let line = &code[code.rfind('\n').map_or(0, |newline| newline + 1)..];
+                let indent = line.len() - line.trim_start().len();
+                if body.is_empty() && line.trim().is_empty() {
+                    // leaves out the line of an empty branch
+                    code.truncate(code.len() - line.len());
+                    rest = rest.strip_prefix('\n').unwrap_or(rest);
+                } else {
+                    let newline = format!("\n{}", &line[..indent]);
+                    code.push_str(&body.replace('\n', &newline));
+                }
+            } else {
+                let operand = self.operand(index, key, kind);
+                if context == Template::Statement && !self.hoisted.is_empty() {
+                    let start = code.rfind('\n').map_or(0, |newline| newline + 1);
+                    let line = &code[start..];
+                    let indent = line[..line.len() - line.trim_start().len()].to_string();
+                    let hoisted: String = self
+                        .hoisted
+                        .drain(..)
+                        .map(|hoisted| format!("{indent}{hoisted}\n"))
+                        .collect();
+                    code.insert_str(start, &hoisted);
+                }
+
+                code.push_str(&operand);
+            }
+        }
+
+        code.push_str(rest);
+        code
+    }
+}
+
+/// A method running `body`, `arguments` come before the host.
+fn method(name: &str, arguments: &str, body: &str) -> String {
+    let mut code = format!(
+        "    pub fn {name}(&mut self, {arguments}host: &mut impl Host) -> Result<(), RunError> {{\n"
+    );
+    for line in body.lines() {
+        if line.trim().is_empty() {
+            code.push('\n');
+        } else {
+            writeln!(code, "        {line}").unwrap();
+        }
+    }
+    code.push_str("        Ok(())\n    }\n");
+    code
+}
+
+/// Snake case identifier resembling `text`.
+fn identifier(text: &str) -> String {
+    let mut identifier = String::new();
+    for char in text.chars() {
+        if char.is_ascii_alphanumeric() {
+            identifier.push(char.to_ascii_lowercase());
+        } else if !identifier.is_empty() && !identifier.ends_with('_') {
+            identifier.push('_');
+        }
+    }
+
+    let mut identifier = identifier.trim_end_matches('_').to_string();
+    if identifier.is_empty() || identifier.starts_with(|char: char| char.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    if KEYWORDS.contains(&identifier.as_str()) || RESERVED.contains(&identifier.as_str()) {
+        identifier.push('_');
+    }
+
+    identifier
+}
+
+/// `name`, followed by a number if it's used already.
+fn unique(used: &mut HashSet<String>, name: String) -> String {
+    let mut unique = name.clone();
+    let mut number = 2;
+    while !used.insert(unique.clone()) {
+        unique = format!("{name}_{number}");
+        number += 1;
+    }
+
+    unique
+}
+
+/// An `f64` which can be followed by a method call.
+fn number_literal(number: f64) -> String {
+    if number.is_nan() {
+        "f64::NAN".to_string()
+    } else if number == f64::INFINITY {
+        "f64::INFINITY".to_string()
+    } else if number == f64::NEG_INFINITY {
+        "f64::NEG_INFINITY".to_string()
+    } else if number.is_sign_negative() {
+        format!("({number:?}_f64)")
+    } else {
+        format!("{number:?}_f64")
+    }
+}
+
+fn value_literal(value: &Value) -> String {
+    match value {
+        Value::Number(number) if number.is_finite() => format!("Value::from({number:?})"),
+        Value::Number(number) => format!("Value::from({})", number_literal(*number)),
+        Value::Text(text) => format!("Value::from({text:?})"),
+        Value::Bool(bool) => format!("Value::from({bool})"),
+        Value::Color(color) => {
+            let [r, g, b, a] = color.to_array();
+            format!(
+                "Value::Color(eframe::epaint::Color32::from_rgba_premultiplied({r}, {g}, {b}, {a}))"
+            )
+        }
+        Value::List(items) => {
+            let items: Vec<String> = items.iter().map(value_literal).collect();
+            format!("Value::List(vec![{}])", items.join(", "))
+        }
+        Value::Custom(_) => "Value::default()".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::{BROADCAST, WHEN_GAME_STARTS, WHEN_I_RECEIVE},
+        procedure::{BOOLEAN_PARAMETER, CALL, DEFINITION, PARAMETER},
+        registry::BlockRegistry,
+        save::tests::{number, text, Workspace},
+        standard,
+        value::ValueType,
+        variable::{CHANGE_VARIABLE, SET_VARIABLE},
+    };
+    use std::{env, fs, path::PathBuf, process::Command};
+
+    const PROTOTYPE: &str = "do %s[text] %b[flag]";
+
+    fn registry() -> BlockRegistry {
+        let mut registry = BlockRegistry::default();
+        standard::register(&mut registry);
+        registry
+    }
+
+    /// A block with a variable and a list in its dropdowns, a hat receiving "go".
+    fn block(workspace: &mut Workspace, opcode: &str, branches: usize) -> usize {
+        let fields = [
+            ("variable", text("x")),
+            ("list", text("l")),
+            ("message", text("go")),
+            ("key", text("space")),
+        ];
+        let bodies: Vec<usize> = (0..branches).map(|_| change(workspace)).collect();
+        let stacks: Vec<&[usize]> = bodies.iter().map(std::slice::from_ref).collect();
+        workspace.c_block(opcode, &fields, &stacks)
+    }
+
+    fn change(workspace: &mut Workspace) -> usize {
+        workspace.block(
+            CHANGE_VARIABLE,
+            &[("variable", text("x")), ("value", number(1.0))],
+        )
+    }
+
+    /// A reporter calling the host, with a text from a reporter in it.
+    fn reporter(workspace: &mut Workspace, accepts: &ValueType) -> Option<usize> {
+        let random = workspace.block("operator_random", &[]);
+        let (opcode, key) = match accepts {
+            ValueType::Number => return Some(random),
+            ValueType::Bool => ("operator_contains", "text"),
+            ValueType::Any | ValueType::Text => ("operator_join", "a"),
+            _ => return None,
+        };
+        let join = workspace.block("operator_join", &[]);
+        workspace.input(join, "a", random);
+        let reporter = workspace.block(opcode, &[]);
+        workspace.input(reporter, key, join);
+        Some(reporter)
+    }
+
+    /// Puts reporters into every input of a block.
+    fn fill(workspace: &mut Workspace, registry: &BlockRegistry, block: usize) {
+        let opcode = workspace.0.blocks[block].opcode.clone();
+        let description = registry.create(&opcode).unwrap().describe();
+        for widget in description.content.iter().flatten() {
+            if let BlockWidget::Dropdown {
+                options: DropdownOptions::Variables | DropdownOptions::Lists,
+                ..
+            } = widget
+            {
+                continue;
+            }
+
+            let (Some(key), Some(accepts)) = (widget.key(), widget.accepts()) else {
+                continue;
+            };
+            if let Some(reporter) = reporter(workspace, &accepts) {
+                workspace.input(block, key, reporter);
+            }
+        }
+    }
+
+    /// Every block of the standard library and of the editor in a script of its own, so none of
+    /// them is unreachable, with reporters in their inputs.
+    fn every_block() -> Workspace {
+        let registry = registry();
+        let mut opcodes: Vec<&str> = registry.opcodes().collect();
+        opcodes.sort();
+
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        workspace.list("l", Vec::new());
+        for opcode in opcodes {
+            if [DEFINITION, CALL, PARAMETER, BOOLEAN_PARAMETER].contains(&opcode) {
+                continue;
+            }
+
+            let shape = registry.create(opcode).unwrap().describe().shape;
+            match shape {
+                BlockShape::Hat => {
+                    let hat = block(&mut workspace, opcode, 0);
+                    let body = change(&mut workspace);
+                    workspace.stack(&[hat, body]);
+                }
+                BlockShape::Reporter | BlockShape::Boolean => {
+                    let reporter = block(&mut workspace, opcode, 0);
+                    fill(&mut workspace, &registry, reporter);
+                    let set = workspace.block(SET_VARIABLE, &[("variable", text("x"))]);
+                    workspace.input(set, "value", reporter);
+                    let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+                    workspace.stack(&[hat, set]);
+                }
+                BlockShape::Stack | BlockShape::C { .. } | BlockShape::Cap => {
+                    let branches = match shape {
+                        BlockShape::C { branches } => branches.get(),
+                        _ => 0,
+                    };
+                    let block = block(&mut workspace, opcode, branches);
+                    fill(&mut workspace, &registry, block);
+                    let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+                    workspace.stack(&[hat, block]);
+                }
+            }
+        }
+
+        let call = workspace.block(CALL, &[]);
+        workspace.mutation(call, PROTOTYPE);
+        fill(&mut workspace, &registry, call);
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        workspace.stack(&[hat, call]);
+
+        let definition = workspace.block(DEFINITION, &[]);
+        workspace.mutation(definition, PROTOTYPE);
+        let parameter = workspace.block(PARAMETER, &[]);
+        workspace.mutation(parameter, "text");
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("x"))]);
+        workspace.input(set, "value", parameter);
+        let flag = workspace.block(BOOLEAN_PARAMETER, &[]);
+        workspace.mutation(flag, "flag");
+        let branch = change(&mut workspace);
+        let check = workspace.c_block(standard::IF, &[], &[&[branch]]);
+        workspace.input(check, "condition", flag);
+        workspace.stack(&[definition, set, check]);
+
+        let receive = workspace.block(WHEN_I_RECEIVE, &[("message", text("other"))]);
+        let body = change(&mut workspace);
+        workspace.stack(&[receive, body]);
+        workspace
+    }
+
+    /// A crate next to the build directory depending on this one, sharing its dependencies.
+    fn build(code: &str) -> Result<(), String> {
+        let manifest = env!("CARGO_MANIFEST_DIR");
+        let target = env::var_os("CARGO_TARGET_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(manifest).join("target"));
+        let dir = target.join("codegen-test");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"generated\"\nversion = \"0.0.0\"\nedition = \"2021\"\n\n\
+                 [dependencies]\negui-block-programming = {{ path = {manifest:?} }}\n\
+                 eframe = \"0.27\"\n\n[workspace]\n"
+            ),
+        )
+        .unwrap();
+        if let Ok(lock) = fs::read(PathBuf::from(manifest).join("Cargo.lock")) {
+            fs::write(dir.join("Cargo.lock"), lock).unwrap();
+        }
+        fs::write(
+            dir.join("src/lib.rs"),
+            format!("#![deny(warnings)]\n\n{code}"),
+        )
+        .unwrap();
+
+        let output = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            .args(["build", "--offline", "--quiet"])
+            .current_dir(&dir)
+            .env("CARGO_TARGET_DIR", &target)
+            .output()
+            .unwrap();
+        match output.status.success() {
+            true => Ok(()),
+            false => Err(String::from_utf8_lossy(&output.stderr).into_owned()),
+        }
+    }
+
+    #[test]
+    fn generated_code_compiles() {
+        let code = every_block().load().generate_rust("Program");
+        if let Err(errors) = build(&code) {
+            panic!("{code}\n{errors}");
+        }
+    }
+
+    #[test]
+    fn broadcasts_call_the_receivers() {
+        let mut workspace = Workspace::default();
+        let hat = workspace.block(WHEN_I_RECEIVE, &[("message", text("Go"))]);
+        let broadcast = workspace.block(BROADCAST, &[("message", text("other"))]);
+        workspace.stack(&[hat, broadcast]);
+        let code = workspace.load().generate_rust("Program");
+
+        assert!(code.contains("self.broadcast(&\"other\", host)?;"));
+        assert!(code.contains("if message == \"go\" {\n            self.when_i_receive_go(host)?;"));
+        assert!(!code.contains("self.when_i_receive_other"));
+    }
+}
@@ -0,0 +1,548 @@
+//! Compiles the scripts of a [`BlockEditor`] to bytecode for the [`Vm`](crate::vm::Vm).
+//!
+//! A [`Program`] is a snapshot of the workspace, it has to be compiled again after the blocks
+//! were edited.
+
+use crate::{
+    block::{BlockEditor, BlockShape, BlockWidget, Next},
+    procedure::{self, ProcedureError, Prototype},
+    standard::{self, Operator},
+    value::Value,
+    variable,
+};
+use std::{borrow::Cow, collections::HashMap};
+use thunderdome::Index;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Instruction {
+    /// pushes one of [`Program::constants`]
+    Constant(u32),
+    Pop,
+    Variable(u32),
+    SetVariable(u32),
+    ChangeVariable(u32),
+    /// pops the item and the index, `list` is `None` for lists which don't exist
+    List {
+        opcode: &'static str,
+        list: Option<u32>,
+        /// the index was pushed before the item, e.g. in "replace item"
+        index_first: bool,
+    },
+    /// argument of the procedure being run
+    Argument(u32),
+    /// pops both operands
+    Operator {
+        operator: Operator,
+        /// the second operand was pushed before the first one
+        b_first: bool,
+    },
+    Not,
+    /// jumps are relative to the jump instruction
+    Jump(i32),
+    JumpIfFalse(i32),
+    JumpIfTrue(i32),
+    /// rounds the number of times on top, pops it and jumps if it's less than 1
+    RepeatStart(i32),
+    /// counts down the number of times on top, yields and jumps back if it's still at least
+    /// 1, pops it otherwise
+    RepeatNext(i32),
+    /// another iteration of a loop, which counts against the limits like a block
+    Yield,
+    /// start of a block of a stack, which pauses at breakpoints and counts against the limits
+    Step,
+    /// start of a reporter or procedure definition, which counts against the limits
+    Spend,
+    /// pops the duration in seconds
+    Wait,
+    Stop(StopOption),
+    /// pops the message
+    Broadcast,
+    BroadcastAndWait,
+    /// one of [`Program::procedures`], pops its arguments
+    Call(u32),
+    /// end of a procedure
+    Return,
+    /// end of a script
+    End,
+    /// one of [`Program::blocks`], pops the values of its inputs
+    Run(u32),
+    Report(u32),
+    /// fails with a text from [`Program::constants`]
+    Fail(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StopOption {
+    All,
+    OtherScripts,
+    ThisScript,
+}
+
+/// A script below a hat block, started by events.
+pub(crate) struct Script {
+    pub hat: Index,
+    pub opcode: String,
+    /// values of the widgets of the hat, for [`Event::triggers`](crate::event::Event)
+    pub values: HashMap<Cow<'static, str>, Value>,
+    pub start: usize,
+}
+
+pub(crate) struct Procedure {
+    pub start: usize,
+    /// number of parameters
+    pub arguments: usize,
+}
+
+/// A block run through its implementation.
+pub(crate) struct CompiledBlock {
+    pub index: Index,
+    /// values of the widgets when the program was compiled
+    pub values: HashMap<Cow<'static, str>, Value>,
+    /// keys of the inputs, in the order their values are pushed
+    pub inputs: Vec<Cow<'static, str>>,
+}
+
+/// The scripts of a workspace, see [`BlockEditor::compile`].
+pub struct Program {
+    pub(crate) code: Vec<Instruction>,
+    /// the block each instruction was compiled from
+    pub(crate) origins: Vec<Index>,
+    pub(crate) constants: Vec<Value>,
+    /// names of the variable slots
+    pub(crate) variables: Vec<String>,
+    /// names of the list slots
+    pub(crate) lists: Vec<String>,
+    pub(crate) scripts: Vec<Script>,
+    pub(crate) procedures: Vec<Procedure>,
+    pub(crate) blocks: Vec<CompiledBlock>,
+}
+
+impl Program {
+    /// number of instructions
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+}
+
+struct Compiler<'a> {
+    editor: &'a BlockEditor,
+    program: Program,
+    /// procedures by prototype
+    procedures: HashMap<String, u32>,
+    /// parameters of the procedure being compiled
+    parameters: Vec<String>,
+    /// block the instructions being emitted belong to
+    origin: Index,
+}
+
+impl BlockEditor {
+    /// Compiles every script below a hat block and every procedure.
+    pub fn compile(&self) -> Program {
+        let mut compiler = Compiler {
+            editor: self,
+            program: Program {
+                code: Vec::new(),
+                origins: Vec::new(),
+                constants: Vec::new(),
+                variables: self.variables.names().map(str::to_string).collect(),
+                lists: self.variables.list_names().map(str::to_string).collect(),
+                scripts: Vec::new(),
+                procedures: Vec::new(),
+                blocks: Vec::new(),
+            },
+            procedures: HashMap::new(),
+            parameters: Vec::new(),
+            origin: Index::DANGLING,
+        };
+
+        let definitions: Vec<(Index, Prototype)> = self
+            .blocks
+            .iter()
+            .filter(|(_index, block)| block.implementation.opcode() == procedure::DEFINITION)
+            .filter_map(|(index, block)| {
+                Some((
+                    index,
+                    Prototype::parse(&block.implementation.mutation()?).ok()?,
+                ))
+            })
+            .collect();
+        for (id, (_index, prototype)) in definitions.iter().enumerate() {
+            compiler
+                .procedures
+                .entry(prototype.to_string())
+                .or_insert(id as u32);
+            compiler.program.procedures.push(Procedure {
+                start: 0,
+                arguments: prototype.parameters().count(),
+            });
+        }
+
+        for (index, block) in &self.blocks {
+            let opcode = block.implementation.opcode();
+            if !matches!(block.description.shape, BlockShape::Hat)
+                || opcode == procedure::DEFINITION
+            {
+                continue;
+            }
+
+            compiler.program.scripts.push(Script {
+                hat: index,
+                opcode: opcode.to_string(),
+                values: block.values.clone(),
+                start: compiler.program.code.len(),
+            });
+            // hats run like other blocks
+            compiler.stack(Some(index));
+            compiler.origin = index;
+            compiler.emit(Instruction::End);
+        }
+
+        for (id, (index, prototype)) in definitions.into_iter().enumerate() {
+            compiler.program.procedures[id].start = compiler.program.code.len();
+            compiler.parameters = prototype
+                .parameters()
+                .map(|(name, _kind)| name.to_string())
+                .collect();
+            compiler.origin = index;
+            compiler.emit(Instruction::Spend);
+            compiler.stack(compiler.next(index, self.blocks[index].parts.len() - 1));
+            compiler.origin = index;
+            compiler.emit(Instruction::Return);
+        }
+
+        compiler.program
+    }
+}
+
+impl Compiler<'_> {
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.program.code.push(instruction);
+        self.program.origins.push(self.origin);
+        self.program.code.len() - 1
+    }
+
+    fn constant(&mut self, value: Value) {
+        let constant = self.program.constants.len() as u32;
+        self.program.constants.push(value);
+        self.emit(Instruction::Constant(constant));
+    }
+
+    /// Offset from the instruction at `from` to the next instruction being emitted.
+    fn offset(&self, from: usize) -> i32 {
+        (self.program.code.len() - from) as i32
+    }
+
+    /// Points the jump at `jump` to the next instruction being emitted.
+    fn patch(&mut self, jump: usize) {
+        let offset = self.offset(jump);
+        match &mut self.program.code[jump] {
+            Instruction::Jump(target)
+            | Instruction::JumpIfFalse(target)
+            | Instruction::JumpIfTrue(target)
+            | Instruction::RepeatStart(target) => *target = offset,
+            _ => unreachable!(),
+        }
+    }
+
+    /// First block attached to a part of a block.
+    fn next(&self, index: Index, part: usize) -> Option<Index> {
+        match self.editor.blocks[index].parts.get(part)?.next {
+            Next::Some { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    fn stack(&mut self, mut next: Option<Index>) {
+        while let Some(index) = next {
+            self.block(index);
+            next = self.next(index, self.editor.blocks[index].parts.len() - 1);
+        }
+    }
+
+    /// Pushes the values of `keys` and returns them in the order they were pushed. Every input
+    /// of the block is evaluated in the order of its widgets like in the interpreter, also the
+    /// ones which aren't needed.
+    fn operands<'k>(&mut self, index: Index, keys: &[&'k str]) -> Vec<&'k str> {
+        let editor = self.editor;
+        let block = &editor.blocks[index];
+        let mut pushed = Vec::new();
+        for key in block
+            .description
+            .content
+            .iter()
+            .flatten()
+            .filter_map(BlockWidget::key)
+        {
+            let used = keys.iter().find(|other| **other == key);
+            if let Some(&input) = block.inputs.get(key) {
+                self.reporter(input);
+                if used.is_none() {
+                    self.origin = index;
+                    self.emit(Instruction::Pop);
+                    continue;
+                }
+            } else if used.is_some() {
+                self.origin = index;
+                self.constant(block.values[key].clone());
+            } else {
+                continue;
+            }
+
+            pushed.extend(used);
+        }
+
+        // keys the block has no widgets for
+        self.origin = index;
+        for key in keys {
+            if !pushed.contains(key) {
+                self.constant(Value::default());
+                pushed.push(key);
+            }
+        }
+
+        pushed
+    }
+
+    /// Pushes the values of every input of a block run through its implementation.
+    fn compiled_block(&mut self, index: Index) -> u32 {
+        let block = &self.editor.blocks[index];
+        let inputs: Vec<Cow<'static, str>> = block
+            .ordered_inputs()
+            .map(|(key, _input)| key.clone())
+            .collect();
+        for (_key, input) in block.ordered_inputs() {
+            self.reporter(input);
+        }
+
+        self.origin = index;
+        self.program.blocks.push(CompiledBlock {
+            index,
+            values: block.values.clone(),
+            inputs,
+        });
+        (self.program.blocks.len() - 1) as u32
+    }
+
+    fn text(&self, index: Index, key: &str) -> String {
+        self.editor.blocks[index]
+            .values
+            .get(key)
+            .map(|value| value.to_text().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn variable(&self, index: Index) -> Option<u32> {
+        let name = self.text(index, "variable");
+        let slot = self
+            .program
+            .variables
+            .iter()
+            .position(|other| *other == name)?;
+        Some(slot as u32)
+    }
+
+    fn list(&self, index: Index) -> Option<u32> {
+        let name = self.text(index, "list");
+        let slot = self.program.lists.iter().position(|other| *other == name)?;
+        Some(slot as u32)
+    }
+
+    /// Compiles a block of a stack.
+    fn block(&mut self, index: Index) {
+        self.origin = index;
+        self.emit(Instruction::Step);
+        let block = &self.editor.blocks[index];
+        let opcode = block.implementation.opcode();
+        match opcode {
+            procedure::CALL => {
+                let prototype = block.implementation.mutation().unwrap_or_default();
+                let Some(&procedure) = self.procedures.get(&prototype) else {
+                    for (_key, input) in block.ordered_inputs() {
+                        self.reporter(input);
+                        self.origin = index;
+                        self.emit(Instruction::Pop);
+                    }
+
+                    self.origin = index;
+                    let error = ProcedureError::Unknown(prototype).to_string();
+                    let constant = self.program.constants.len() as u32;
+                    self.program.constants.push(error.into());
+                    self.emit(Instruction::Fail(constant));
+                    return;
+                };
+
+                let parameters: Vec<String> = Prototype::parse(&prototype)
+                    .map(|prototype| {
+                        prototype
+                            .parameters()
+                            .map(|(name, _kind)| name.to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let keys: Vec<&str> = parameters.iter().map(String::as_str).collect();
+                self.operands(index, &keys);
+                self.emit(Instruction::Call(procedure));
+            }
+            procedure::DEFINITION => (),
+            standard::IF => {
+                self.operands(index, &["condition"]);
+                let skip = self.emit(Instruction::JumpIfFalse(0));
+                self.stack(self.next(index, 0));
+                self.patch(skip);
+            }
+            standard::IF_ELSE => {
+                self.operands(index, &["condition"]);
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.stack(self.next(index, 0));
+                self.origin = index;
+                let to_end = self.emit(Instruction::Jump(0));
+                self.patch(to_else);
+                self.stack(self.next(index, 1));
+                self.patch(to_end);
+            }
+            standard::REPEAT => {
+                self.operands(index, &["times"]);
+                let start = self.emit(Instruction::RepeatStart(0));
+                let body = self.program.code.len();
+                self.stack(self.next(index, 0));
+                self.origin = index;
+                let next = self.program.code.len();
+                self.emit(Instruction::RepeatNext(body as i32 - next as i32));
+                self.patch(start);
+            }
+            standard::REPEAT_UNTIL => {
+                self.operands(index, &["condition"]);
+                let skip = self.emit(Instruction::JumpIfTrue(0));
+                let body = self.program.code.len();
+                self.stack(self.next(index, 0));
+                self.operands(index, &["condition"]);
+                let done = self.emit(Instruction::JumpIfTrue(0));
+                self.emit(Instruction::Yield);
+                let back = self.program.code.len();
+                self.emit(Instruction::Jump(body as i32 - back as i32));
+                self.patch(skip);
+                self.patch(done);
+            }
+            standard::FOREVER => {
+                let body = self.program.code.len();
+                self.stack(self.next(index, 0));
+                self.origin = index;
+                self.emit(Instruction::Yield);
+                let back = self.program.code.len();
+                self.emit(Instruction::Jump(body as i32 - back as i32));
+            }
+            standard::WAIT => {
+                self.operands(index, &["duration"]);
+                self.emit(Instruction::Wait);
+            }
+            standard::STOP => {
+                let option = match self.text(index, "option").as_str() {
+                    standard::STOP_ALL => StopOption::All,
+                    standard::STOP_OTHER_SCRIPTS => StopOption::OtherScripts,
+                    _ => StopOption::ThisScript,
+                };
+                self.operands(index, &[]);
+                self.emit(Instruction::Stop(option));
+            }
+            crate::event::BROADCAST => {
+                self.operands(index, &["message"]);
+                self.emit(Instruction::Broadcast);
+            }
+            crate::event::BROADCAST_AND_WAIT => {
+                self.operands(index, &["message"]);
+                self.emit(Instruction::BroadcastAndWait);
+            }
+            variable::SET_VARIABLE | variable::CHANGE_VARIABLE => {
+                self.operands(index, &["value"]);
+                let instruction = match (self.variable(index), opcode) {
+                    (Some(slot), variable::SET_VARIABLE) => Instruction::SetVariable(slot),
+                    (Some(slot), _) => Instruction::ChangeVariable(slot),
+                    (None, _) => Instruction::Pop,
+                };
+                self.emit(instruction);
+            }
+            _ if variable::LIST_BLOCKS.contains(&opcode) || opcode == variable::VARIABLE => {
+                self.value(index);
+                self.emit(Instruction::Pop);
+            }
+            _ => {
+                let block = self.compiled_block(index);
+                self.emit(Instruction::Run(block));
+            }
+        }
+    }
+
+    /// Compiles a block pushing a value.
+    fn reporter(&mut self, index: Index) {
+        self.origin = index;
+        self.emit(Instruction::Spend);
+        self.value(index);
+    }
+
+    /// The instructions of a reporter, also used for list blocks in stacks.
+    fn value(&mut self, index: Index) {
+        self.origin = index;
+        let block = &self.editor.blocks[index];
+        let opcode = block.implementation.opcode();
+        match opcode {
+            procedure::PARAMETER | procedure::BOOLEAN_PARAMETER => {
+                let name = block.implementation.mutation();
+                let argument = self
+                    .parameters
+                    .iter()
+                    .position(|parameter| Some(parameter) == name.as_ref());
+                self.operands(index, &[]);
+                match argument {
+                    Some(argument) => {
+                        self.emit(Instruction::Argument(argument as u32));
+                    }
+                    // Scratch reports 0 outside of procedures
+                    None => self.constant(Value::Number(0.0)),
+                }
+            }
+            variable::VARIABLE => {
+                self.operands(index, &[]);
+                match self.variable(index) {
+                    Some(slot) => {
+                        self.emit(Instruction::Variable(slot));
+                    }
+                    None => self.constant(Value::default()),
+                }
+            }
+            _ if variable::LIST_BLOCKS.contains(&opcode) => {
+                let opcode = variable::LIST_BLOCKS
+                    .into_iter()
+                    .find(|other| *other == opcode)
+                    .unwrap();
+                let pushed = self.operands(index, &["item", "index"]);
+                let list = self.list(index);
+                self.emit(Instruction::List {
+                    opcode,
+                    list,
+                    index_first: pushed[0] == "index",
+                });
+            }
+            standard::NOT => {
+                self.operands(index, &["value"]);
+                self.emit(Instruction::Not);
+            }
+            _ => match Operator::from_opcode(opcode) {
+                Some(operator) => {
+                    let pushed = self.operands(index, &["a", "b"]);
+                    self.emit(Instruction::Operator {
+                        operator,
+                        b_first: pushed[0] == "b",
+                    });
+                }
+                None => {
+                    let block = self.compiled_block(index);
+                    self.emit(Instruction::Report(block));
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Rules deciding which blocks may be snapped together in the editor.
+
+use crate::value::ValueType;
+
+/// A reporter being put into an input slot.
+pub struct InputConnection<'a> {
+    /// opcode of the block containing the slot
+    pub parent: &'a str,
+    /// key of the slot
+    pub key: &'a str,
+    pub accepts: &'a ValueType,
+    /// opcode of the reporter
+    pub child: &'a str,
+    pub output: &'a ValueType,
+}
+
+/// A block being attached below a part of another block.
+pub struct NextConnection<'a> {
+    /// opcode of the upper block
+    pub parent: &'a str,
+    /// 0 is the first branch of C blocks, the last part is the bottom of the block
+    pub part: usize,
+    /// opcode of the attached block
+    pub child: &'a str,
+}
+
+/// Game-specific connection rules, snaps which don't pass are shown as rejected.
+pub trait ConnectionChecker {
+    fn check_input(&self, connection: &InputConnection) -> bool {
+        connection.accepts.accepts(connection.output)
+    }
+
+    fn check_next(&self, _connection: &NextConnection) -> bool {
+        true
+    }
+}
+
+/// Only checks the types of input slots, see [`ValueType::accepts`].
+#[derive(Default)]
+pub struct TypeChecker;
+
+impl ConnectionChecker for TypeChecker {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Only allows blocks of the same category below each other.
+    struct SameCategory;
+
+    impl ConnectionChecker for SameCategory {
+        fn check_next(&self, connection: &NextConnection) -> bool {
+            connection.parent.split('_').next() == connection.child.split('_').next()
+        }
+    }
+
+    fn input<'a>(accepts: &'a ValueType, output: &'a ValueType) -> InputConnection<'a> {
+        InputConnection {
+            parent: "control_if",
+            key: "condition",
+            accepts,
+            child: "operator_join",
+            output,
+        }
+    }
+
+    #[test]
+    fn type_checker() {
+        assert!(TypeChecker.check_input(&input(&ValueType::Bool, &ValueType::Bool)));
+        assert!(!TypeChecker.check_input(&input(&ValueType::Bool, &ValueType::Text)));
+        assert!(TypeChecker.check_next(&NextConnection {
+            parent: "control_if",
+            part: 0,
+            child: "motion_move",
+        }));
+    }
+
+    #[test]
+    fn custom_rules() {
+        // the types are still checked
+        assert!(!SameCategory.check_input(&input(&ValueType::Number, &ValueType::Text)));
+
+        let next = |child| NextConnection {
+            parent: "control_if",
+            part: 1,
+            child,
+        };
+        assert!(SameCategory.check_next(&next("control_stop")));
+        assert!(!SameCategory.check_next(&next("motion_move")));
+    }
+}
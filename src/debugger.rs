@@ -0,0 +1,280 @@
+//! Breakpoints, pausing and stepping through the threads of a [`BlockEditor`].
+
+use crate::{block::BlockEditor, interpreter::ThreadId};
+use eframe::egui::{Button, Label, RichText, Sense, Ui};
+use std::collections::HashSet;
+use thunderdome::Index;
+
+#[derive(Default)]
+pub(crate) struct Debugger {
+    pub breakpoints: HashSet<Index>,
+    pub paused: bool,
+    /// the thread which hit a breakpoint or is being stepped through
+    pub thread: Option<ThreadId>,
+    pub stepping: Option<Stepping>,
+}
+
+pub(crate) enum Stepping {
+    /// pauses before the next block, also inside of branches and procedures
+    Into,
+    /// pauses once the thread is back at this number of frames
+    Over { depth: usize },
+}
+
+impl BlockEditor {
+    pub fn has_breakpoint(&self, index: Index) -> bool {
+        self.debugger.breakpoints.contains(&index)
+    }
+
+    pub fn set_breakpoint(&mut self, index: Index, breakpoint: bool) {
+        if breakpoint {
+            self.debugger.breakpoints.insert(index);
+        } else {
+            self.debugger.breakpoints.remove(&index);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.debugger.paused
+    }
+
+    /// The thread stopped by a breakpoint or a step, if it's still running.
+    pub fn paused_thread(&self) -> Option<ThreadId> {
+        let id = self.debugger.thread?;
+        self.threads
+            .iter()
+            .any(|thread| thread.id() == id)
+            .then_some(id)
+    }
+
+    /// Pauses every thread, the first one is the one being stepped through.
+    pub fn pause(&mut self) {
+        self.debugger.paused = true;
+        self.debugger.stepping = None;
+        if self.paused_thread().is_none() {
+            self.debugger.thread = self.threads.first().map(|thread| thread.id());
+        }
+    }
+
+    /// Continues running every thread.
+    pub fn resume(&mut self) {
+        self.debugger.paused = false;
+        self.debugger.stepping = None;
+    }
+
+    /// Runs the next block of the paused thread, pausing again before the block after it,
+    /// which is the first block of a branch or procedure it starts.
+    pub fn step_into(&mut self) {
+        self.step_paused(Stepping::Into);
+    }
+
+    /// Runs the next block of the paused thread including its branches and procedure calls.
+    pub fn step_over(&mut self) {
+        let Some(id) = self.paused_thread() else {
+            return;
+        };
+
+        let thread = self
+            .threads
+            .iter()
+            .find(|thread| thread.id() == id)
+            .unwrap();
+        let depth = thread.depth();
+        self.step_paused(Stepping::Over { depth });
+    }
+
+    fn step_paused(&mut self, stepping: Stepping) {
+        if !self.debugger.paused || self.paused_thread().is_none() {
+            return;
+        }
+
+        self.debugger.paused = false;
+        self.debugger.stepping = Some(stepping);
+    }
+}
+
+/// The errors of failed threads, clicking one scrolls to the block which failed.
+pub fn error_list(ui: &mut Ui, editor: &mut BlockEditor) {
+    let mut focused = None;
+    for error in editor.errors() {
+        let Some(block) = editor.blocks.get(error.block) else {
+            continue;
+        };
+
+        let mut text = format!("{}: {error}", block.implementation.opcode());
+        if !error.call_stack.is_empty() {
+            let depth = error.call_stack.len();
+            text += &format!(" ({depth} procedure calls deep)");
+        }
+        if ui
+            .add(
+                Label::new(RichText::new(text).color(ui.visuals().error_fg_color))
+                    .sense(Sense::click()),
+            )
+            .on_hover_text("show block")
+            .clicked()
+        {
+            focused = Some(error.block);
+        }
+    }
+
+    if let Some(index) = focused {
+        editor.focus_block(index);
+    }
+
+    if ui
+        .add_enabled(!editor.errors().is_empty(), Button::new("Clear"))
+        .clicked()
+    {
+        editor.clear_errors();
+    }
+}
+
+/// Buttons for pausing, stepping and resuming the threads of `editor`.
+pub fn toolbar(ui: &mut Ui, editor: &mut BlockEditor) {
+    ui.horizontal(|ui| {
+        if editor.is_paused() {
+            if ui.button("▶ Continue").clicked() {
+                editor.resume();
+            }
+        } else if ui
+            .add_enabled(!editor.threads().is_empty(), Button::new("⏸ Pause"))
+            .clicked()
+        {
+            editor.pause();
+        }
+
+        let can_step = editor.is_paused() && editor.paused_thread().is_some();
+        ui.add_enabled_ui(can_step, |ui| {
+            if ui.button("Step into").clicked() {
+                editor.step_into();
+            }
+
+            if ui.button("Step over").clicked() {
+                editor.step_over();
+            }
+        });
+
+        let status = match (editor.is_paused(), editor.threads().len()) {
+            (true, _) => "paused".to_string(),
+            (false, 0) => "idle".to_string(),
+            (false, 1) => "1 thread running".to_string(),
+            (false, threads) => format!("{threads} threads running"),
+        };
+        ui.weak(status);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::{Event, WHEN_GAME_STARTS},
+        procedure::{CALL, DEFINITION},
+        save::tests::{number, text, Workspace},
+        variable::CHANGE_VARIABLE,
+    };
+
+    const PROCEDURE: &str = "add twenty";
+
+    /// Changes `x` by 1, calls a procedure changing it by 10 twice, then changes it by 100.
+    fn workspace() -> (Workspace, [usize; 3]) {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        let change = |workspace: &mut Workspace, by| {
+            workspace.block(
+                CHANGE_VARIABLE,
+                &[("variable", text("x")), ("value", number(by))],
+            )
+        };
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let first = change(&mut workspace, 1.0);
+        let call = workspace.block(CALL, &[]);
+        workspace.mutation(call, PROCEDURE);
+        let last = change(&mut workspace, 100.0);
+        workspace.stack(&[hat, first, call, last]);
+
+        let definition = workspace.block(DEFINITION, &[]);
+        workspace.mutation(definition, PROCEDURE);
+        let inner = change(&mut workspace, 10.0);
+        let second = change(&mut workspace, 10.0);
+        workspace.stack(&[definition, inner, second]);
+        (workspace, [call, inner, last])
+    }
+
+    fn x(editor: &BlockEditor) -> f64 {
+        editor.variables().get("x").unwrap().to_number()
+    }
+
+    fn next_block(editor: &BlockEditor) -> Option<Index> {
+        editor.threads()[0].next_block()
+    }
+
+    #[test]
+    fn breakpoints() {
+        let (workspace, [call, inner, last]) = workspace();
+        for step_over in [false, true] {
+            let mut editor = workspace.load();
+            let indices: Vec<Index> = editor.blocks.iter().map(|(index, _block)| index).collect();
+            editor.set_breakpoint(indices[call], true);
+            editor.emit(Event::GameStarts);
+
+            // pauses before the block with the breakpoint
+            editor.tick();
+            assert!(editor.is_paused());
+            assert_eq!(x(&editor), 1.0);
+            assert_eq!(next_block(&editor), Some(indices[call]));
+            assert_eq!(editor.paused_thread(), Some(editor.threads()[0].id()));
+            editor.tick();
+            assert_eq!(x(&editor), 1.0);
+
+            if step_over {
+                // runs the whole procedure
+                editor.step_over();
+                editor.tick();
+                assert!(editor.is_paused());
+                assert_eq!(x(&editor), 21.0);
+                assert_eq!(next_block(&editor), Some(indices[last]));
+            } else {
+                // pauses before the first block of the procedure
+                editor.step_into();
+                editor.tick();
+                assert!(editor.is_paused());
+                assert_eq!(x(&editor), 1.0);
+                assert_eq!(next_block(&editor), Some(indices[inner]));
+                editor.step_into();
+                editor.tick();
+                assert_eq!(x(&editor), 11.0);
+            }
+
+            editor.resume();
+            editor.tick();
+            assert!(!editor.is_paused());
+            assert!(editor.threads().is_empty());
+            assert_eq!(x(&editor), 121.0);
+        }
+    }
+
+    #[test]
+    fn pause() {
+        let (workspace, _blocks) = workspace();
+        let mut editor = workspace.load();
+        editor.emit(Event::GameStarts);
+        editor.pause();
+        editor.tick();
+        assert_eq!(x(&editor), 0.0);
+        // stepping runs one block at a time, starting with the hat
+        editor.step_into();
+        editor.tick();
+        assert_eq!(x(&editor), 0.0);
+        editor.step_into();
+        editor.tick();
+        assert_eq!(x(&editor), 1.0);
+
+        // stepping while running does nothing
+        editor.resume();
+        editor.step_into();
+        editor.tick();
+        assert_eq!(x(&editor), 121.0);
+    }
+}
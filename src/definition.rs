@@ -0,0 +1,484 @@
+use crate::{
+    block::{
+        Block, BlockCategory, BlockDescription, BlockFields, BlockShape, BlockWidget,
+        DropdownOptions, RunError,
+    },
+    registry::BlockRegistry,
+    template::{self, TemplateError},
+    value::{parse_color, Value, ValueType},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    rc::Rc,
+};
+
+/// A block described in a data file instead of Rust code.
+///
+/// Every part is a [template], e.g. `"move %{steps:number=10} steps"`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockDefinition {
+    pub opcode: String,
+    pub shape: BlockShape,
+    #[serde(default = "default_category")]
+    pub category: BlockCategory,
+    /// `"#RRGGBB"`, overrides the category color
+    #[serde(default)]
+    pub color: Option<String>,
+    /// one template per branch plus one
+    pub parts: Vec<String>,
+    /// type of the value of reporters, e.g. `"number"`
+    #[serde(default)]
+    pub output: ValueType,
+    /// name of the handler in [`BlockHandlers`], a report handler for reporters and booleans
+    pub handler: String,
+    /// see [`Block::rust`]
+    #[serde(default)]
+    pub rust: Option<String>,
+}
+
+fn default_category() -> BlockCategory {
+    BlockCategory::Other
+}
+
+/// File containing block definitions, either JSON or TOML.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BlockDefinitions {
+    pub blocks: Vec<BlockDefinition>,
+}
+
+impl BlockDefinitions {
+    pub fn from_json(json: &str) -> Result<Self, DefinitionError> {
+        serde_json::from_str(json).map_err(DefinitionError::Json)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, DefinitionError> {
+        toml::from_str(toml).map_err(DefinitionError::Toml)
+    }
+}
+
+pub type RunHandler = Rc<dyn Fn(&BlockFields) -> Result<(), RunError>>;
+pub type ReportHandler = Rc<dyn Fn(&BlockFields) -> Result<Value, RunError>>;
+pub type MenuHandler = Rc<dyn Fn() -> Vec<String>>;
+
+/// Rust behavior of defined blocks, bound by name.
+#[derive(Default)]
+pub struct BlockHandlers {
+    run: HashMap<String, RunHandler>,
+    report: HashMap<String, ReportHandler>,
+    menus: HashMap<String, MenuHandler>,
+}
+
+impl BlockHandlers {
+    pub fn on_run(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl Fn(&BlockFields) -> Result<(), RunError> + 'static,
+    ) {
+        self.run.insert(name.into(), Rc::new(handler));
+    }
+
+    pub fn on_report(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl Fn(&BlockFields) -> Result<Value, RunError> + 'static,
+    ) {
+        self.report.insert(name.into(), Rc::new(handler));
+    }
+
+    /// Provides the options of dynamic dropdowns with the key `name`.
+    pub fn menu(&mut self, name: impl Into<String>, handler: impl Fn() -> Vec<String> + 'static) {
+        self.menus.insert(name.into(), Rc::new(handler));
+    }
+}
+
+#[derive(Debug)]
+pub enum DefinitionError {
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    UnknownHandler {
+        opcode: String,
+        handler: String,
+    },
+    /// a dynamic dropdown without a menu handler
+    UnknownMenu {
+        opcode: String,
+        menu: String,
+    },
+    /// a dynamic dropdown with the same key as one of another defined block, which would share
+    /// its menu
+    SharedMenu {
+        opcode: String,
+        menu: String,
+    },
+    /// another block, built-in or defined, has the same opcode
+    OpcodeExists {
+        opcode: String,
+    },
+    Template {
+        opcode: String,
+        error: TemplateError,
+    },
+    InvalidColor {
+        opcode: String,
+        color: String,
+    },
+    PartCount {
+        opcode: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DefinitionError::Json(err) => write!(f, "invalid JSON: {err}"),
+            DefinitionError::Toml(err) => write!(f, "invalid TOML: {err}"),
+            DefinitionError::UnknownHandler { opcode, handler } => {
+                write!(f, "{opcode}: unknown handler \"{handler}\"")
+            }
+            DefinitionError::UnknownMenu { opcode, menu } => {
+                write!(f, "{opcode}: unknown menu \"{menu}\"")
+            }
+            DefinitionError::SharedMenu { opcode, menu } => {
+                write!(f, "{opcode}: menu \"{menu}\" is used by another block")
+            }
+            DefinitionError::OpcodeExists { opcode } => {
+                write!(f, "{opcode}: a block with this opcode already exists")
+            }
+            DefinitionError::Template { opcode, error } => write!(f, "{opcode}: {error}"),
+            DefinitionError::InvalidColor { opcode, color } => {
+                write!(f, "{opcode}: invalid color \"{color}\"")
+            }
+            DefinitionError::PartCount {
+                opcode,
+                expected,
+                found,
+            } => write!(f, "{opcode}: expected {expected} parts, found {found}"),
+        }
+    }
+}
+
+impl Error for DefinitionError {}
+
+impl BlockDefinition {
+    fn describe(&self, handlers: &BlockHandlers) -> Result<BlockDescription, DefinitionError> {
+        let opcode = || self.opcode.clone();
+        let expected = self.shape.branches() + 1;
+        if self.parts.len() != expected {
+            return Err(DefinitionError::PartCount {
+                opcode: opcode(),
+                expected,
+                found: self.parts.len(),
+            });
+        }
+
+        let color = match &self.color {
+            Some(color) => {
+                Some(
+                    parse_color(color).ok_or_else(|| DefinitionError::InvalidColor {
+                        opcode: opcode(),
+                        color: color.clone(),
+                    })?,
+                )
+            }
+            None => None,
+        };
+
+        let content =
+            template::parse_content(&self.parts).map_err(|error| DefinitionError::Template {
+                opcode: opcode(),
+                error,
+            })?;
+
+        for widget in content.iter().flatten() {
+            if let BlockWidget::Dropdown {
+                key,
+                options: DropdownOptions::Dynamic,
+                ..
+            } = widget
+            {
+                if !handlers.menus.contains_key(key.as_ref()) {
+                    return Err(DefinitionError::UnknownMenu {
+                        opcode: opcode(),
+                        menu: key.to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(BlockDescription {
+            shape: self.shape,
+            category: self.category,
+            color,
+            content,
+            output: self.output.clone(),
+        })
+    }
+}
+
+#[derive(Clone)]
+enum Handler {
+    Run(RunHandler),
+    Report(ReportHandler),
+}
+
+/// [`Block`] implementation of a [`BlockDefinition`].
+#[derive(Clone)]
+pub struct DefinedBlock {
+    opcode: String,
+    description: BlockDescription,
+    handler: Handler,
+    menus: HashMap<String, MenuHandler>,
+    rust: Option<String>,
+}
+
+impl DefinedBlock {
+    pub fn new(
+        definition: &BlockDefinition,
+        handlers: &BlockHandlers,
+    ) -> Result<Self, DefinitionError> {
+        let handler = if definition.shape.is_output() {
+            handlers
+                .report
+                .get(&definition.handler)
+                .cloned()
+                .map(Handler::Report)
+        } else {
+            handlers
+                .run
+                .get(&definition.handler)
+                .cloned()
+                .map(Handler::Run)
+        };
+        let handler = handler.ok_or_else(|| DefinitionError::UnknownHandler {
+            opcode: definition.opcode.clone(),
+            handler: definition.handler.clone(),
+        })?;
+
+        let description = definition.describe(handlers)?;
+        let menus = description
+            .content
+            .iter()
+            .flatten()
+            .filter_map(|widget| match widget {
+                BlockWidget::Dropdown {
+                    key,
+                    options: DropdownOptions::Dynamic,
+                    ..
+                } => Some((key.to_string(), handlers.menus[key.as_ref()].clone())),
+                _ => None,
+            })
+            .collect();
+
+        Ok(DefinedBlock {
+            opcode: definition.opcode.clone(),
+            description,
+            handler,
+            menus,
+            rust: definition.rust.clone(),
+        })
+    }
+}
+
+impl Block for DefinedBlock {
+    fn opcode(&self) -> &str {
+        &self.opcode
+    }
+
+    fn describe(&mut self) -> BlockDescription {
+        self.description.clone()
+    }
+
+    fn dropdown_options(&mut self, key: &str) -> Vec<String> {
+        self.menus.get(key).map(|menu| menu()).unwrap_or_default()
+    }
+
+    fn run(&mut self, fields: &BlockFields) -> Result<(), RunError> {
+        match &self.handler {
+            Handler::Run(run) => run(fields),
+            Handler::Report(report) => report(fields).map(drop),
+        }
+    }
+
+    fn report(&mut self, fields: &BlockFields) -> Result<Value, RunError> {
+        match &self.handler {
+            Handler::Run(run) => run(fields).map(|()| Value::default()),
+            Handler::Report(report) => report(fields),
+        }
+    }
+
+    fn rust(&self) -> Option<&str> {
+        self.rust.as_deref()
+    }
+}
+
+impl BlockRegistry {
+    /// Registers every definition, or none if any of them is invalid.
+    pub fn register_definitions(
+        &mut self,
+        definitions: &BlockDefinitions,
+        handlers: &BlockHandlers,
+    ) -> Result<(), DefinitionError> {
+        let mut opcodes = HashSet::new();
+        let mut menus = self.menus.clone();
+        let mut blocks = Vec::with_capacity(definitions.blocks.len());
+        for definition in &definitions.blocks {
+            let block = DefinedBlock::new(definition, handlers)?;
+            if self.contains(&block.opcode) || !opcodes.insert(block.opcode.clone()) {
+                return Err(DefinitionError::OpcodeExists {
+                    opcode: block.opcode,
+                });
+            }
+
+            for menu in block.menus.keys() {
+                if menus.insert(menu.clone(), block.opcode.clone()).is_some() {
+                    return Err(DefinitionError::SharedMenu {
+                        opcode: block.opcode,
+                        menu: menu.clone(),
+                    });
+                }
+            }
+
+            blocks.push(block);
+        }
+
+        self.menus = menus;
+        for block in blocks {
+            self.register(move || block.clone());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockEditor,
+        event::{Event, WHEN_GAME_STARTS},
+        save::tests::{number, text, Workspace},
+        variable::{SET_VARIABLE, VARIABLE},
+    };
+    use std::cell::RefCell;
+
+    const JSON: &str = r#"{
+        "blocks": [{
+            "opcode": "test_twice",
+            "shape": "reporter",
+            "parts": ["twice %{number:number}"],
+            "output": "number",
+            "handler": "twice"
+        }]
+    }"#;
+
+    fn handlers(said: Rc<RefCell<Vec<String>>>) -> BlockHandlers {
+        let mut handlers = BlockHandlers::default();
+        handlers.on_run("say", move |fields| {
+            let text = format!("{} {}", fields.text("message"), fields.number("secs"));
+            said.borrow_mut().push(text);
+            Ok(())
+        });
+        handlers.on_run("point_towards", |_fields| Ok(()));
+        handlers.on_run("set_volume", |_fields| Ok(()));
+        handlers.on_report("timer", |_fields| Ok(Value::from(0.0)));
+        handlers.on_report("twice", |fields| {
+            Ok(Value::from(2.0 * fields.number("number")))
+        });
+        handlers.menu("target", Vec::new);
+        handlers
+    }
+
+    fn definition(opcode: &str, shape: BlockShape, part: &str, handler: &str) -> BlockDefinitions {
+        BlockDefinitions {
+            blocks: vec![BlockDefinition {
+                opcode: opcode.to_string(),
+                shape,
+                category: BlockCategory::Other,
+                color: None,
+                parts: vec![part.to_string()],
+                output: ValueType::Any,
+                handler: handler.to_string(),
+                rust: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn run_handlers() {
+        let said = Rc::default();
+        let handlers = handlers(Rc::clone(&said));
+        let mut registry = Workspace::default().registry();
+        let toml = BlockDefinitions::from_toml(include_str!("../assets/blocks.toml")).unwrap();
+        registry.register_definitions(&toml, &handlers).unwrap();
+        let json = BlockDefinitions::from_json(JSON).unwrap();
+        registry.register_definitions(&json, &handlers).unwrap();
+
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let say = workspace.block("looks_sayforsecs", &[("message", text("hi"))]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("x"))]);
+        let twice = workspace.block("test_twice", &[("number", number(21.0))]);
+        workspace.input(set, "value", twice);
+        workspace.stack(&[hat, say, set]);
+
+        let mut editor = BlockEditor::load(&workspace.0, &registry).unwrap();
+        editor.emit(Event::GameStarts);
+        while !editor.threads().is_empty() {
+            editor.tick();
+        }
+        assert_eq!(*said.borrow(), ["hi 2"]);
+        assert_eq!(editor.variables.get("x").map(Value::to_number), Some(42.0));
+    }
+
+    #[test]
+    fn errors() {
+        let handlers = handlers(Rc::default());
+        let register = |definitions: &[BlockDefinitions]| {
+            let mut registry = BlockRegistry::default();
+            definitions
+                .iter()
+                .try_for_each(|definitions| registry.register_definitions(definitions, &handlers))
+                .err()
+        };
+
+        // reporters need a report handler
+        let say = definition("test_say", BlockShape::Reporter, "say", "say");
+        assert!(matches!(
+            register(&[say]),
+            Some(DefinitionError::UnknownHandler { .. })
+        ));
+
+        let variable = definition(VARIABLE, BlockShape::Reporter, "x", "timer");
+        assert!(matches!(
+            register(&[variable]),
+            Some(DefinitionError::OpcodeExists { opcode }) if opcode == VARIABLE
+        ));
+
+        let mut twice = definition("test_say", BlockShape::Stack, "say", "say");
+        twice.blocks.extend(twice.blocks.clone());
+        assert!(matches!(
+            register(&[twice]),
+            Some(DefinitionError::OpcodeExists { .. })
+        ));
+
+        let point = |opcode| {
+            definition(
+                opcode,
+                BlockShape::Stack,
+                "point towards %{target:dropdown}",
+                "point_towards",
+            )
+        };
+        assert!(register(&[point("test_a")]).is_none());
+        assert!(matches!(
+            register(&[point("test_a"), point("test_b")]),
+            Some(DefinitionError::SharedMenu { opcode, menu }) if opcode == "test_b" && menu == "target"
+        ));
+    }
+}
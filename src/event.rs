@@ -0,0 +1,184 @@
+//! Events starting the scripts below hat blocks.
+//!
+//! The host app fires events with [`BlockEditor::emit`](crate::block::BlockEditor::emit), every
+//! script whose hat matches starts as a new thread. Scripts fire messages themselves with the
+//! broadcast blocks.
+
+use crate::{
+    block::{
+        Block, BlockCategory, BlockDescription, BlockFields, BlockShape, BlockWidget,
+        DropdownOptions, RunError,
+    },
+    field::DerivedBlock,
+    value::ValueType,
+};
+use eframe::egui::Key;
+use std::borrow::Cow;
+
+pub const WHEN_GAME_STARTS: &str = "event_whenflagclicked";
+pub const WHEN_KEY_PRESSED: &str = "event_whenkeypressed";
+pub const WHEN_I_RECEIVE: &str = "event_whenbroadcastreceived";
+pub const BROADCAST: &str = "event_broadcast";
+pub const BROADCAST_AND_WAIT: &str = "event_broadcastandwait";
+
+/// Options of the key dropdown besides the letters and digits, with their keys.
+const NAMED_KEYS: [(&str, Key); 5] = [
+    ("space", Key::Space),
+    ("up arrow", Key::ArrowUp),
+    ("down arrow", Key::ArrowDown),
+    ("right arrow", Key::ArrowRight),
+    ("left arrow", Key::ArrowLeft),
+];
+/// matches every key
+const ANY_KEY: &str = "any";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// e.g. the green flag was clicked
+    GameStarts,
+    KeyPressed(Key),
+    /// a broadcast message, matched case-insensitively like in Scratch
+    Message(String),
+}
+
+impl Event {
+    /// Whether the script below a hat block with the given fields runs on this event.
+    pub(crate) fn triggers(&self, opcode: &str, fields: &BlockFields) -> bool {
+        match (self, opcode) {
+            (Event::GameStarts, WHEN_GAME_STARTS) => true,
+            (Event::KeyPressed(key), WHEN_KEY_PRESSED) => {
+                let name = fields.text("key");
+                name == ANY_KEY || key_name(*key).is_some_and(|key| key == name)
+            }
+            (Event::Message(message), WHEN_I_RECEIVE) => {
+                fields.text("message").to_lowercase() == message.to_lowercase()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Name of a key in the key dropdown, `None` for keys which aren't in it.
+fn key_name(key: Key) -> Option<Cow<'static, str>> {
+    if let Some((name, _key)) = NAMED_KEYS.iter().find(|(_name, other)| *other == key) {
+        return Some(Cow::Borrowed(name));
+    }
+
+    // letters and digits
+    let name = key.name();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(char), None) if char.is_ascii_alphanumeric() => {
+            Some(Cow::Owned(char.to_ascii_lowercase().to_string()))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "event_whenflagclicked",
+    shape = "hat",
+    category = "events",
+    label = "when game starts",
+    rust = "when_game_starts"
+)]
+pub struct WhenGameStarts {}
+
+// started by the editor
+impl DerivedBlock for WhenGameStarts {}
+
+pub struct WhenKeyPressed;
+
+impl Block for WhenKeyPressed {
+    fn opcode(&self) -> &str {
+        WHEN_KEY_PRESSED
+    }
+
+    fn describe(&mut self) -> BlockDescription {
+        let options = NAMED_KEYS
+            .iter()
+            .map(|(name, _key)| Cow::Borrowed(*name))
+            .chain(std::iter::once(Cow::Borrowed(ANY_KEY)))
+            .chain(
+                ('a'..='z')
+                    .chain('0'..='9')
+                    .map(|char| char.to_string().into()),
+            )
+            .collect();
+
+        BlockDescription {
+            shape: BlockShape::Hat,
+            category: BlockCategory::Events,
+            color: None,
+            content: vec![vec![
+                BlockWidget::Label {
+                    text: Cow::Borrowed("when"),
+                },
+                BlockWidget::Dropdown {
+                    key: Cow::Borrowed("key"),
+                    options: DropdownOptions::Static(options),
+                    default: Cow::Borrowed("space"),
+                },
+                BlockWidget::Label {
+                    text: Cow::Borrowed("key pressed"),
+                },
+            ]],
+            output: ValueType::Any,
+        }
+    }
+
+    // started by the editor
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        Ok(())
+    }
+
+    fn rust(&self) -> Option<&str> {
+        Some("when_%{key}_key_pressed")
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "event_whenbroadcastreceived",
+    shape = "hat",
+    category = "events",
+    label = "when I receive {message}",
+    rust = "when_i_receive_%{message}"
+)]
+pub struct WhenIReceive {
+    #[block(default = "message1")]
+    pub message: String,
+}
+
+impl DerivedBlock for WhenIReceive {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "event_broadcast",
+    category = "events",
+    label = "broadcast {message}",
+    rust = "self.broadcast(&%{message:text}, host)?;"
+)]
+pub struct Broadcast {
+    #[block(default = "message1")]
+    pub message: String,
+}
+
+// run by the editor, which starts the threads
+impl DerivedBlock for Broadcast {}
+
+/// Continues once every script started by the message has finished.
+#[derive(Block, Default)]
+#[block(
+    opcode = "event_broadcastandwait",
+    category = "events",
+    label = "broadcast {message} and wait",
+    rust = "self.broadcast(&%{message:text}, host)?;"
+)]
+pub struct BroadcastAndWait {
+    #[block(default = "message1")]
+    pub message: String,
+}
+
+impl DerivedBlock for BroadcastAndWait {}
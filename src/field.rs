@@ -0,0 +1,278 @@
+//! Typed fields of blocks using `#[derive(Block)]`.
+//!
+//! ```ignore
+//! #[derive(Block, Default)]
+//! #[block(shape = "stack", category = "motion", label = "move {steps} steps toward {target}")]
+//! struct MoveSteps {
+//!     #[block(default = 10.0, step = 0.5)]
+//!     steps: f64,
+//!     #[block(options("mouse-pointer", "random position"))]
+//!     target: String,
+//! }
+//!
+//! impl DerivedBlock for MoveSteps {
+//!     fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+//!         println!("moving {} steps towards {}", self.steps, self.target);
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! Reporters implement [`DerivedBlock::report`] instead:
+//!
+//! ```ignore
+//! #[derive(Block, Default)]
+//! #[block(shape = "boolean", category = "operators", label = "{a} and {b}")]
+//! struct And {
+//!     #[block(slot)]
+//!     a: bool,
+//!     #[block(slot)]
+//!     b: bool,
+//! }
+//!
+//! impl DerivedBlock for And {
+//!     fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+//!         Ok((self.a && self.b).into())
+//!     }
+//! }
+//! ```
+//!
+//! Struct attributes:
+//!
+//! - `opcode = "..."`, defaults to the struct name in snake case
+//! - `shape = "hat" | "stack" | "c" | "cap" | "reporter" | "boolean"`, defaults to `"stack"`
+//! - `branches = N`, number of branches of `"c"` blocks, defaults to 1
+//! - `category = "..."`, a [`BlockCategory`](crate::block::BlockCategory) in snake case
+//! - `color = "#RRGGBB"`, overrides the category color
+//! - `output = "..."`, the [`ValueType`](crate::value::ValueType) reporters report
+//! - `label = "..."`, once per part, `{field}` places the widget of a field, `{{` and `}}` are
+//!   literal braces
+//! - `rust = "..."`, the [template](crate::codegen) of the generated Rust code
+//!
+//! Field attributes:
+//!
+//! - `default = value`, string literals are converted with [`From<&str>`]
+//! - `min = N`, `max = N`, `step = N` for numbers and sliders
+//! - `slider` turns an [`f64`] into a [`BlockWidget::Slider`]
+//! - `options("a", "b")` turns a [`String`] into a dropdown, `dropdown` makes it dynamic
+//! - `variable` turns a [`String`] into a dropdown of the variables of the editor, `list` into
+//!   one of the lists
+//! - `slot` replaces the widget with an empty [`BlockWidget::Slot`] of the same type
+//! - `skip` excludes a field, it isn't touched when the block runs
+//!
+//! Every field without `skip` has to appear in a label. Attributes which don't apply to the type
+//! of a field are errors, e.g. `slider` on a `bool`.
+
+use crate::{
+    block::{BlockFields, BlockWidget, DropdownOptions, NumberFormat, RunError},
+    value::Value,
+};
+use eframe::epaint::Color32;
+use std::borrow::Cow;
+
+/// Behavior of blocks using `#[derive(Block)]`.
+pub trait DerivedBlock {
+    /// Runs the block, the fields have been updated to the current values of the widgets.
+    /// `fields` gives access to the editor, e.g. [`BlockFields::random`].
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        Ok(())
+    }
+
+    /// Value of reporters, the fields have been updated like for [`DerivedBlock::run`].
+    fn report(&mut self, fields: &BlockFields) -> Result<Value, RunError> {
+        self.run(fields)?;
+        Ok(Value::default())
+    }
+
+    /// options of dynamic dropdowns, `key` is the name of the field
+    fn dropdown_options(&mut self, _key: &str) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Field attributes besides `default`.
+#[derive(Default)]
+pub struct FieldAttributes {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+    pub slider: bool,
+    pub options: Option<DropdownOptions>,
+}
+
+/// Type of a field of a derived block, decides which widget it gets.
+pub trait BlockField: Sized {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        attributes: FieldAttributes,
+    ) -> BlockWidget;
+
+    fn read(fields: &BlockFields, key: &str) -> Self;
+}
+
+/// Direction in degrees, shown as a [`BlockWidget::Angle`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Angle(pub f32);
+
+fn number_format(attributes: &FieldAttributes, integer: bool) -> NumberFormat {
+    NumberFormat {
+        min: attributes.min,
+        max: attributes.max,
+        step: attributes.step.unwrap_or(0.0),
+        integer,
+        ..Default::default()
+    }
+}
+
+impl BlockField for String {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        attributes: FieldAttributes,
+    ) -> BlockWidget {
+        match attributes.options {
+            Some(options) => {
+                let default = match (default, &options) {
+                    (Some(default), _) => default.into(),
+                    (None, DropdownOptions::Static(options)) => {
+                        options.first().cloned().unwrap_or_default()
+                    }
+                    (None, _) => Cow::Borrowed(""),
+                };
+
+                BlockWidget::Dropdown {
+                    key,
+                    options,
+                    default,
+                }
+            }
+            None => BlockWidget::TextEdit {
+                key,
+                default: default.unwrap_or_default().into(),
+            },
+        }
+    }
+
+    fn read(fields: &BlockFields, key: &str) -> Self {
+        fields.text(key).into_owned()
+    }
+}
+
+impl BlockField for f64 {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        attributes: FieldAttributes,
+    ) -> BlockWidget {
+        if attributes.slider {
+            let min = attributes.min.unwrap_or(0.0);
+            BlockWidget::Slider {
+                key,
+                default: default.unwrap_or(min),
+                min,
+                max: attributes.max.unwrap_or(100.0),
+                step: attributes.step.unwrap_or(0.0),
+            }
+        } else {
+            BlockWidget::NumberEdit {
+                key,
+                default: default.unwrap_or(0.0),
+                format: number_format(&attributes, false),
+            }
+        }
+    }
+
+    fn read(fields: &BlockFields, key: &str) -> Self {
+        fields.number(key)
+    }
+}
+
+impl BlockField for i64 {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        attributes: FieldAttributes,
+    ) -> BlockWidget {
+        BlockWidget::NumberEdit {
+            key,
+            default: default.unwrap_or(0) as f64,
+            format: number_format(&attributes, true),
+        }
+    }
+
+    fn read(fields: &BlockFields, key: &str) -> Self {
+        fields.integer(key)
+    }
+}
+
+impl BlockField for bool {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        _attributes: FieldAttributes,
+    ) -> BlockWidget {
+        BlockWidget::Checkbox {
+            key,
+            default: default.unwrap_or(false),
+        }
+    }
+
+    fn read(fields: &BlockFields, key: &str) -> Self {
+        fields.bool(key)
+    }
+}
+
+impl BlockField for Color32 {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        _attributes: FieldAttributes,
+    ) -> BlockWidget {
+        BlockWidget::ColorPicker {
+            key,
+            default: default.unwrap_or(Color32::WHITE),
+        }
+    }
+
+    fn read(fields: &BlockFields, key: &str) -> Self {
+        fields.color(key)
+    }
+}
+
+impl BlockField for Angle {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        attributes: FieldAttributes,
+    ) -> BlockWidget {
+        BlockWidget::Angle {
+            key,
+            default: default.unwrap_or(Angle(90.0)).0,
+            snap: attributes.step.unwrap_or(15.0) as f32,
+        }
+    }
+
+    fn read(fields: &BlockFields, key: &str) -> Self {
+        Angle(fields.angle(key))
+    }
+}
+
+/// Any value, edited as text.
+impl BlockField for Value {
+    fn widget(
+        key: Cow<'static, str>,
+        default: Option<Self>,
+        attributes: FieldAttributes,
+    ) -> BlockWidget {
+        String::widget(
+            key,
+            default.map(|value| value.to_text().into_owned()),
+            attributes,
+        )
+    }
+
+    fn read(fields: &BlockFields, key: &str) -> Self {
+        fields.value(key).clone()
+    }
+}
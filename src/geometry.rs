@@ -0,0 +1,539 @@
+use eframe::epaint::Vec2;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// Curve of a notch, `depth` pixels deep.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NotchProfile {
+    /// straight slopes
+    Trapezoid,
+    /// half ellipse, ignores `inset`
+    Round,
+    /// smooth slopes with a flat bottom, like Blockly's puzzle tabs
+    Puzzle,
+}
+
+/// Shape of the notch connecting stacked blocks, relative to the left edge of a part.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NotchGeometry {
+    pub profile: NotchProfile,
+    /// distance from the left edge of the part
+    pub offset: f32,
+    /// width at the top of the notch
+    pub width: f32,
+    /// how far the bottom of the notch is inset from each side
+    pub inset: f32,
+    pub depth: f32,
+}
+
+impl Default for NotchGeometry {
+    fn default() -> Self {
+        NotchGeometry {
+            profile: NotchProfile::Trapezoid,
+            offset: 10.0,
+            width: 30.0,
+            inset: 10.0,
+            depth: 10.0,
+        }
+    }
+}
+
+impl NotchGeometry {
+    /// where the notches of two blocks are considered attached
+    pub(crate) fn attachment(&self) -> Vec2 {
+        Vec2::new(self.offset + self.width / 2.0, self.depth)
+    }
+
+    /// depth of the notch at `x` pixels from its left side
+    fn depth_at(&self, x: f32) -> f32 {
+        let inset = self.inset.clamp(0.0, self.width / 2.0);
+        let slope = |x: f32| {
+            if inset == 0.0 {
+                1.0
+            } else {
+                (x.min(self.width - x) / inset).min(1.0)
+            }
+        };
+
+        self.depth
+            * match self.profile {
+                NotchProfile::Trapezoid => slope(x),
+                NotchProfile::Round => {
+                    let t = 2.0 * x / self.width - 1.0;
+                    (1.0 - t * t).max(0.0).sqrt()
+                }
+                NotchProfile::Puzzle => {
+                    let t = slope(x);
+                    t * t * (3.0 - 2.0 * t)
+                }
+            }
+    }
+
+    /// offsets from the left side of the notch, from left to right
+    fn points(&self, segments: usize) -> Vec<Vec2> {
+        let xs: Vec<f32> = match self.profile {
+            NotchProfile::Trapezoid => {
+                let inset = self.inset.clamp(0.0, self.width / 2.0);
+                vec![0.0, inset, self.width - inset, self.width]
+            }
+            NotchProfile::Round => (0..=2 * segments)
+                .map(|i| self.width * i as f32 / (2 * segments) as f32)
+                .collect(),
+            NotchProfile::Puzzle => {
+                let inset = self.inset.clamp(0.0, self.width / 2.0);
+                let left = (0..=segments).map(|i| inset * i as f32 / segments as f32);
+                let right = (0..=segments)
+                    .map(|i| self.width - inset * (segments - i) as f32 / segments as f32);
+                left.chain(right).collect()
+            }
+        };
+
+        xs.into_iter()
+            .map(|x| Vec2::new(x, self.depth_at(x)))
+            .collect()
+    }
+}
+
+/// Everything that influences the outline of a block besides the size of its parts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockGeometry {
+    pub notch: NotchGeometry,
+    /// radius of the corners, clamped to fit
+    pub rounding: f32,
+    /// width of the curved cap of hat blocks
+    pub hat_width: f32,
+    /// how far the cap of hat blocks rises above the block
+    pub hat_height: f32,
+    /// line segments per curve
+    pub curve_segments: usize,
+}
+
+impl Default for BlockGeometry {
+    fn default() -> Self {
+        BlockGeometry {
+            notch: NotchGeometry::default(),
+            rounding: 4.0,
+            hat_width: 80.0,
+            hat_height: 20.0,
+            curve_segments: 6,
+        }
+    }
+}
+
+/// Layout of a single part, relative to the block position.
+#[derive(PartialEq)]
+pub(crate) struct PartOutline {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// Options of a block shape relevant to its outline.
+pub(crate) struct OutlineShape {
+    pub hat: bool,
+    pub top_notch: bool,
+    pub bottom_notch: bool,
+}
+
+struct PathBuilder {
+    points: Vec<Vec2>,
+}
+
+impl PathBuilder {
+    fn push(&mut self, point: Vec2) {
+        if self.points.last() != Some(&point) {
+            self.points.push(point);
+        }
+    }
+
+    /// arc around `center` from angle `from` to `to`, in screen space (y pointing down)
+    fn arc(&mut self, center: Vec2, radius: f32, from: f32, to: f32, segments: usize) {
+        if radius <= 0.0 {
+            self.push(center);
+            return;
+        }
+
+        for i in 0..=segments {
+            let angle = from + (to - from) * i as f32 / segments as f32;
+            self.push(center + radius * Vec2::angled(angle));
+        }
+    }
+}
+
+impl BlockGeometry {
+    /// Closed clockwise outline of a block, the last point connects to the first.
+    pub(crate) fn outline(&self, shape: &OutlineShape, parts: &[PartOutline]) -> Vec<Vec2> {
+        let segments = self.curve_segments.max(1);
+        let notch = &self.notch;
+        let first = parts.first().unwrap();
+        let last = parts.last().unwrap();
+        let indent = parts.get(1).map_or(0.0, |part| part.left);
+
+        // every straight edge has to fit two corners, and the notches must not overlap them
+        let radius = |width: f32, height: f32| {
+            self.rounding
+                .min(width / 2.0)
+                .min(height / 2.0)
+                .min(notch.offset)
+                .max(0.0)
+        };
+        let notch_points = notch.points(segments);
+        let mut path = PathBuilder { points: Vec::new() };
+
+        // top of the first part, left to right
+        let r = radius(first.right - first.left, first.bottom - first.top);
+        if shape.hat {
+            let width = self.hat_width.min(first.right - first.left - r);
+            let start = Vec2::new(first.left, first.top);
+            let control = Vec2::new(width * 0.26, -self.hat_height);
+            let control_end = Vec2::new(width * 0.74, -self.hat_height);
+            let end = Vec2::new(width, 0.0);
+            for i in 0..=segments * 2 {
+                let t = i as f32 / (segments * 2) as f32;
+                let u = 1.0 - t;
+                path.push(
+                    start
+                        + 3.0 * u * u * t * control
+                        + 3.0 * u * t * t * control_end
+                        + t * t * t * end,
+                );
+            }
+        } else {
+            path.arc(
+                Vec2::new(first.left + r, first.top + r),
+                r,
+                PI,
+                PI + FRAC_PI_2,
+                segments,
+            );
+        }
+
+        if shape.top_notch {
+            for point in &notch_points {
+                path.push(Vec2::new(first.left + notch.offset, first.top) + *point);
+            }
+        }
+
+        for (i, part) in parts.iter().enumerate() {
+            let r = radius(part.right - part.left, part.bottom - part.top);
+            let is_last = i == parts.len() - 1;
+
+            if i != 0 {
+                // top of an arm, left to right, with a concave corner towards the mouth
+                let upper = &parts[i - 1];
+                let r_inner = radius(f32::INFINITY, part.top - upper.bottom);
+                path.arc(
+                    Vec2::new(part.left + r_inner, part.top - r_inner),
+                    r_inner,
+                    PI,
+                    FRAC_PI_2,
+                    segments,
+                );
+
+                if shape.top_notch {
+                    for point in &notch_points {
+                        path.push(Vec2::new(part.left + notch.offset, part.top) + *point);
+                    }
+                }
+            }
+
+            // right side
+            path.arc(
+                Vec2::new(part.right - r, part.top + r),
+                r,
+                -FRAC_PI_2,
+                0.0,
+                segments,
+            );
+            path.arc(
+                Vec2::new(part.right - r, part.bottom - r),
+                r,
+                0.0,
+                FRAC_PI_2,
+                segments,
+            );
+
+            // bottom, right to left
+            let bottom_left = if is_last { first.left } else { indent };
+            if !is_last || shape.bottom_notch {
+                for point in notch_points.iter().rev() {
+                    path.push(Vec2::new(bottom_left + notch.offset, part.bottom) + *point);
+                }
+            }
+
+            if !is_last {
+                // concave corner into the mouth
+                let lower = &parts[i + 1];
+                let r_inner = radius(f32::INFINITY, lower.top - part.bottom);
+                path.arc(
+                    Vec2::new(indent + r_inner, part.bottom + r_inner),
+                    r_inner,
+                    -FRAC_PI_2,
+                    -PI,
+                    segments,
+                );
+            }
+        }
+
+        // bottom left corner, the left side closes the path
+        let r = radius(last.right - first.left, last.bottom - last.top);
+        path.arc(
+            Vec2::new(first.left + r, last.bottom - r),
+            r,
+            FRAC_PI_2,
+            PI,
+            segments,
+        );
+
+        if path.points.len() > 1 && path.points.first() == path.points.last() {
+            path.points.pop();
+        }
+
+        path.points
+    }
+}
+
+impl BlockGeometry {
+    /// Closed clockwise outline of reporters and input slots, round or pointed for booleans.
+    pub(crate) fn output_outline(&self, size: Vec2, boolean: bool) -> Vec<Vec2> {
+        let radius = size.y / 2.0;
+        let inset = radius.min(size.x / 2.0);
+        let mut path = PathBuilder { points: Vec::new() };
+        if boolean {
+            // the top and bottom edges vanish if the boolean isn't wider than it's tall
+            for point in [
+                Vec2::new(0.0, radius),
+                Vec2::new(inset, 0.0),
+                Vec2::new(size.x - inset, 0.0),
+                Vec2::new(size.x, radius),
+                Vec2::new(size.x - inset, size.y),
+                Vec2::new(inset, size.y),
+            ] {
+                path.push(point);
+            }
+
+            return path.points;
+        }
+
+        let segments = self.curve_segments.max(1) * 2;
+        path.arc(
+            Vec2::new(inset, radius),
+            radius,
+            FRAC_PI_2,
+            PI + FRAC_PI_2,
+            segments,
+        );
+        path.arc(
+            Vec2::new(size.x - inset, radius),
+            radius,
+            -FRAC_PI_2,
+            FRAC_PI_2,
+            segments,
+        );
+
+        // both arcs meet at the bottom if the reporter isn't wider than it's tall
+        if path.points.len() > 1 && path.points.first() == path.points.last() {
+            path.points.pop();
+        }
+
+        path.points
+    }
+}
+
+fn cross(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    (b - a).x * (c - a).y - (b - a).y * (c - a).x
+}
+
+/// Ear clipping triangulation of a simple polygon.
+pub(crate) fn triangulate(polygon: &[Vec2]) -> Vec<u32> {
+    const EPSILON: f32 = 1e-4;
+
+    let signed_area: f32 = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    let orientation = signed_area.signum();
+
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut indices = Vec::with_capacity(3 * polygon.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let corner = |i: usize| {
+            (
+                remaining[(i + len - 1) % len],
+                remaining[i],
+                remaining[(i + 1) % len],
+            )
+        };
+
+        let ear = (0..len).find(|&i| {
+            let (a, b, c) = corner(i);
+            let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+            if cross(pa, pb, pc) * orientation <= EPSILON {
+                return false;
+            }
+
+            remaining.iter().all(|&j| {
+                let p = polygon[j];
+                j == a
+                    || j == b
+                    || j == c
+                    || p == pa
+                    || p == pb
+                    || p == pc
+                    || cross(pa, pb, p) * orientation < -EPSILON
+                    || cross(pb, pc, p) * orientation < -EPSILON
+                    || cross(pc, pa, p) * orientation < -EPSILON
+            })
+        });
+
+        match ear {
+            Some(i) => {
+                let (a, b, c) = corner(i);
+                indices.extend_from_slice(&[a as u32, b as u32, c as u32]);
+                remaining.remove(i);
+            }
+            None => {
+                // only degenerate corners are left, they don't contribute any area
+                match (0..len).find(|&i| {
+                    let (a, b, c) = corner(i);
+                    cross(polygon[a], polygon[b], polygon[c]).abs() <= EPSILON
+                }) {
+                    Some(i) => {
+                        remaining.remove(i);
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    if remaining.len() == 3 {
+        indices.extend(remaining.iter().map(|&i| i as u32));
+    }
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(left: f32, top: f32, right: f32, bottom: f32) -> PartOutline {
+        PartOutline {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// twice the signed area, positive for clockwise outlines in screen space
+    fn area(polygon: &[Vec2]) -> f32 {
+        polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum()
+    }
+
+    fn outlines() -> Vec<(String, Vec<Vec2>)> {
+        let stack = [part(0.0, 0.0, 120.0, 40.0)];
+        let c_block = [part(0.0, 0.0, 140.0, 40.0), part(16.0, 80.0, 140.0, 104.0)];
+        let e_block = [
+            part(0.0, 0.0, 140.0, 40.0),
+            part(16.0, 60.0, 140.0, 90.0),
+            part(16.0, 120.0, 140.0, 140.0),
+        ];
+        // narrower than the cap of hats
+        let small = [part(0.0, 0.0, 60.0, 24.0)];
+
+        let mut outlines = Vec::new();
+        for profile in [
+            NotchProfile::Trapezoid,
+            NotchProfile::Round,
+            NotchProfile::Puzzle,
+        ] {
+            let geometry = BlockGeometry {
+                notch: NotchGeometry {
+                    profile,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+
+            for (hat, top_notch, bottom_notch) in [
+                (false, true, true),
+                (true, false, true),
+                (false, true, false),
+            ] {
+                let shape = OutlineShape {
+                    hat,
+                    top_notch,
+                    bottom_notch,
+                };
+                for parts in [&stack[..], &c_block, &e_block, &small] {
+                    outlines.push((
+                        format!(
+                            "{profile:?} hat={hat} top={top_notch} bottom={bottom_notch} parts={}",
+                            parts.len()
+                        ),
+                        geometry.outline(&shape, parts),
+                    ));
+                }
+            }
+
+            for boolean in [false, true] {
+                for size in [Vec2::new(80.0, 24.0), Vec2::new(10.0, 24.0)] {
+                    outlines.push((
+                        format!("{profile:?} output boolean={boolean} size={size:?}"),
+                        geometry.output_outline(size, boolean),
+                    ));
+                }
+            }
+        }
+
+        outlines
+    }
+
+    #[test]
+    fn outlines_are_closed() {
+        for (name, outline) in outlines() {
+            assert!(outline.len() >= 3, "{name}");
+            // the last point connects to the first, so neither may be repeated
+            for (a, b) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+                assert_ne!(a, b, "{name}");
+            }
+            assert!(area(&outline) > 0.0, "{name}");
+        }
+    }
+
+    #[test]
+    fn triangulation_covers_outline() {
+        for (name, outline) in outlines() {
+            let indices = triangulate(&outline);
+            assert_eq!(indices.len() % 3, 0, "{name}");
+
+            let triangles: f32 = indices
+                .chunks(3)
+                .map(|triangle| {
+                    let triangle = [
+                        outline[triangle[0] as usize],
+                        outline[triangle[1] as usize],
+                        outline[triangle[2] as usize],
+                    ];
+                    let area = area(&triangle);
+                    // overlapping triangles would have to be inverted to add up
+                    assert!(area >= 0.0, "{name}");
+                    area
+                })
+                .sum();
+            let area = area(&outline);
+            assert!(
+                (triangles - area).abs() <= area * 1e-4,
+                "{name}: {triangles} != {area}"
+            );
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Live values of the variables, lists and threads of a [`BlockEditor`].
+
+use crate::{
+    block::BlockEditor,
+    interpreter::{FrameKind, Repeat},
+    value::Value,
+    variable::VariableKind,
+};
+use eframe::egui::{CollapsingHeader, Grid, Ui};
+
+/// Lists the variables, lists and threads of `editor`.
+pub fn panel(ui: &mut Ui, editor: &mut BlockEditor) {
+    let editable = editor.is_paused();
+
+    CollapsingHeader::new("Variables")
+        .default_open(true)
+        .show(ui, |ui| {
+            let names: Vec<String> = editor.variables().names().map(str::to_string).collect();
+            if names.is_empty() {
+                ui.weak("(none)");
+            }
+
+            Grid::new("variables").num_columns(3).show(ui, |ui| {
+                for name in names {
+                    pin(ui, editor, VariableKind::Variable, &name);
+                    ui.label(&name);
+                    let value = editor.variables_mut().get_mut(&name).unwrap();
+                    value_ui(ui, value, editable);
+                    ui.end_row();
+                }
+            });
+        });
+
+    CollapsingHeader::new("Lists")
+        .default_open(true)
+        .show(ui, |ui| {
+            let names: Vec<String> = editor
+                .variables()
+                .list_names()
+                .map(str::to_string)
+                .collect();
+            if names.is_empty() {
+                ui.weak("(none)");
+            }
+
+            for name in names {
+                ui.horizontal(|ui| {
+                    pin(ui, editor, VariableKind::List, &name);
+                    let items = editor.variables_mut().list_mut(&name).unwrap();
+                    CollapsingHeader::new(format!("{name} ({})", items.len()))
+                        .id_source(("list", &name))
+                        .show(ui, |ui| {
+                            Grid::new(("list items", &name))
+                                .num_columns(2)
+                                .show(ui, |ui| {
+                                    for (i, item) in items.iter_mut().enumerate() {
+                                        ui.weak((i + 1).to_string());
+                                        value_ui(ui, item, editable);
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                });
+            }
+        });
+
+    CollapsingHeader::new("Threads")
+        .default_open(true)
+        .show(ui, |ui| threads(ui, editor, editable));
+}
+
+/// Shows or hides the monitor of a variable or list.
+fn pin(ui: &mut Ui, editor: &mut BlockEditor, kind: VariableKind, name: &str) {
+    let mut pinned = editor.has_monitor(kind, name);
+    if ui
+        .checkbox(&mut pinned, "")
+        .on_hover_text("show on the workspace")
+        .changed()
+    {
+        editor.set_monitor(kind, name, pinned);
+    }
+}
+
+/// The frames of every thread, with the arguments of the procedures being run.
+fn threads(ui: &mut Ui, editor: &mut BlockEditor, editable: bool) {
+    if editor.threads.is_empty() {
+        ui.weak("(none)");
+    }
+
+    let paused = editor.paused_thread();
+    for (i, thread) in editor.threads.iter_mut().enumerate() {
+        let script = editor.blocks.get(thread.script());
+        let opcode = script.map_or("", |block| block.implementation.opcode());
+        let mut title = format!("thread {} ({opcode})", i + 1);
+        if paused == Some(thread.id()) {
+            title += ", paused";
+        } else if thread.is_waiting() {
+            title += ", waiting";
+        }
+
+        // the paused thread is the one being debugged
+        CollapsingHeader::new(title)
+            .id_source(thread.id())
+            .default_open(paused == Some(thread.id()))
+            .show(ui, |ui| {
+                for frame in thread.frames_mut() {
+                    match &mut frame.kind {
+                        FrameKind::Script => {
+                            ui.label("script");
+                        }
+                        FrameKind::Block => {
+                            ui.label("block");
+                        }
+                        FrameKind::Procedure { call, arguments } => {
+                            let prototype = editor
+                                .blocks
+                                .get(*call)
+                                .and_then(|block| block.implementation.mutation())
+                                .unwrap_or_default();
+                            ui.label(format!("procedure \"{prototype}\""));
+
+                            let mut names: Vec<String> = arguments.keys().cloned().collect();
+                            names.sort();
+                            ui.indent("arguments", |ui| {
+                                Grid::new("arguments").num_columns(2).show(ui, |ui| {
+                                    for name in names {
+                                        ui.label(&name);
+                                        value_ui(ui, arguments.get_mut(&name).unwrap(), editable);
+                                        ui.end_row();
+                                    }
+                                });
+                            });
+                        }
+                        FrameKind::Branch => {
+                            ui.label("branch");
+                        }
+                        FrameKind::Loop { repeat, .. } => {
+                            let text = match repeat {
+                                Repeat::Times(times) => format!("repeat, {times} times left"),
+                                Repeat::Until => "repeat until".to_string(),
+                                Repeat::Forever => "forever".to_string(),
+                            };
+                            ui.label(text);
+                        }
+                    }
+                }
+            });
+    }
+}
+
+/// A text field for the value if `editable`, otherwise a label.
+fn value_ui(ui: &mut Ui, value: &mut Value, editable: bool) {
+    let mut text = value.to_text().into_owned();
+    if !editable {
+        ui.label(text);
+        return;
+    }
+
+    // numbers typed into it are still used as numbers, like the text of slots
+    if ui.text_edit_singleline(&mut text).changed() {
+        *value = Value::Text(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::{Event, WHEN_GAME_STARTS},
+        procedure::{CALL, DEFINITION},
+        save::tests::{number, text, Workspace},
+        standard::WAIT,
+    };
+    use eframe::{
+        egui::{CentralPanel, Context, RawInput},
+        epaint::Shape,
+    };
+
+    /// Texts the panel shows for `editor`.
+    fn texts(editor: &mut BlockEditor) -> Vec<String> {
+        let ctx = Context::default();
+        // grids are laid out in the first frame and shown in the next one
+        let mut run = || {
+            ctx.run(RawInput::default(), |ctx| {
+                CentralPanel::default().show(ctx, |ui| panel(ui, editor));
+            })
+        };
+        run();
+        let output = run();
+
+        output
+            .shapes
+            .into_iter()
+            .filter_map(|shape| match shape.shape {
+                Shape::Text(text) => Some(text.galley.job.text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn panel_shows_threads_and_arguments() {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", text("apple"));
+        workspace.list("items", vec![number(1.0), number(2.0)]);
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let call = workspace.block(CALL, &[("n", number(5.0))]);
+        workspace.mutation(call, "p %n[n]");
+        workspace.stack(&[hat, call]);
+        let definition = workspace.block(DEFINITION, &[]);
+        workspace.mutation(definition, "p %n[n]");
+        let wait = workspace.block(WAIT, &[("duration", number(60.0))]);
+        workspace.stack(&[definition, wait]);
+
+        let mut editor = workspace.load();
+        assert!(texts(&mut editor).contains(&"(none)".to_string()));
+
+        editor.emit(Event::GameStarts);
+        editor.tick();
+        editor.pause();
+        let texts = texts(&mut editor);
+        for expected in [
+            "x",
+            "apple",
+            "items (2)",
+            "thread 1 (event_whenflagclicked), paused",
+            "script",
+            "procedure \"p %n[n]\"",
+            "n",
+            "5",
+        ] {
+            assert!(
+                texts.iter().any(|text| text == expected),
+                "{expected:?} in {texts:?}"
+            );
+        }
+    }
+}
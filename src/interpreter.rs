@@ -0,0 +1,771 @@
+//! Runs the scripts of a [`BlockEditor`], one block at a time.
+//!
+//! Each running script is a [`Thread`] with a stack of frames, one for the script itself and
+//! one for each procedure call, branch and loop being run. The threads of the editor take
+//! turns in [`BlockEditor::tick`], each one runs until it finishes, has to wait or reaches the
+//! end of a loop.
+
+use crate::{
+    block::{BlockEditor, BlockFields, BlockShape, Next, RunError},
+    debugger::Stepping,
+    event::{self, Event},
+    limits::{LimitError, Limits},
+    procedure::{self, ProcedureError, Prototype},
+    scheduler::{self, Schedule, Scheduled, Scheduler},
+    standard,
+    value::Value,
+    variable,
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    time::Instant,
+};
+use thunderdome::Index;
+
+pub(crate) enum FrameKind {
+    /// the script the thread was started with
+    Script,
+    /// a block run on its own, without the blocks below it
+    Block,
+    /// the script of a procedure definition
+    Procedure {
+        /// the call block
+        call: Index,
+        arguments: HashMap<String, Value>,
+    },
+    /// a branch of an if block
+    Branch,
+    /// the branch of a loop block, which starts over once its end is reached
+    Loop { block: Index, repeat: Repeat },
+}
+
+pub(crate) enum Repeat {
+    /// iterations left
+    Times(f64),
+    /// until the condition of the block is true
+    Until,
+    Forever,
+}
+
+/// Whether a thread keeps running after a step.
+pub(crate) enum Flow {
+    Continue,
+    /// other threads get a turn first
+    Yield,
+}
+
+pub(crate) struct Frame {
+    /// block being run, e.g. a loop while its branch runs
+    pub current: Option<Index>,
+    /// block to run next, `None` once the end of the stack has been reached
+    pub next: Option<Index>,
+    pub kind: FrameKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ThreadId(pub(crate) u64);
+
+/// A block which failed while a thread ran it, the thread is stopped.
+#[derive(Clone, Debug)]
+pub struct ScriptError {
+    /// the failing block, which might be a reporter
+    pub block: Index,
+    /// top block of the script the thread was running
+    pub script: Index,
+    pub thread: ThreadId,
+    /// procedure calls being run, innermost last
+    pub call_stack: Vec<Index>,
+    pub error: RunError,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl Error for ScriptError {}
+
+/// A running script.
+pub struct Thread {
+    id: ThreadId,
+    script: Index,
+    frames: Vec<Frame>,
+    /// breakpoint the thread paused at, which doesn't pause it again when it continues
+    resume_past: Option<Index>,
+    schedule: Schedule,
+}
+
+impl Thread {
+    fn new(id: ThreadId, script: Index, kind: FrameKind) -> Self {
+        Thread {
+            id,
+            script,
+            frames: vec![Frame {
+                current: None,
+                next: Some(script),
+                kind,
+            }],
+            resume_past: None,
+            schedule: Schedule::default(),
+        }
+    }
+
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    /// the top block of the script
+    pub fn script(&self) -> Index {
+        self.script
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The block which runs next, `None` if the end of a stack has been reached.
+    pub fn next_block(&self) -> Option<Index> {
+        self.frames.last()?.next
+    }
+
+    /// number of frames, which grows with every branch and procedure being run
+    pub(crate) fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Waiting for other threads or sleeping.
+    pub fn is_waiting(&self) -> bool {
+        self.schedule.is_waiting()
+    }
+
+    /// The blocks being run, outermost first: the blocks whose branches or procedures are
+    /// being run, followed by the block which ran last.
+    pub fn current_blocks(&self) -> impl Iterator<Item = Index> + '_ {
+        self.frames.iter().filter_map(|frame| frame.current)
+    }
+
+    /// The procedure calls being run, innermost last.
+    pub fn call_stack(&self) -> Vec<Index> {
+        self.frames
+            .iter()
+            .filter_map(|frame| match frame.kind {
+                FrameKind::Procedure { call, .. } => Some(call),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn error(&self, block: Index, error: RunError) -> ScriptError {
+        ScriptError {
+            block,
+            script: self.script,
+            thread: self.id,
+            call_stack: self.call_stack(),
+            error,
+        }
+    }
+
+    /// The frames of the thread, outermost first.
+    pub(crate) fn frames_mut(&mut self) -> &mut [Frame] {
+        &mut self.frames
+    }
+
+    /// Argument of the innermost procedure call.
+    pub fn argument(&self, name: &str) -> Option<&Value> {
+        self.frames
+            .iter()
+            .rev()
+            .find_map(|frame| match &frame.kind {
+                FrameKind::Procedure { arguments, .. } => Some(arguments.get(name)),
+                _ => None,
+            })?
+    }
+}
+
+impl Scheduled for Thread {
+    type Script = Index;
+
+    fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    fn script(&self) -> Index {
+        self.script
+    }
+
+    fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    fn stop(&mut self) {
+        self.frames.clear();
+    }
+
+    fn schedule(&mut self) -> &mut Schedule {
+        &mut self.schedule
+    }
+}
+
+impl Scheduler for BlockEditor {
+    type Thread = Thread;
+
+    fn threads(&mut self) -> &mut Vec<Thread> {
+        &mut self.threads
+    }
+
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    fn is_paused(&self) -> bool {
+        self.debugger.paused
+    }
+
+    fn run_turn(&mut self, thread: &mut Thread) {
+        while !thread.is_finished()
+            && !thread.is_waiting()
+            && !thread.schedule.budget.is_spent(&self.limits)
+            && !self.debugger.paused
+        {
+            let next = thread.next_block();
+            if next.is_some_and(|next| self.debugger.breakpoints.contains(&next))
+                && thread.resume_past != next
+            {
+                self.debugger.stepping = None;
+                self.pause_thread(thread);
+                break;
+            }
+
+            thread.resume_past = None;
+            let flow = match self.step(thread) {
+                Ok(flow) => flow,
+                Err(error) => {
+                    thread.frames.clear();
+                    self.errors.push(error);
+                    break;
+                }
+            };
+
+            if self.stepped(thread) {
+                self.pause_thread(thread);
+                break;
+            }
+
+            if let Flow::Yield = flow {
+                break;
+            }
+        }
+
+        if thread.is_finished() && self.debugger.thread == Some(thread.id) {
+            self.debugger.stepping = None;
+        }
+    }
+}
+
+impl BlockEditor {
+    pub fn threads(&self) -> &[Thread] {
+        &self.threads
+    }
+
+    /// The current blocks of every thread, see [`Thread::current_blocks`].
+    pub fn running_blocks(&self) -> HashSet<Index> {
+        self.threads
+            .iter()
+            .flat_map(Thread::current_blocks)
+            .collect()
+    }
+
+    /// When a block last ran or reported a value, for blocks which did so recently.
+    pub fn last_evaluated(&self, index: Index) -> Option<Instant> {
+        self.evaluated.get(&index).copied()
+    }
+
+    /// Starts the scripts below the hats matching `event`, scripts which are already running
+    /// start over.
+    pub fn emit(&mut self, event: Event) -> Vec<ThreadId> {
+        let scripts: Vec<Index> = self
+            .blocks
+            .iter()
+            .filter(|(_index, block)| {
+                let fields = BlockFields {
+                    values: &block.values,
+                    random: &self.random,
+                };
+                matches!(block.description.shape, BlockShape::Hat)
+                    && event.triggers(block.implementation.opcode(), &fields)
+            })
+            .map(|(index, _block)| index)
+            .collect();
+
+        scripts
+            .into_iter()
+            .map(|script| self.start_script(script))
+            .collect()
+    }
+
+    /// Starts a thread running the script starting at `index` in the next
+    /// [`BlockEditor::tick`]. A thread already running it is stopped.
+    pub fn start_script(&mut self, index: Index) -> ThreadId {
+        self.start(index, FrameKind::Script)
+    }
+
+    /// Starts a thread running only the block at `index`, along with the branches and
+    /// procedures it runs, like [`BlockEditor::start_script`].
+    pub fn start_block(&mut self, index: Index) -> ThreadId {
+        self.start(index, FrameKind::Block)
+    }
+
+    fn start(&mut self, index: Index, kind: FrameKind) -> ThreadId {
+        let id = ThreadId(self.next_thread);
+        self.next_thread += 1;
+        self.threads.retain(|thread| thread.script != index);
+        self.threads.push(Thread::new(id, index, kind));
+        self.errors.retain(|error| error.script != index);
+        id
+    }
+
+    pub fn stop_all(&mut self) {
+        self.threads.clear();
+    }
+
+    /// Errors of the threads which failed, oldest first. They're kept until their script is
+    /// started again.
+    pub fn errors(&self) -> &[ScriptError] {
+        &self.errors
+    }
+
+    pub fn clear_errors(&mut self) {
+        self.errors.clear();
+    }
+
+    /// Gives every thread turns until all of them are finished or waiting, for at most 10 ms.
+    /// Each turn runs a thread until it finishes, has to wait or reaches the end of a loop.
+    /// Threads started in the meantime get their first turn in the next round.
+    ///
+    /// Nothing runs while the editor [is paused](BlockEditor::is_paused) or
+    /// [replaying a trace](BlockEditor::is_replaying).
+    pub fn tick(&mut self) {
+        if !self.is_replaying() {
+            scheduler::tick(self);
+        }
+    }
+
+    /// Whether the thread is done with the step it's being stepped through, once there's a
+    /// block to pause at.
+    fn stepped(&mut self, thread: &Thread) -> bool {
+        if self.debugger.thread != Some(thread.id) || thread.next_block().is_none() {
+            return false;
+        }
+
+        let done = match self.debugger.stepping {
+            Some(Stepping::Into) => true,
+            Some(Stepping::Over { depth }) => thread.depth() <= depth,
+            None => false,
+        };
+        if done {
+            self.debugger.stepping = None;
+        }
+
+        done
+    }
+
+    fn pause_thread(&mut self, thread: &mut Thread) {
+        thread.resume_past = thread.next_block();
+        self.debugger.paused = true;
+        self.debugger.thread = Some(thread.id);
+    }
+
+    /// Runs the next block of the thread.
+    pub(crate) fn step(&mut self, thread: &mut Thread) -> Result<Flow, ScriptError> {
+        let Some(frame) = thread.frames.last_mut() else {
+            return Ok(Flow::Yield);
+        };
+
+        // the block might have been removed while the thread was running
+        let Some(block) = frame.next.and_then(|index| self.blocks.get(index)) else {
+            return self.end_frame(thread);
+        };
+
+        let index = frame.next.unwrap();
+        frame.current = Some(index);
+        frame.next = match (&frame.kind, &block.parts.last().unwrap().next) {
+            (FrameKind::Block, _) => None,
+            (_, Next::Some { index, .. }) => Some(*index),
+            _ => None,
+        };
+        self.evaluated.insert(index, Instant::now());
+        self.spend(thread, index)?;
+
+        let values = self.field_values(index, thread)?;
+        self.record(thread.id, index, &values, None);
+        let fields = BlockFields {
+            values: &values,
+            random: &self.random,
+        };
+        let implementation = &mut self.blocks[index].implementation;
+        match implementation.opcode() {
+            procedure::CALL => {
+                let prototype = implementation.mutation().unwrap_or_default();
+                let Some(definition) = self.definition(&prototype) else {
+                    let error = ProcedureError::Unknown(prototype).to_string();
+                    return Err(thread.error(index, error.into()));
+                };
+
+                if let Some(max) = self.limits.max_depth {
+                    if thread.call_stack().len() >= max {
+                        let error = LimitError::Depth(max).to_string();
+                        return Err(thread.error(index, error.into()));
+                    }
+                }
+
+                let arguments = values
+                    .into_iter()
+                    .map(|(key, value)| (key.into_owned(), value))
+                    .collect();
+                thread.frames.push(Frame {
+                    current: None,
+                    next: Some(definition),
+                    kind: FrameKind::Procedure {
+                        call: index,
+                        arguments,
+                    },
+                });
+                // the definition itself is a hat which does nothing
+                return self.step(thread);
+            }
+            procedure::DEFINITION => (),
+            standard::IF => {
+                if fields.bool("condition") {
+                    self.enter(thread, index, 0, FrameKind::Branch);
+                }
+            }
+            standard::IF_ELSE => {
+                let branch = if fields.bool("condition") { 0 } else { 1 };
+                self.enter(thread, index, branch, FrameKind::Branch);
+            }
+            standard::REPEAT => {
+                let times = fields.value("times").to_number().round();
+                if times >= 1.0 {
+                    let repeat = Repeat::Times(times);
+                    self.enter(
+                        thread,
+                        index,
+                        0,
+                        FrameKind::Loop {
+                            block: index,
+                            repeat,
+                        },
+                    );
+                }
+            }
+            standard::REPEAT_UNTIL => {
+                if !fields.bool("condition") {
+                    let repeat = Repeat::Until;
+                    self.enter(
+                        thread,
+                        index,
+                        0,
+                        FrameKind::Loop {
+                            block: index,
+                            repeat,
+                        },
+                    );
+                }
+            }
+            standard::FOREVER => {
+                let repeat = Repeat::Forever;
+                self.enter(
+                    thread,
+                    index,
+                    0,
+                    FrameKind::Loop {
+                        block: index,
+                        repeat,
+                    },
+                );
+            }
+            standard::WAIT => {
+                thread.schedule.sleep(fields.value("duration").to_number());
+                return Ok(Flow::Yield);
+            }
+            standard::STOP => match fields.text("option").as_ref() {
+                standard::STOP_ALL => {
+                    thread.frames.clear();
+                    thread.schedule.stop_others = true;
+                    self.threads.clear();
+                }
+                standard::STOP_OTHER_SCRIPTS => {
+                    thread.schedule.stop_others = true;
+                    self.threads.clear();
+                }
+                // returns from the innermost procedure, like in Scratch
+                _ => {
+                    while let Some(frame) = thread.frames.pop() {
+                        if let FrameKind::Procedure { .. } = frame.kind {
+                            break;
+                        }
+                    }
+                }
+            },
+            event::BROADCAST => {
+                self.emit(Event::Message(fields.text("message").into_owned()));
+            }
+            event::BROADCAST_AND_WAIT => {
+                thread.schedule.waiting =
+                    self.emit(Event::Message(fields.text("message").into_owned()));
+            }
+            opcode => {
+                if variable::evaluate(opcode, &fields, &mut self.variables).is_none() {
+                    implementation
+                        .run(&fields)
+                        .map_err(|error| thread.error(index, error))?;
+                } else if self.limits.exceeded_by(opcode, &fields, &self.variables) {
+                    // e.g. adding to a list
+                    return Err(self.length_error(thread, index));
+                }
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Starts running a branch of a C block.
+    fn enter(&self, thread: &mut Thread, index: Index, branch: usize, kind: FrameKind) {
+        thread.frames.push(Frame {
+            current: None,
+            next: self.branch(index, branch),
+            kind,
+        });
+    }
+
+    /// First block of a branch of a C block.
+    fn branch(&self, index: Index, branch: usize) -> Option<Index> {
+        match self.blocks.get(index)?.parts.get(branch)?.next {
+            Next::Some { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Called once the end of the innermost frame has been reached, loops start over.
+    fn end_frame(&mut self, thread: &mut Thread) -> Result<Flow, ScriptError> {
+        let Some(Frame {
+            kind: FrameKind::Loop { block, mut repeat },
+            ..
+        }) = thread.frames.pop()
+        else {
+            return Ok(Flow::Continue);
+        };
+
+        // the loop block might have been removed as well
+        if !self.blocks.contains(block) {
+            return Ok(Flow::Continue);
+        }
+
+        let again = match &mut repeat {
+            Repeat::Times(times) => {
+                *times -= 1.0;
+                *times >= 1.0
+            }
+            Repeat::Until => {
+                let values = self.field_values(block, thread)?;
+                !BlockFields {
+                    values: &values,
+                    random: &self.random,
+                }
+                .bool("condition")
+            }
+            Repeat::Forever => true,
+        };
+
+        if !again {
+            return Ok(Flow::Continue);
+        }
+
+        // empty loops count as well
+        self.spend(thread, block)?;
+        self.enter(thread, block, 0, FrameKind::Loop { block, repeat });
+        Ok(Flow::Yield)
+    }
+
+    /// Values of the widgets of a block, with its inputs evaluated.
+    fn field_values(
+        &mut self,
+        index: Index,
+        thread: &mut Thread,
+    ) -> Result<HashMap<Cow<'static, str>, Value>, ScriptError> {
+        let block = &self.blocks[index];
+        let mut values = block.values.clone();
+        let inputs: Vec<_> = block
+            .ordered_inputs()
+            .map(|(key, input)| (key.clone(), input))
+            .collect();
+
+        for (key, input) in inputs {
+            let value = self.report(input, thread)?;
+            values.insert(key, value);
+        }
+
+        Ok(values)
+    }
+
+    /// Evaluates a reporter.
+    fn report(&mut self, index: Index, thread: &mut Thread) -> Result<Value, ScriptError> {
+        self.evaluated.insert(index, Instant::now());
+        self.spend(thread, index)?;
+        let values = self.field_values(index, thread)?;
+        let fields = BlockFields {
+            values: &values,
+            random: &self.random,
+        };
+        let implementation = &mut self.blocks[index].implementation;
+        let value = match implementation.opcode() {
+            procedure::PARAMETER | procedure::BOOLEAN_PARAMETER => implementation
+                .mutation()
+                .and_then(|name| thread.argument(&name).cloned())
+                // Scratch reports 0 outside of procedures
+                .unwrap_or(Value::Number(0.0)),
+            opcode => match variable::evaluate(opcode, &fields, &mut self.variables) {
+                Some(value) => value,
+                None => implementation
+                    .report(&fields)
+                    .map_err(|error| thread.error(index, error))?,
+            },
+        };
+
+        if self.limits.too_long(&value) {
+            return Err(self.length_error(thread, index));
+        }
+
+        self.record(thread.id, index, &values, Some(&value));
+        Ok(value)
+    }
+
+    /// Counts a block the thread runs or evaluates against the instruction limits.
+    fn spend(&self, thread: &mut Thread, index: Index) -> Result<(), ScriptError> {
+        thread
+            .schedule
+            .budget
+            .spend(&self.limits)
+            .map_err(|error| thread.error(index, error.to_string().into()))
+    }
+
+    fn length_error(&self, thread: &Thread, index: Index) -> ScriptError {
+        let error = LimitError::Length(self.limits.max_length.unwrap()).to_string();
+        thread.error(index, error.into())
+    }
+
+    /// The `define` block of a procedure.
+    pub(crate) fn definition(&self, prototype: &str) -> Option<Index> {
+        self.blocks
+            .iter()
+            .find(|(_index, block)| {
+                block.implementation.opcode() == procedure::DEFINITION
+                    && block.implementation.mutation().as_deref() == Some(prototype)
+            })
+            .map(|(index, _block)| index)
+    }
+
+    /// Prototypes of the procedures defined on the workspace.
+    pub fn procedures(&self) -> Vec<Prototype> {
+        self.blocks
+            .iter()
+            .filter(|(_index, block)| block.implementation.opcode() == procedure::DEFINITION)
+            .filter_map(|(_index, block)| Prototype::parse(&block.implementation.mutation()?).ok())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::WHEN_GAME_STARTS,
+        procedure::CALL,
+        save::tests::{number, text, Workspace},
+        standard::{FOREVER, REPEAT, WAIT},
+        variable::{CHANGE_VARIABLE, SET_VARIABLE},
+    };
+
+    #[test]
+    fn running_blocks() {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("x"))]);
+        let join = workspace.block("operator_join", &[]);
+        workspace.input(set, "value", join);
+        let wait = workspace.block(WAIT, &[("duration", number(60.0))]);
+        let repeat = workspace.c_block(REPEAT, &[("times", number(2.0))], &[&[wait]]);
+        let after = workspace.block(WAIT, &[("duration", number(0.0))]);
+        workspace.stack(&[hat, set, repeat, after]);
+
+        let mut editor = workspace.load();
+        let indices: Vec<Index> = editor.blocks.iter().map(|(index, _block)| index).collect();
+        assert!(editor.running_blocks().is_empty());
+        editor.emit(Event::GameStarts);
+        editor.tick();
+
+        // the loop and the block waiting in it
+        let running = editor.running_blocks();
+        assert_eq!(running, HashSet::from([indices[repeat], indices[wait]]));
+        for block in [set, join, repeat, wait] {
+            assert!(editor.last_evaluated(indices[block]).is_some());
+        }
+        assert!(editor.last_evaluated(indices[after]).is_none());
+
+        editor.stop_all();
+        assert!(editor.running_blocks().is_empty());
+    }
+
+    #[test]
+    fn start_block() {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        let change = |workspace: &mut Workspace, by| {
+            workspace.block(
+                CHANGE_VARIABLE,
+                &[("variable", text("x")), ("value", number(by))],
+            )
+        };
+        let first = change(&mut workspace, 1.0);
+        let second = change(&mut workspace, 10.0);
+        let looped = change(&mut workspace, 100.0);
+        let forever = workspace.c_block(FOREVER, &[], &[&[looped]]);
+        let wait = workspace.block(WAIT, &[("duration", number(60.0))]);
+        let call = workspace.block(CALL, &[]);
+        workspace.mutation(call, "missing");
+        workspace.stack(&[first, second, forever, wait, call]);
+
+        let mut editor = workspace.load();
+        let indices: Vec<Index> = editor.blocks.iter().map(|(index, _block)| index).collect();
+        let x = |editor: &BlockEditor| editor.variables().get("x").unwrap().to_number();
+
+        // not the blocks below it
+        editor.start_block(indices[first]);
+        editor.tick();
+        assert!(editor.threads().is_empty());
+        assert_eq!(x(&editor), 1.0);
+
+        // loops and waits keep running in the next ticks
+        editor.start_block(indices[forever]);
+        let waiting = editor.start_block(indices[wait]);
+        editor.tick();
+        assert_eq!(editor.threads().len(), 2);
+        assert!(x(&editor) > 101.0);
+        assert!(editor
+            .threads()
+            .iter()
+            .any(|thread| thread.id() == waiting && thread.is_waiting()));
+        editor.stop_all();
+
+        let failing = editor.start_block(indices[call]);
+        editor.tick();
+        assert_eq!(editor.errors().len(), 1);
+        assert_eq!(editor.errors()[0].thread, failing);
+        assert_eq!(editor.errors()[0].block, indices[call]);
+    }
+}
@@ -0,0 +1,36 @@
+// lets `#[derive(Block)]` refer to this crate by name from inside of it
+extern crate self as egui_block_programming;
+
+pub mod block;
+pub mod codegen;
+pub mod compiler;
+pub mod connection;
+pub mod debugger;
+pub mod definition;
+pub mod event;
+pub mod field;
+pub mod geometry;
+pub mod inspector;
+pub mod interpreter;
+pub mod limits;
+mod monitor;
+pub mod palette;
+pub mod procedure;
+pub mod random;
+pub mod registry;
+pub mod save;
+mod scheduler;
+pub mod standard;
+pub mod style;
+pub mod template;
+pub mod trace;
+pub mod value;
+pub mod variable;
+pub mod vm;
+pub mod widgets;
+
+/// Paths used by the code `#[derive(Block)]` generates, so it doesn't depend on `eframe` itself.
+#[doc(hidden)]
+pub mod __private {
+    pub use eframe::epaint::Color32;
+}
@@ -0,0 +1,144 @@
+//! Limits keeping scripts from hanging or exhausting the host.
+
+use crate::{
+    block::{BlockEditor, BlockFields},
+    value::Value,
+    variable::{Variables, LIST_BLOCKS, SET_VARIABLE},
+};
+use std::{error::Error, fmt};
+
+/// Every limit is unlimited if `None`. The defaults keep scripts like a `forever` loop without a
+/// wait from freezing the host, without stopping scripts which run for a long time.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// blocks a thread may run or evaluate during one [`BlockEditor::tick`], it continues in
+    /// the next tick once it ran this many
+    pub instructions_per_tick: Option<u64>,
+    /// blocks a thread may run or evaluate until it finishes
+    pub total_instructions: Option<u64>,
+    /// procedure calls a thread may be inside of at once
+    pub max_depth: Option<usize>,
+    /// items of lists and characters of text, in variables and reported values
+    pub max_length: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            instructions_per_tick: Some(100_000),
+            total_instructions: None,
+            max_depth: Some(1000),
+            max_length: Some(200_000),
+        }
+    }
+}
+
+/// Stops the thread exceeding a limit, see [`BlockEditor::errors`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimitError {
+    /// [`Limits::total_instructions`]
+    Instructions,
+    Depth(usize),
+    Length(usize),
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::Instructions => write!(f, "script terminated: budget exceeded"),
+            LimitError::Depth(depth) => write!(
+                f,
+                "script terminated: more than {depth} nested procedure calls"
+            ),
+            LimitError::Length(length) => write!(
+                f,
+                "script terminated: list or text longer than {length} items or characters"
+            ),
+        }
+    }
+}
+
+impl Error for LimitError {}
+
+/// Blocks a thread ran or evaluated, counted against the instruction limits.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Budget {
+    total: u64,
+    /// during the current tick
+    tick: u64,
+}
+
+impl Budget {
+    /// Counts a block, fails once the thread ran more than [`Limits::total_instructions`].
+    pub fn spend(&mut self, limits: &Limits) -> Result<(), LimitError> {
+        self.total += 1;
+        self.tick += 1;
+        if limits
+            .total_instructions
+            .is_some_and(|max| self.total > max)
+        {
+            return Err(LimitError::Instructions);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the thread ran [`Limits::instructions_per_tick`] blocks and waits for the next
+    /// tick.
+    pub fn is_spent(&self, limits: &Limits) -> bool {
+        limits
+            .instructions_per_tick
+            .is_some_and(|max| self.tick >= max)
+    }
+
+    pub fn next_tick(&mut self) {
+        self.tick = 0;
+    }
+}
+
+impl Limits {
+    /// Whether the value is a list or text longer than [`Limits::max_length`].
+    pub(crate) fn too_long(&self, value: &Value) -> bool {
+        let length = match value {
+            Value::Text(text) => text.chars().count(),
+            Value::List(items) => items.len(),
+            _ => return false,
+        };
+
+        self.max_length.is_some_and(|max| length > max)
+    }
+
+    /// Whether the variable or list written by a variable or list block is longer than
+    /// [`Limits::max_length`].
+    pub(crate) fn exceeded_by(
+        &self,
+        opcode: &str,
+        fields: &BlockFields,
+        variables: &Variables,
+    ) -> bool {
+        let Some(max) = self.max_length else {
+            return false;
+        };
+
+        match opcode {
+            SET_VARIABLE => variables
+                .get(&fields.text("variable"))
+                .is_some_and(|value| self.too_long(value)),
+            _ if LIST_BLOCKS.contains(&opcode) => variables
+                .list(&fields.text("list"))
+                .is_some_and(|items| items.len() > max),
+            _ => false,
+        }
+    }
+}
+
+impl BlockEditor {
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Applies to the running threads as well.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+}
@@ -1,8 +1,25 @@
-mod block;
-
-use block::{Block, BlockDescription, BlockEditor, BlockShape, BlockWidget};
-use eframe::{egui, epaint::Pos2};
-use std::num::NonZeroUsize;
+use eframe::{
+    egui,
+    epaint::{Color32, Pos2},
+};
+use egui_block_programming::{
+    block::{
+        Block, BlockCategory, BlockDescription, BlockEditor, BlockFields, BlockShape, RunError,
+    },
+    debugger,
+    definition::{BlockDefinitions, BlockHandlers},
+    event::Event,
+    field::{Angle, DerivedBlock},
+    inspector,
+    palette::Palette,
+    registry::BlockRegistry,
+    save::SavedWorkspace,
+    standard,
+    template::ContentBuilder,
+    trace::{self, Trace},
+    value::{Value, ValueType},
+};
+use std::{error::Error, fs, num::NonZeroUsize, time::Instant};
 
 fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
@@ -14,130 +31,328 @@ fn main() -> Result<(), eframe::Error> {
     )
 }
 
+const SAVE_PATH: &str = "workspace.json";
+const TRACE_PATH: &str = "trace.json";
+const RUST_PATH: &str = "program.rs";
+const DEFINITIONS_PATH: &str = "assets/blocks.toml";
+
 struct Main {
     block_editor: BlockEditor,
+    registry: BlockRegistry,
+    palette: Palette,
 }
 
 impl Default for Main {
     fn default() -> Self {
+        let mut registry = BlockRegistry::default();
+        standard::register(&mut registry);
+        registry.register(|| TestingBlock {});
+        registry.register(|| IfBlock {});
+        registry.register(PenBlock::default);
+        registry.register(JoinBlock::default);
+        registry.register(EqualsBlock::default);
+        if let Err(err) = register_definitions(&mut registry) {
+            eprintln!("failed to load {DEFINITIONS_PATH}: {err}");
+        }
+
         let mut block_editor = BlockEditor::default();
         block_editor.add_block(Pos2::new(50.0, 50.0), TestingBlock {});
         block_editor.add_block(Pos2::new(100.0, 100.0), IfBlock {});
+        block_editor.add_block(Pos2::new(150.0, 250.0), PenBlock::default());
+        block_editor.add_block(Pos2::new(50.0, 350.0), JoinBlock::default());
+        block_editor.add_block(Pos2::new(250.0, 350.0), EqualsBlock::default());
 
-        Self { block_editor }
+        Self {
+            block_editor,
+            registry,
+            palette: Palette::default(),
+        }
     }
 }
 
 impl eframe::App for Main {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // keys typed into text fields don't count
+        if !ctx.wants_keyboard_input() {
+            let pressed: Vec<egui::Key> = ctx.input(|input| {
+                input
+                    .events
+                    .iter()
+                    .filter_map(|event| match event {
+                        egui::Event::Key {
+                            key,
+                            pressed: true,
+                            repeat: false,
+                            ..
+                        } => Some(*key),
+                        _ => None,
+                    })
+                    .collect()
+            });
+            for key in pressed {
+                self.block_editor.emit(Event::KeyPressed(key));
+            }
+        }
+
+        egui::SidePanel::left("palette").show(ctx, |ui| {
+            self.palette
+                .show(ui, &self.registry, &mut self.block_editor);
+        });
+
+        egui::SidePanel::right("inspector").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                inspector::panel(ui, &mut self.block_editor);
+            });
+        });
+
         egui::Window::new("My Window").show(ctx, |ui| {
             ui.label("Hello World!");
             egui::widgets::global_dark_light_mode_buttons(ui);
 
-            if ui.button("stack").clicked() {
-                self.block_editor
-                    .add_block(Pos2::new(150.0, 150.0), TestingBlock {});
-            }
+            ui.horizontal(|ui| {
+                if ui.button("▶").on_hover_text("start").clicked() {
+                    self.block_editor.emit(Event::GameStarts);
+                }
+
+                if ui.button("⏹").on_hover_text("stop").clicked() {
+                    self.block_editor.stop_all();
+                }
 
+                if ui.button("stack").clicked() {
+                    self.block_editor
+                        .add_block(Pos2::new(150.0, 150.0), TestingBlock {});
+                }
+
+                if ui.button("save").clicked() {
+                    let saved = self.block_editor.save();
+                    let json = serde_json::to_string_pretty(&saved).unwrap();
+                    if let Err(err) = fs::write(SAVE_PATH, json) {
+                        eprintln!("failed to save {SAVE_PATH}: {err}");
+                    }
+                }
+
+                if ui.button("load").clicked() {
+                    match load(&self.registry) {
+                        Ok(block_editor) => self.block_editor = block_editor,
+                        Err(err) => eprintln!("failed to load {SAVE_PATH}: {err}"),
+                    }
+                }
+
+                if ui.button("export Rust").clicked() {
+                    let code = self.block_editor.generate_rust("Program");
+                    if let Err(err) = fs::write(RUST_PATH, code) {
+                        eprintln!("failed to save {RUST_PATH}: {err}");
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                if self.block_editor.is_recording() {
+                    if ui.button("stop recording").clicked() {
+                        let trace = self.block_editor.stop_recording().unwrap();
+                        let json = serde_json::to_string(&trace).unwrap();
+                        if let Err(err) = fs::write(TRACE_PATH, json) {
+                            eprintln!("failed to save {TRACE_PATH}: {err}");
+                        }
+                    }
+                } else if ui.button("record").clicked() {
+                    self.block_editor.start_recording();
+                }
+
+                if ui.button("replay").clicked() {
+                    match replay(&self.registry) {
+                        Ok(block_editor) => self.block_editor = block_editor,
+                        Err(err) => eprintln!("failed to replay {TRACE_PATH}: {err}"),
+                    }
+                }
+            });
+
+            trace::replay_controls(ui, &mut self.block_editor);
+
+            debugger::toolbar(ui, &mut self.block_editor);
             ui.add(&mut self.block_editor);
         });
+
+        if !self.block_editor.errors().is_empty() {
+            egui::Window::new("Errors").show(ctx, |ui| {
+                debugger::error_list(ui, &mut self.block_editor);
+            });
+        }
     }
 }
 
+fn register_definitions(registry: &mut BlockRegistry) -> Result<(), Box<dyn Error>> {
+    let mut handlers = BlockHandlers::default();
+    handlers.on_run("say", |fields| {
+        println!("{} ({}s)", fields.text("message"), fields.number("secs"));
+        Ok(())
+    });
+    handlers.on_run("point_towards", |fields| {
+        println!("pointing towards {}", fields.text("target"));
+        Ok(())
+    });
+    handlers.on_run("set_volume", |fields| {
+        let volume = fields.number("volume");
+        if !(0.0..=100.0).contains(&volume) {
+            return Err(format!("volume {volume}% is out of range").into());
+        }
+
+        println!("{} volume: {volume}%", fields.text("channel"));
+        Ok(())
+    });
+    let start = Instant::now();
+    handlers.on_report("timer", move |_fields| {
+        Ok(start.elapsed().as_secs_f64().into())
+    });
+    handlers.menu("target", || {
+        ["mouse-pointer", "Sprite1", "Sprite2"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    });
+
+    let toml = fs::read_to_string(DEFINITIONS_PATH)?;
+    registry.register_definitions(&BlockDefinitions::from_toml(&toml)?, &handlers)?;
+    Ok(())
+}
+
+fn load(registry: &BlockRegistry) -> Result<BlockEditor, Box<dyn Error>> {
+    let json = fs::read_to_string(SAVE_PATH)?;
+    let saved: SavedWorkspace = serde_json::from_str(&json)?;
+    Ok(BlockEditor::load(&saved, registry)?)
+}
+
+fn replay(registry: &BlockRegistry) -> Result<BlockEditor, Box<dyn Error>> {
+    let json = fs::read_to_string(TRACE_PATH)?;
+    let trace: Trace = serde_json::from_str(&json)?;
+    Ok(BlockEditor::replay(&trace, registry)?)
+}
+
 struct TestingBlock {}
 
 impl TestingBlock {
-    const STEPS: &'static str = "steps";
-    const TESTING: &'static str = "testing";
+    const TARGET: &'static str = "target";
 }
 
 impl Block for TestingBlock {
+    fn opcode(&self) -> &str {
+        "testing"
+    }
+
     fn describe(&mut self) -> BlockDescription {
         BlockDescription {
             shape: BlockShape::Stack,
-            content: vec![vec![
-                BlockWidget::Label { text: "move" },
-                BlockWidget::NumberEdit {
-                    key: Self::STEPS,
-                    default: 0,
-                },
-                BlockWidget::Label { text: "steps" },
-                BlockWidget::TextEdit {
-                    key: Self::TESTING,
-                    default: ":3",
-                },
-                BlockWidget::Label { text: "abc" },
-            ]],
+            category: BlockCategory::Motion,
+            color: None,
+            content: ContentBuilder::new()
+                .part(
+                    "move %{steps:number(step=0.5)} steps %{testing:text=:3} abc \
+                     toward %{target:dropdown=mouse-pointer}",
+                )
+                .build()
+                .unwrap(),
+            output: ValueType::Any,
+        }
+    }
+
+    fn dropdown_options(&mut self, key: &str) -> Vec<String> {
+        match key {
+            Self::TARGET => ["mouse-pointer", "Sprite1", "Sprite2"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            _ => Vec::new(),
         }
     }
 
-    fn run(&mut self) {}
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        Ok(())
+    }
 }
 
 struct IfBlock {}
 
 impl Block for IfBlock {
+    fn opcode(&self) -> &str {
+        "if"
+    }
+
     fn describe(&mut self) -> BlockDescription {
         BlockDescription {
             shape: BlockShape::C {
                 branches: NonZeroUsize::new(3).unwrap(),
             },
-            content: vec![
-                vec![
-                    BlockWidget::Label { text: "move" },
-                    BlockWidget::NumberEdit {
-                        key: "steps0",
-                        default: 0,
-                    },
-                    BlockWidget::Label { text: "steps" },
-                    BlockWidget::TextEdit {
-                        key: "testing0",
-                        default: ":3",
-                    },
-                    BlockWidget::Label { text: "abc" },
-                ],
-                vec![
-                    BlockWidget::Label { text: "move" },
-                    BlockWidget::NumberEdit {
-                        key: "steps1",
-                        default: 0,
-                    },
-                    BlockWidget::Label { text: "steps" },
-                    BlockWidget::TextEdit {
-                        key: "testing1",
-                        default: ":3",
-                    },
-                    BlockWidget::Label { text: "abc" },
-                ],
-                vec![
-                    BlockWidget::Label { text: "move" },
-                    BlockWidget::NumberEdit {
-                        key: "steps2",
-                        default: 0,
-                    },
-                    BlockWidget::Label { text: "steps" },
-                    BlockWidget::TextEdit {
-                        key: "testing2",
-                        default: ":3",
-                    },
-                    BlockWidget::Label { text: "abc" },
-                ],
-                vec![
-                    BlockWidget::Label { text: "move" },
-                    BlockWidget::NumberEdit {
-                        key: "steps3",
-                        default: 0,
-                    },
-                    BlockWidget::Label { text: "steps" },
-                    BlockWidget::TextEdit {
-                        key: "testing3",
-                        default: ":3",
-                    },
-                    BlockWidget::Label { text: "abc" },
-                ],
-            ],
+            category: BlockCategory::Control,
+            color: None,
+            content: (0..4)
+                .fold(ContentBuilder::new(), |builder, i| {
+                    builder.part(format!(
+                        "move %{{steps{i}:integer}} steps %{{testing{i}:text=:3}} abc"
+                    ))
+                })
+                .build()
+                .unwrap(),
+            output: ValueType::Any,
         }
     }
 
-    fn run(&mut self) {}
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        Ok(())
+    }
+}
+
+#[derive(Block, Default)]
+#[block(label = "pen {color} size {size} direction {direction} down {down}")]
+struct PenBlock {
+    #[block(default = Color32::from_rgb(0x00, 0x99, 0xFF))]
+    color: Color32,
+    #[block(slider, min = 1, max = 10, step = 0.5, default = 1.0)]
+    size: f64,
+    direction: Angle,
+    #[block(default = true)]
+    down: bool,
+}
+
+impl DerivedBlock for PenBlock {
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        println!(
+            "pen {:?} size {} direction {}° down {}",
+            self.color, self.size, self.direction.0, self.down
+        );
+        Ok(())
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    shape = "reporter",
+    category = "operators",
+    output = "text",
+    label = "join {a} {b}"
+)]
+struct JoinBlock {
+    #[block(default = "apple ")]
+    a: String,
+    #[block(default = "banana")]
+    b: String,
+}
+
+impl DerivedBlock for JoinBlock {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(format!("{}{}", self.a, self.b).into())
+    }
+}
+
+#[derive(Block, Default)]
+#[block(shape = "boolean", category = "operators", label = "{a} = {b}")]
+struct EqualsBlock {
+    a: Value,
+    #[block(default = "50")]
+    b: Value,
+}
+
+impl DerivedBlock for EqualsBlock {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(self.a.equals(&self.b).into())
+    }
 }
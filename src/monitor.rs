@@ -0,0 +1,74 @@
+//! Shows the current value of a variable or the contents of a list on the workspace.
+
+use crate::variable::{VariableKind, Variables};
+use eframe::{
+    egui::{Align, Frame, Layout, ScrollArea, Sense, Ui},
+    epaint::{Pos2, Rect, Vec2},
+};
+
+const MONITOR_WIDTH: f32 = 140.0;
+/// lists with more items can be scrolled
+const LIST_HEIGHT: f32 = 160.0;
+
+pub(crate) struct Monitor {
+    pub kind: VariableKind,
+    pub name: String,
+    /// on the workspace, like block positions
+    pub position: Pos2,
+}
+
+impl Monitor {
+    /// Paints the monitor relative to the workspace `origin`, dragging it moves it.
+    pub(crate) fn ui(&mut self, ui: &mut Ui, origin: Pos2, clip_rect: Rect, variables: &Variables) {
+        let rect = Rect::from_min_size(
+            origin + self.position.to_vec2(),
+            Vec2::new(MONITOR_WIDTH, LIST_HEIGHT * 2.0),
+        );
+        let mut ui =
+            ui.child_ui_with_id_source(rect, Layout::top_down(Align::Min), (self.kind, &self.name));
+        ui.set_clip_rect(clip_rect);
+
+        let frame = Frame::popup(ui.style()).show(&mut ui, |ui| {
+            ui.set_width(MONITOR_WIDTH);
+            ui.strong(&self.name);
+
+            match self.kind {
+                VariableKind::Variable => {
+                    let value = variables
+                        .get(&self.name)
+                        .map(|value| value.to_text().into_owned())
+                        .unwrap_or_default();
+                    ui.label(value);
+                }
+                VariableKind::List => {
+                    let items = variables
+                        .list(&self.name)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    ScrollArea::vertical()
+                        .id_source((self.kind, &self.name))
+                        .max_height(LIST_HEIGHT)
+                        .auto_shrink([false, true])
+                        .show(ui, |ui| {
+                            if items.is_empty() {
+                                ui.weak("(empty)");
+                            }
+
+                            for (i, item) in items.iter().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.weak((i + 1).to_string());
+                                    ui.label(item.to_text());
+                                });
+                            }
+                        });
+                    ui.weak(format!("length {}", items.len()));
+                }
+            }
+        });
+
+        let response = ui.interact(frame.response.rect, ui.id().with("drag"), Sense::drag());
+        if response.dragged() {
+            self.position += response.drag_delta();
+        }
+    }
+}
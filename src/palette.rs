@@ -0,0 +1,481 @@
+//! Lists the blocks of a [`BlockRegistry`] by category, clicking one adds it to the editor.
+
+use crate::{
+    block::{Block, BlockCategory, BlockDescription, BlockEditor, BlockWidget},
+    procedure::{self, Parameter, ParameterKind, ProcedureCall, Prototype, PrototypeElement},
+    registry::BlockRegistry,
+    style::BlockEditorStyle,
+    value::Value,
+    variable::{self, ListContents, VariableKind, VariableReporter, VariableScope},
+};
+use eframe::{
+    egui::{self, Key, RichText, ScrollArea, Ui},
+    epaint::{Pos2, Vec2},
+};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// where new blocks are put, relative to the visible part of the workspace
+const NEW_BLOCK_POSITION: Pos2 = Pos2::new(20.0, 20.0);
+
+enum VariableDialog {
+    Create {
+        kind: VariableKind,
+        name: String,
+        scope: VariableScope,
+    },
+    Rename {
+        kind: VariableKind,
+        variable: String,
+        name: String,
+    },
+}
+
+/// Prototype editor of a new or an existing procedure.
+struct BlockDialog {
+    /// `None` for new procedures
+    editing: Option<Prototype>,
+    /// with the original names of parameters, to keep the arguments of renamed ones
+    elements: Vec<(PrototypeElement, Option<String>)>,
+}
+
+#[derive(Default)]
+pub struct Palette {
+    category: Option<BlockCategory>,
+    dialog: Option<VariableDialog>,
+    block_dialog: Option<BlockDialog>,
+    /// of the last attempt to confirm a dialog
+    error: Option<String>,
+}
+
+impl Palette {
+    pub fn show(&mut self, ui: &mut Ui, registry: &BlockRegistry, editor: &mut BlockEditor) {
+        let style = editor
+            .style()
+            .cloned()
+            .unwrap_or_else(|| BlockEditorStyle::from_visuals(ui.visuals()));
+
+        ui.horizontal_wrapped(|ui| {
+            for category in BlockCategory::ALL {
+                let empty = registry
+                    .opcodes()
+                    .all(|opcode| registry.category(opcode) != Some(category));
+                if empty {
+                    continue;
+                }
+
+                ui.label(RichText::new("⏺").color(style.category_fill(category)));
+                let selected = self.category == Some(category);
+                if ui.selectable_label(selected, category.name()).clicked() {
+                    self.category = Some(category);
+                }
+            }
+        });
+        ui.separator();
+
+        let Some(category) = self.category else {
+            return;
+        };
+
+        let position = NEW_BLOCK_POSITION - editor.offset();
+        ScrollArea::vertical().show(ui, |ui| {
+            let kind = match category {
+                BlockCategory::Variables => Some(VariableKind::Variable),
+                BlockCategory::Lists => Some(VariableKind::List),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                self.variables(ui, editor, kind, position);
+                ui.separator();
+            }
+
+            if category == BlockCategory::MyBlocks {
+                self.procedures(ui, editor, position);
+                ui.separator();
+            }
+
+            let opcodes = registry
+                .opcodes()
+                .filter(|opcode| registry.category(opcode) == Some(category))
+                // there are buttons for each variable, list and procedure instead
+                .filter(|opcode| {
+                    ![
+                        variable::VARIABLE,
+                        variable::LIST_CONTENTS,
+                        procedure::DEFINITION,
+                        procedure::CALL,
+                        procedure::PARAMETER,
+                        procedure::BOOLEAN_PARAMETER,
+                    ]
+                    .contains(opcode)
+                })
+                .sorted_unstable();
+            for opcode in opcodes {
+                let mut block = registry.create(opcode).unwrap();
+                if ui.button(label(&block.describe())).clicked() {
+                    editor.add_boxed(position, block);
+                }
+            }
+        });
+
+        self.dialog(ui.ctx(), editor);
+        self.block_dialog(ui.ctx(), editor, position);
+    }
+
+    fn variables(
+        &mut self,
+        ui: &mut Ui,
+        editor: &mut BlockEditor,
+        kind: VariableKind,
+        position: Pos2,
+    ) {
+        let make = match kind {
+            VariableKind::Variable => "Make a variable",
+            VariableKind::List => "Make a list",
+        };
+        if ui.button(make).clicked() {
+            self.dialog = Some(VariableDialog::Create {
+                kind,
+                name: String::new(),
+                scope: VariableScope::Global,
+            });
+            self.block_dialog = None;
+            self.error = None;
+        }
+
+        let variables: Vec<_> = match kind {
+            VariableKind::Variable => editor
+                .variables()
+                .iter()
+                .map(|(name, scope, value)| (name.to_string(), scope, value.to_text().into_owned()))
+                .collect(),
+            VariableKind::List => editor
+                .variables()
+                .lists()
+                .map(|(name, scope, items)| {
+                    (name.to_string(), scope, format!("length {}", items.len()))
+                })
+                .collect(),
+        };
+        for (name, scope, value) in variables {
+            ui.horizontal(|ui| {
+                let mut monitor = editor.has_monitor(kind, &name);
+                if ui
+                    .checkbox(&mut monitor, "")
+                    .on_hover_text("show on the workspace")
+                    .changed()
+                {
+                    editor.set_monitor(kind, &name, monitor);
+                }
+
+                let response = ui.button(&name).on_hover_text(match scope {
+                    VariableScope::Global => "for all objects",
+                    VariableScope::Object => "for this object only",
+                });
+                ui.weak(value);
+
+                if response.clicked() {
+                    let (index, key) = match kind {
+                        VariableKind::Variable => (
+                            editor.add_block(position, VariableReporter::default()),
+                            "variable",
+                        ),
+                        VariableKind::List => {
+                            (editor.add_block(position, ListContents::default()), "list")
+                        }
+                    };
+                    editor.set_field(index, key, Value::Text(name.clone()));
+                }
+
+                response.context_menu(|ui| {
+                    if ui.button("Rename").clicked() {
+                        self.dialog = Some(VariableDialog::Rename {
+                            kind,
+                            variable: name.clone(),
+                            name: name.clone(),
+                        });
+                        self.block_dialog = None;
+                        self.error = None;
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Delete").clicked() {
+                        match kind {
+                            VariableKind::Variable => editor.delete_variable(&name),
+                            VariableKind::List => editor.delete_list(&name),
+                        }
+                        .unwrap();
+                        ui.close_menu();
+                    }
+                });
+            });
+        }
+    }
+
+    fn procedures(&mut self, ui: &mut Ui, editor: &mut BlockEditor, position: Pos2) {
+        if ui.button("Make a block").clicked() {
+            self.block_dialog = Some(BlockDialog {
+                editing: None,
+                elements: vec![(PrototypeElement::Label("block name".to_string()), None)],
+            });
+            self.dialog = None;
+            self.error = None;
+        }
+
+        for prototype in editor.procedures() {
+            let mut call = ProcedureCall {
+                prototype: prototype.clone(),
+            };
+            let response = ui.button(label(&call.describe()));
+            if response.clicked() {
+                editor.add_block(position, call);
+            }
+
+            response.context_menu(|ui| {
+                if ui.button("Edit").clicked() {
+                    self.block_dialog = Some(BlockDialog {
+                        editing: Some(prototype.clone()),
+                        elements: prototype
+                            .elements
+                            .iter()
+                            .map(|element| match element {
+                                PrototypeElement::Label(_) => (element.clone(), None),
+                                PrototypeElement::Parameter { name, .. } => {
+                                    (element.clone(), Some(name.clone()))
+                                }
+                            })
+                            .collect(),
+                    });
+                    self.dialog = None;
+                    self.error = None;
+                    ui.close_menu();
+                }
+            });
+
+            // parameter reporters, for the script of the definition
+            ui.horizontal_wrapped(|ui| {
+                ui.add_space(ui.spacing().indent);
+                for (name, kind) in prototype.parameters() {
+                    let text = match kind {
+                        ParameterKind::Bool => format!("<{name}>"),
+                        _ => format!("({name})"),
+                    };
+                    if ui.small_button(text).clicked() {
+                        editor.add_block(position, Parameter::new(name, kind));
+                    }
+                }
+            });
+        }
+    }
+
+    fn block_dialog(&mut self, ctx: &egui::Context, editor: &mut BlockEditor, position: Pos2) {
+        let Some(dialog) = &mut self.block_dialog else {
+            return;
+        };
+
+        let title = match dialog.editing {
+            Some(_) => "Edit block",
+            None => "Make a block",
+        };
+
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .default_pos(ctx.screen_rect().center() - Vec2::new(150.0, 100.0))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let mut remove = None;
+                for (i, (element, _original)) in dialog.elements.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        match element {
+                            PrototypeElement::Label(text) => {
+                                ui.label("label");
+                                ui.text_edit_singleline(text);
+                            }
+                            PrototypeElement::Parameter { name, kind } => {
+                                ui.label(match kind {
+                                    ParameterKind::Number => "number",
+                                    ParameterKind::Text => "text",
+                                    ParameterKind::Bool => "boolean",
+                                });
+                                ui.text_edit_singleline(name);
+                            }
+                        }
+
+                        if ui.small_button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+
+                if let Some(i) = remove {
+                    dialog.elements.remove(i);
+                }
+
+                ui.horizontal_wrapped(|ui| {
+                    let mut add = |element| dialog.elements.push((element, None));
+                    if ui.button("Add label").clicked() {
+                        add(PrototypeElement::Label("label".to_string()));
+                    }
+
+                    let parameters = [
+                        ("Add number input", ParameterKind::Number, "number"),
+                        ("Add text input", ParameterKind::Text, "text"),
+                        ("Add boolean input", ParameterKind::Bool, "boolean"),
+                    ];
+                    for (text, kind, name) in parameters {
+                        if ui.button(text).clicked() {
+                            add(PrototypeElement::Parameter {
+                                name: name.to_string(),
+                                kind,
+                            });
+                        }
+                    }
+                });
+
+                let prototype = Prototype {
+                    elements: dialog
+                        .elements
+                        .iter()
+                        .map(|(element, _original)| element.clone())
+                        .collect(),
+                };
+                ui.weak(prototype.to_string());
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                if ui.button("OK").clicked() {
+                    let result = match &dialog.editing {
+                        Some(editing) => {
+                            let renamed: HashMap<String, String> = dialog
+                                .elements
+                                .iter()
+                                .filter_map(|(element, original)| match element {
+                                    PrototypeElement::Parameter { name, .. } => {
+                                        Some((original.clone()?, name.trim().to_string()))
+                                    }
+                                    PrototypeElement::Label(_) => None,
+                                })
+                                .collect();
+                            editor.edit_procedure(editing, &prototype, &renamed)
+                        }
+                        None => editor.define_procedure(position, &prototype).map(drop),
+                    };
+
+                    match result {
+                        Ok(()) => done = true,
+                        Err(err) => self.error = Some(err.to_string()),
+                    }
+                }
+            });
+
+        if done || !open {
+            self.block_dialog = None;
+            self.error = None;
+        }
+    }
+
+    fn dialog(&mut self, ctx: &egui::Context, editor: &mut BlockEditor) {
+        let Some(dialog) = &mut self.dialog else {
+            return;
+        };
+
+        let title = match dialog {
+            VariableDialog::Create {
+                kind: VariableKind::Variable,
+                ..
+            } => "New variable",
+            VariableDialog::Create {
+                kind: VariableKind::List,
+                ..
+            } => "New list",
+            VariableDialog::Rename {
+                kind: VariableKind::Variable,
+                ..
+            } => "Rename variable",
+            VariableDialog::Rename {
+                kind: VariableKind::List,
+                ..
+            } => "Rename list",
+        };
+
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .default_pos(ctx.screen_rect().center() - Vec2::new(100.0, 50.0))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let (VariableDialog::Create { name, .. } | VariableDialog::Rename { name, .. }) =
+                    dialog;
+                let response = ui.text_edit_singleline(name);
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+
+                if let VariableDialog::Create { scope, .. } = dialog {
+                    ui.radio_value(scope, VariableScope::Global, "For all objects");
+                    ui.radio_value(scope, VariableScope::Object, "For this object only");
+                }
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                if ui.button("OK").clicked() || submitted {
+                    let variables = editor.variables_mut();
+                    let result = match dialog {
+                        VariableDialog::Create {
+                            kind: VariableKind::Variable,
+                            name,
+                            scope,
+                        } => variables.create(name, *scope),
+                        VariableDialog::Create {
+                            kind: VariableKind::List,
+                            name,
+                            scope,
+                        } => variables.create_list(name, *scope),
+                        VariableDialog::Rename {
+                            kind: VariableKind::Variable,
+                            variable,
+                            name,
+                        } => editor.rename_variable(variable, name),
+                        VariableDialog::Rename {
+                            kind: VariableKind::List,
+                            variable,
+                            name,
+                        } => editor.rename_list(variable, name),
+                    };
+
+                    match result {
+                        Ok(()) => done = true,
+                        Err(err) => self.error = Some(err.to_string()),
+                    }
+                }
+            });
+
+        if done || !open {
+            self.dialog = None;
+            self.error = None;
+        }
+    }
+}
+
+/// Text of a block, e.g. `move (10) steps toward [mouse-pointer]`.
+fn label(description: &BlockDescription) -> String {
+    description
+        .content
+        .iter()
+        .flatten()
+        .map(|widget| match widget {
+            BlockWidget::Label { text } => text.to_string(),
+            BlockWidget::Dropdown { key, default, .. } if default.is_empty() => format!("[{key}]"),
+            BlockWidget::Dropdown { default, .. } => format!("[{default}]"),
+            BlockWidget::Slot { .. } => "( )".to_string(),
+            _ => format!("({})", widget.default_value().unwrap().to_text()),
+        })
+        .join(" ")
+}
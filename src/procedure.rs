@@ -0,0 +1,414 @@
+//! Blocks defined on the workspace ("My Blocks").
+//!
+//! A procedure is defined by a `define` hat block, the script below it runs whenever one of its
+//! call blocks runs. Both know the procedure by its [`Prototype`], written like
+//! `"jump %n[height] times %b[fast]"`: `%n[..]` is a number parameter, `%s[..]` a text
+//! parameter, `%b[..]` a boolean parameter and `%%` a literal `%`. The script reads the
+//! arguments with parameter reporters.
+
+use crate::{
+    block::{
+        Block, BlockCategory, BlockDescription, BlockFields, BlockShape, BlockWidget, NumberFormat,
+        RunError,
+    },
+    value::ValueType,
+};
+use std::{borrow::Cow, collections::HashSet, error::Error, fmt};
+
+pub const DEFINITION: &str = "procedures_definition";
+pub const CALL: &str = "procedures_call";
+pub const PARAMETER: &str = "argument_reporter_string_number";
+pub const BOOLEAN_PARAMETER: &str = "argument_reporter_boolean";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParameterKind {
+    Number,
+    Text,
+    Bool,
+}
+
+impl ParameterKind {
+    fn shorthand(&self) -> char {
+        match self {
+            ParameterKind::Number => 'n',
+            ParameterKind::Text => 's',
+            ParameterKind::Bool => 'b',
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PrototypeElement {
+    Label(String),
+    Parameter { name: String, kind: ParameterKind },
+}
+
+/// Signature of a procedure, also identifies it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Prototype {
+    pub elements: Vec<PrototypeElement>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcedureError {
+    /// the prototype has no elements, or only empty labels
+    Empty,
+    InvalidParameter(String),
+    DuplicateParameter(String),
+    /// `%` followed by something that isn't a parameter
+    InvalidSyntax(String),
+    /// another procedure has the same prototype
+    Exists(String),
+    Unknown(String),
+    /// setting the mutation of a block changed its number of parts
+    PartCount {
+        opcode: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for ProcedureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcedureError::Empty => write!(f, "blocks need a label or a parameter"),
+            ProcedureError::InvalidParameter(name) => {
+                write!(f, "invalid parameter name \"{name}\"")
+            }
+            ProcedureError::DuplicateParameter(name) => {
+                write!(f, "there's more than one parameter named \"{name}\"")
+            }
+            ProcedureError::InvalidSyntax(prototype) => {
+                write!(f, "invalid prototype \"{prototype}\"")
+            }
+            ProcedureError::Exists(prototype) => {
+                write!(f, "a block \"{prototype}\" already exists")
+            }
+            ProcedureError::Unknown(prototype) => write!(f, "unknown block \"{prototype}\""),
+            ProcedureError::PartCount {
+                opcode,
+                expected,
+                found,
+            } => write!(f, "{opcode}: expected {expected} parts, found {found}"),
+        }
+    }
+}
+
+impl Error for ProcedureError {}
+
+impl Prototype {
+    pub fn parse(text: &str) -> Result<Self, ProcedureError> {
+        let invalid = || ProcedureError::InvalidSyntax(text.to_string());
+        let mut elements = Vec::new();
+        let mut label = String::new();
+        let mut rest = text;
+        while let Some(percent) = rest.find('%') {
+            label.push_str(&rest[..percent]);
+            rest = &rest[percent + 1..];
+
+            if let Some(after) = rest.strip_prefix('%') {
+                label.push('%');
+                rest = after;
+                continue;
+            }
+
+            let kind = match rest.chars().next() {
+                Some('n') => ParameterKind::Number,
+                Some('s') => ParameterKind::Text,
+                Some('b') => ParameterKind::Bool,
+                _ => return Err(invalid()),
+            };
+            let (name, after) = rest[1..]
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once(']'))
+                .ok_or_else(invalid)?;
+
+            push_label(&mut elements, &mut label);
+            elements.push(PrototypeElement::Parameter {
+                name: name.to_string(),
+                kind,
+            });
+            rest = after;
+        }
+
+        label.push_str(rest);
+        push_label(&mut elements, &mut label);
+
+        let prototype = Prototype { elements };
+        prototype.validate()?;
+        Ok(prototype)
+    }
+
+    pub fn validate(&self) -> Result<(), ProcedureError> {
+        let empty = self.elements.iter().all(|element| match element {
+            PrototypeElement::Label(label) => label.trim().is_empty(),
+            PrototypeElement::Parameter { .. } => false,
+        });
+        if empty {
+            return Err(ProcedureError::Empty);
+        }
+
+        let mut names = HashSet::new();
+        for (name, _kind) in self.parameters() {
+            if name.trim().is_empty() || name.contains(['[', ']']) {
+                return Err(ProcedureError::InvalidParameter(name.to_string()));
+            }
+
+            if !names.insert(name) {
+                return Err(ProcedureError::DuplicateParameter(name.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn parameters(&self) -> impl Iterator<Item = (&str, ParameterKind)> {
+        self.elements.iter().filter_map(|element| match element {
+            PrototypeElement::Label(_) => None,
+            PrototypeElement::Parameter { name, kind } => Some((name.as_str(), *kind)),
+        })
+    }
+
+    /// Widgets of call blocks, keyed by the parameter names.
+    fn call_widgets(&self) -> Vec<BlockWidget> {
+        self.elements
+            .iter()
+            .map(|element| match element {
+                PrototypeElement::Label(label) => BlockWidget::Label {
+                    text: label.trim().to_string().into(),
+                },
+                PrototypeElement::Parameter { name, kind } => {
+                    let key = Cow::Owned(name.clone());
+                    match kind {
+                        ParameterKind::Number => BlockWidget::NumberEdit {
+                            key,
+                            default: 0.0,
+                            format: NumberFormat::default(),
+                        },
+                        ParameterKind::Text => BlockWidget::TextEdit {
+                            key,
+                            default: Cow::Borrowed(""),
+                        },
+                        ParameterKind::Bool => BlockWidget::Slot {
+                            key,
+                            accepts: ValueType::Bool,
+                        },
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Like the call widgets, with the parameters as labels.
+    fn definition_widgets(&self) -> Vec<BlockWidget> {
+        let elements = self.elements.iter().map(|element| match element {
+            PrototypeElement::Label(label) => label.trim().to_string(),
+            PrototypeElement::Parameter {
+                name,
+                kind: ParameterKind::Bool,
+            } => format!("<{name}>"),
+            PrototypeElement::Parameter { name, .. } => format!("({name})"),
+        });
+
+        std::iter::once("define".to_string())
+            .chain(elements)
+            .map(|text| BlockWidget::Label { text: text.into() })
+            .collect()
+    }
+}
+
+fn push_label(elements: &mut Vec<PrototypeElement>, label: &mut String) {
+    if !label.trim().is_empty() {
+        elements.push(PrototypeElement::Label(label.trim().to_string()));
+    }
+
+    label.clear();
+}
+
+impl fmt::Display for Prototype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, element) in self.elements.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+
+            match element {
+                PrototypeElement::Label(label) => f.write_str(&label.replace('%', "%%"))?,
+                PrototypeElement::Parameter { name, kind } => {
+                    write!(f, "%{}[{name}]", kind.shorthand())?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `define` hat, the script below it is run by the call blocks.
+#[derive(Default)]
+pub struct ProcedureDefinition {
+    pub prototype: Prototype,
+}
+
+impl Block for ProcedureDefinition {
+    fn opcode(&self) -> &str {
+        DEFINITION
+    }
+
+    fn describe(&mut self) -> BlockDescription {
+        BlockDescription {
+            shape: BlockShape::Hat,
+            category: BlockCategory::MyBlocks,
+            color: None,
+            content: vec![self.prototype.definition_widgets()],
+            output: ValueType::Any,
+        }
+    }
+
+    // run by the editor
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        Ok(())
+    }
+
+    fn mutation(&self) -> Option<String> {
+        Some(self.prototype.to_string())
+    }
+
+    fn set_mutation(&mut self, mutation: &str) {
+        self.prototype = Prototype::parse(mutation).unwrap_or_default();
+    }
+}
+
+#[derive(Default)]
+pub struct ProcedureCall {
+    pub prototype: Prototype,
+}
+
+impl Block for ProcedureCall {
+    fn opcode(&self) -> &str {
+        CALL
+    }
+
+    fn describe(&mut self) -> BlockDescription {
+        BlockDescription {
+            shape: BlockShape::Stack,
+            category: BlockCategory::MyBlocks,
+            color: None,
+            content: vec![self.prototype.call_widgets()],
+            output: ValueType::Any,
+        }
+    }
+
+    // run by the editor
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        Ok(())
+    }
+
+    fn mutation(&self) -> Option<String> {
+        Some(self.prototype.to_string())
+    }
+
+    fn set_mutation(&mut self, mutation: &str) {
+        self.prototype = Prototype::parse(mutation).unwrap_or_default();
+    }
+}
+
+/// Reports an argument of the procedure call being run, `boolean` decides the shape.
+pub struct Parameter {
+    pub name: String,
+    pub boolean: bool,
+}
+
+impl Parameter {
+    pub fn new(name: impl Into<String>, kind: ParameterKind) -> Self {
+        Parameter {
+            name: name.into(),
+            boolean: kind == ParameterKind::Bool,
+        }
+    }
+}
+
+impl Block for Parameter {
+    fn opcode(&self) -> &str {
+        if self.boolean {
+            BOOLEAN_PARAMETER
+        } else {
+            PARAMETER
+        }
+    }
+
+    fn describe(&mut self) -> BlockDescription {
+        BlockDescription {
+            shape: if self.boolean {
+                BlockShape::Boolean
+            } else {
+                BlockShape::Reporter
+            },
+            category: BlockCategory::MyBlocks,
+            color: None,
+            content: vec![vec![BlockWidget::Label {
+                text: self.name.clone().into(),
+            }]],
+            output: ValueType::Any,
+        }
+    }
+
+    // reported by the editor
+    fn run(&mut self, _fields: &BlockFields) -> Result<(), RunError> {
+        Ok(())
+    }
+
+    fn mutation(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn set_mutation(&mut self, mutation: &str) {
+        self.name = mutation.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prototypes() {
+        let prototype = Prototype::parse("jump %n[height]  times%b[fast] 100%%").unwrap();
+        let parameter = |name: &str, kind| PrototypeElement::Parameter {
+            name: name.to_string(),
+            kind,
+        };
+        assert_eq!(
+            prototype.elements,
+            [
+                PrototypeElement::Label("jump".to_string()),
+                parameter("height", ParameterKind::Number),
+                PrototypeElement::Label("times".to_string()),
+                parameter("fast", ParameterKind::Bool),
+                PrototypeElement::Label("100%".to_string()),
+            ]
+        );
+        let text = prototype.to_string();
+        assert_eq!(text, "jump %n[height] times %b[fast] 100%%");
+        assert_eq!(Prototype::parse(&text), Ok(prototype));
+
+        let error = |text: &str| Prototype::parse(text).unwrap_err();
+        assert_eq!(error(" "), ProcedureError::Empty);
+        assert_eq!(
+            error("%x[a]"),
+            ProcedureError::InvalidSyntax("%x[a]".to_string())
+        );
+        assert_eq!(
+            error("%s[a"),
+            ProcedureError::InvalidSyntax("%s[a".to_string())
+        );
+        assert_eq!(
+            error("%n[ ]"),
+            ProcedureError::InvalidParameter(" ".to_string())
+        );
+        assert_eq!(
+            error("%n[a] %s[a]"),
+            ProcedureError::DuplicateParameter("a".to_string())
+        );
+    }
+}
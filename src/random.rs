@@ -0,0 +1,88 @@
+//! The random numbers of a [`BlockEditor`], which only depend on the seed saved with the
+//! workspace.
+
+use crate::block::BlockEditor;
+use std::cell::RefCell;
+
+/// SplitMix64, which only uses integer arithmetic and is the same on every platform.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+    /// The seed the numbers continue from, [`Random::new`] with it generates the same numbers.
+    pub fn seed(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Random::new(0)
+    }
+}
+
+impl BlockEditor {
+    /// The seed the random numbers continue from, it's saved with the workspace.
+    pub fn seed(&self) -> u64 {
+        self.random.borrow().seed()
+    }
+
+    /// Starts the random numbers over from `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = RefCell::new(Random::new(seed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitmix64() {
+        let mut random = Random::new(0);
+        assert_eq!(random.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(random.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+        assert_eq!(random.next_u64(), 0x06C4_5D18_8009_454F);
+    }
+
+    #[test]
+    fn continues_from_the_seed() {
+        let mut random = Random::new(42);
+        random.next_u64();
+        let mut copy = Random::new(random.seed());
+        let numbers: Vec<f64> = (0..100).map(|_| random.next_f64()).collect();
+        assert_eq!(
+            numbers,
+            (0..100).map(|_| copy.next_f64()).collect::<Vec<_>>()
+        );
+        assert!(numbers.iter().all(|number| (0.0..1.0).contains(number)));
+    }
+
+    #[test]
+    fn editor_seed() {
+        let mut editor = BlockEditor::default();
+        editor.set_seed(7);
+        let first = editor.random.borrow_mut().next_u64();
+        editor.set_seed(7);
+        assert_eq!(editor.random.borrow_mut().next_u64(), first);
+    }
+}
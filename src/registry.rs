@@ -0,0 +1,85 @@
+use crate::{
+    block::{Block, BlockCategory},
+    event::{Broadcast, BroadcastAndWait, WhenGameStarts, WhenIReceive, WhenKeyPressed},
+    procedure::{Parameter, ParameterKind, ProcedureCall, ProcedureDefinition},
+    variable::{
+        AddToList, ChangeVariable, DeleteAllOfList, DeleteOfList, InsertAtList, ItemNumOfList,
+        ItemOfList, LengthOfList, ListContainsItem, ListContents, ReplaceItemOfList, SetVariable,
+        VariableReporter,
+    },
+};
+use std::collections::HashMap;
+
+type Constructor = Box<dyn Fn() -> Box<dyn Block>>;
+
+/// Knows how to create blocks from their opcode, needed for loading saved workspaces.
+///
+/// The built-in blocks, e.g. the variable blocks, are always registered.
+pub struct BlockRegistry {
+    constructors: HashMap<String, Constructor>,
+    categories: HashMap<String, BlockCategory>,
+    /// keys of the dynamic dropdowns of defined blocks, with the opcode of the block
+    pub(crate) menus: HashMap<String, String>,
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        let mut registry = BlockRegistry {
+            constructors: HashMap::new(),
+            categories: HashMap::new(),
+            menus: HashMap::new(),
+        };
+        registry.register(WhenGameStarts::default);
+        registry.register(|| WhenKeyPressed);
+        registry.register(WhenIReceive::default);
+        registry.register(Broadcast::default);
+        registry.register(BroadcastAndWait::default);
+        registry.register(SetVariable::default);
+        registry.register(ChangeVariable::default);
+        registry.register(VariableReporter::default);
+        registry.register(AddToList::default);
+        registry.register(DeleteOfList::default);
+        registry.register(DeleteAllOfList::default);
+        registry.register(InsertAtList::default);
+        registry.register(ReplaceItemOfList::default);
+        registry.register(ItemOfList::default);
+        registry.register(ItemNumOfList::default);
+        registry.register(LengthOfList::default);
+        registry.register(ListContainsItem::default);
+        registry.register(ListContents::default);
+        registry.register(ProcedureDefinition::default);
+        registry.register(ProcedureCall::default);
+        registry.register(|| Parameter::new("", ParameterKind::Text));
+        registry.register(|| Parameter::new("", ParameterKind::Bool));
+        registry
+    }
+}
+
+impl BlockRegistry {
+    pub fn register<B: Block + 'static>(&mut self, constructor: impl Fn() -> B + 'static) {
+        let mut block = constructor();
+        let opcode = block.opcode().to_string();
+        self.categories
+            .insert(opcode.clone(), block.describe().category);
+        self.constructors
+            .insert(opcode, Box::new(move || Box::new(constructor())));
+    }
+
+    pub fn opcodes(&self) -> impl Iterator<Item = &str> {
+        self.constructors.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, opcode: &str) -> bool {
+        self.constructors.contains_key(opcode)
+    }
+
+    pub fn category(&self, opcode: &str) -> Option<BlockCategory> {
+        self.categories.get(opcode).copied()
+    }
+
+    pub fn create(&self, opcode: &str) -> Option<Box<dyn Block>> {
+        self.constructors
+            .get(opcode)
+            .map(|constructor| constructor())
+    }
+}
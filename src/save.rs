@@ -0,0 +1,346 @@
+use crate::{
+    value::Value,
+    variable::{VariableError, VariableScope},
+};
+use eframe::epaint::Color32;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error::Error, fmt};
+
+/// Serializable snapshot of a [`BlockEditor`](crate::block::BlockEditor).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SavedWorkspace {
+    #[serde(default)]
+    pub offset: [f32; 2],
+    pub blocks: Vec<SavedBlock>,
+    #[serde(default)]
+    pub variables: Vec<SavedVariable>,
+    /// the values are [`SavedValue::List`]s
+    #[serde(default)]
+    pub lists: Vec<SavedVariable>,
+    /// see [`BlockEditor::seed`](crate::block::BlockEditor::seed)
+    #[serde(default)]
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedVariable {
+    pub name: String,
+    pub scope: VariableScope,
+    pub value: SavedValue,
+    /// position of the monitor if it's shown
+    #[serde(default)]
+    pub monitor: Option<[f32; 2]>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedBlock {
+    pub opcode: String,
+    /// see [`Block::mutation`](crate::block::Block::mutation)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mutation: Option<String>,
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub fields: BTreeMap<String, SavedValue>,
+    /// one per part, indices into [`SavedWorkspace::blocks`]
+    #[serde(default)]
+    pub next: Vec<Option<usize>>,
+    /// reporters in input slots by key, indices into [`SavedWorkspace::blocks`]
+    #[serde(default)]
+    pub inputs: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SavedValue {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    /// RGBA, not premultiplied
+    Color([u8; 4]),
+    List(Vec<SavedValue>),
+}
+
+impl SavedValue {
+    /// `None` for custom values
+    pub fn from_value(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::Number(number) => SavedValue::Float(*number),
+            Value::Text(text) => SavedValue::Text(text.clone()),
+            Value::Bool(bool) => SavedValue::Bool(*bool),
+            Value::Color(color) => SavedValue::Color(color.to_srgba_unmultiplied()),
+            Value::List(items) => {
+                SavedValue::List(items.iter().filter_map(SavedValue::from_value).collect())
+            }
+            Value::Custom(_) => return None,
+        })
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            SavedValue::Text(text) => Value::Text(text.clone()),
+            SavedValue::Integer(number) => Value::Number(*number as f64),
+            SavedValue::Float(number) => Value::Number(*number),
+            SavedValue::Bool(bool) => Value::Bool(*bool),
+            SavedValue::Color([r, g, b, a]) => {
+                Value::Color(Color32::from_rgba_unmultiplied(*r, *g, *b, *a))
+            }
+            SavedValue::List(items) => {
+                Value::List(items.iter().map(SavedValue::to_value).collect())
+            }
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            SavedValue::Integer(value) => Some(*value as f64),
+            SavedValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// the opcode isn't known to the [`BlockRegistry`](crate::registry::BlockRegistry)
+    UnknownBlock(String),
+    /// a block is connected to a block which doesn't exist
+    InvalidConnection { block: usize, next: usize },
+    /// e.g. a stack block in an input or a block below a cap block
+    IncompatibleConnection { block: usize, next: usize },
+    /// a block is attached to more than one block
+    MultipleParents { block: usize },
+    /// a block is attached to itself, directly or through other blocks
+    Cycle { block: usize },
+    /// e.g. two variables with the same name
+    Variable(VariableError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::UnknownBlock(opcode) => write!(f, "unknown block \"{opcode}\""),
+            LoadError::InvalidConnection { block, next } => {
+                write!(f, "block {block} is connected to nonexistent block {next}")
+            }
+            LoadError::IncompatibleConnection { block, next } => {
+                write!(f, "block {next} doesn't fit into block {block}")
+            }
+            LoadError::MultipleParents { block } => {
+                write!(f, "block {block} is attached to more than one block")
+            }
+            LoadError::Cycle { block } => write!(f, "block {block} is attached to itself"),
+            LoadError::Variable(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        block::BlockEditor,
+        event::WHEN_GAME_STARTS,
+        registry::BlockRegistry,
+        standard::{self, IF, REPEAT, STOP},
+        variable::{SET_VARIABLE, VARIABLE},
+    };
+
+    pub(crate) fn text(text: &str) -> SavedValue {
+        SavedValue::Text(text.to_string())
+    }
+
+    pub(crate) fn number(number: f64) -> SavedValue {
+        SavedValue::Float(number)
+    }
+
+    /// Builds a [`SavedWorkspace`] with the standard blocks, block by block.
+    #[derive(Default)]
+    pub(crate) struct Workspace(pub SavedWorkspace);
+
+    impl Workspace {
+        pub fn block(&mut self, opcode: &str, fields: &[(&str, SavedValue)]) -> usize {
+            self.add(opcode, fields, 1)
+        }
+
+        /// A C block with the stacks of its branches.
+        pub fn c_block(
+            &mut self,
+            opcode: &str,
+            fields: &[(&str, SavedValue)],
+            branches: &[&[usize]],
+        ) -> usize {
+            let block = self.add(opcode, fields, branches.len() + 1);
+            for (part, stack) in branches.iter().enumerate() {
+                self.0.blocks[block].next[part] = stack.first().copied();
+                self.stack(stack);
+            }
+
+            block
+        }
+
+        fn add(&mut self, opcode: &str, fields: &[(&str, SavedValue)], parts: usize) -> usize {
+            self.0.blocks.push(SavedBlock {
+                opcode: opcode.to_string(),
+                mutation: None,
+                position: [0.0; 2],
+                fields: fields
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.clone()))
+                    .collect(),
+                next: vec![None; parts],
+                inputs: BTreeMap::new(),
+            });
+            self.0.blocks.len() - 1
+        }
+
+        /// Connects the blocks below each other.
+        pub fn stack(&mut self, blocks: &[usize]) {
+            for pair in blocks.windows(2) {
+                *self.0.blocks[pair[0]].next.last_mut().unwrap() = Some(pair[1]);
+            }
+        }
+
+        pub fn input(&mut self, block: usize, key: &str, reporter: usize) {
+            self.0.blocks[block]
+                .inputs
+                .insert(key.to_string(), reporter);
+        }
+
+        pub fn mutation(&mut self, block: usize, mutation: &str) {
+            self.0.blocks[block].mutation = Some(mutation.to_string());
+        }
+
+        pub fn variable(&mut self, name: &str, value: SavedValue) {
+            self.0.variables.push(SavedVariable {
+                name: name.to_string(),
+                scope: VariableScope::Global,
+                value,
+                monitor: None,
+            });
+        }
+
+        pub fn list(&mut self, name: &str, items: Vec<SavedValue>) {
+            self.0.lists.push(SavedVariable {
+                name: name.to_string(),
+                scope: VariableScope::Global,
+                value: SavedValue::List(items),
+                monitor: None,
+            });
+        }
+
+        pub fn load(&self) -> BlockEditor {
+            self.try_load().unwrap()
+        }
+
+        pub fn try_load(&self) -> Result<BlockEditor, LoadError> {
+            BlockEditor::load(&self.0, &self.registry())
+        }
+
+        pub fn registry(&self) -> BlockRegistry {
+            let mut registry = BlockRegistry::default();
+            standard::register(&mut registry);
+            registry
+        }
+    }
+
+    fn json(editor: &BlockEditor) -> String {
+        serde_json::to_string(&editor.save()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(1.5));
+        workspace.list("l", vec![text("a"), number(2.0)]);
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("x"))]);
+        let join = workspace.block("operator_join", &[("a", text("a")), ("b", text("b"))]);
+        let variable = workspace.block(VARIABLE, &[("variable", text("x"))]);
+        workspace.input(set, "value", join);
+        workspace.input(join, "b", variable);
+        let repeat = workspace.c_block(REPEAT, &[("times", number(3.0))], &[&[set]]);
+        let stop = workspace.block(STOP, &[("option", text("all"))]);
+        workspace.stack(&[hat, repeat, stop]);
+        workspace.0.seed = 7;
+
+        let editor = workspace.load();
+        let saved = json(&editor);
+        let loaded = BlockEditor::load(
+            &serde_json::from_str(&saved).unwrap(),
+            &Workspace::default().registry(),
+        )
+        .unwrap();
+        assert_eq!(json(&loaded), saved);
+        assert_eq!(loaded.variables.get("x").map(Value::to_number), Some(1.5));
+        assert_eq!(loaded.seed(), 7);
+    }
+
+    fn rejected(workspace: &Workspace) -> LoadError {
+        workspace.try_load().err().expect("loaded")
+    }
+
+    #[test]
+    fn cycles() {
+        let mut workspace = Workspace::default();
+        let set = workspace.block(SET_VARIABLE, &[]);
+        workspace.stack(&[set, set]);
+        assert!(matches!(rejected(&workspace), LoadError::Cycle { .. }));
+
+        let mut workspace = Workspace::default();
+        let a = workspace.block(SET_VARIABLE, &[]);
+        let b = workspace.block(SET_VARIABLE, &[]);
+        let repeat = workspace.c_block(REPEAT, &[], &[&[a, b]]);
+        workspace.stack(&[b, repeat]);
+        assert!(matches!(rejected(&workspace), LoadError::Cycle { .. }));
+    }
+
+    #[test]
+    fn multiple_parents() {
+        let mut workspace = Workspace::default();
+        let a = workspace.block(SET_VARIABLE, &[]);
+        let b = workspace.block(SET_VARIABLE, &[]);
+        let variable = workspace.block(VARIABLE, &[]);
+        workspace.input(a, "value", variable);
+        workspace.input(b, "value", variable);
+        assert!(matches!(
+            rejected(&workspace),
+            LoadError::MultipleParents { block } if block == variable
+        ));
+    }
+
+    #[test]
+    fn incompatible_connections() {
+        let connections: [(&str, &str, Option<&str>); 5] = [
+            // a stack block in an input
+            (SET_VARIABLE, SET_VARIABLE, Some("value")),
+            // a reporter below a block
+            (SET_VARIABLE, VARIABLE, None),
+            // a block below a cap block
+            (STOP, SET_VARIABLE, None),
+            // a hat below a block
+            (SET_VARIABLE, WHEN_GAME_STARTS, None),
+            // a reporter in a bool input
+            (IF, "operator_join", Some("condition")),
+        ];
+        for (parent, child, input) in connections {
+            let mut workspace = Workspace::default();
+            let parent = workspace.block(parent, &[]);
+            let child = workspace.block(child, &[]);
+            match input {
+                Some(key) => workspace.input(parent, key, child),
+                None => workspace.stack(&[parent, child]),
+            }
+            assert!(
+                matches!(
+                    rejected(&workspace),
+                    LoadError::IncompatibleConnection { block, next }
+                        if block == parent && next == child
+                ),
+                "{:?}",
+                workspace.0.blocks
+            );
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Turns of the threads of the interpreter and of the [`Vm`](crate::vm::Vm).
+
+use crate::{
+    interpreter::ThreadId,
+    limits::{Budget, Limits},
+};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+/// how long a tick keeps running threads which reached the end of a loop
+pub(crate) const TICK_DURATION: Duration = Duration::from_millis(10);
+
+/// longest wait, longer ones would overflow [`Instant`]
+const MAX_SLEEP: Duration = Duration::from_secs(60 * 60 * 24 * 365);
+
+/// When a thread gets its next turn.
+#[derive(Default)]
+pub(crate) struct Schedule {
+    /// threads which have to finish before this one continues
+    pub waiting: Vec<ThreadId>,
+    /// set by wait blocks
+    pub sleep_until: Option<Instant>,
+    /// set by stop blocks stopping the other threads
+    pub stop_others: bool,
+    /// blocks run or evaluated, see [`Limits`]
+    pub budget: Budget,
+}
+
+impl Schedule {
+    pub fn is_waiting(&self) -> bool {
+        !self.waiting.is_empty() || self.sleep_until.is_some()
+    }
+
+    /// Waits for `seconds`, at most [`MAX_SLEEP`] and not at all if it isn't positive.
+    pub fn sleep(&mut self, seconds: f64) {
+        let duration = Duration::try_from_secs_f64(seconds.max(0.0)).unwrap_or(MAX_SLEEP);
+        self.sleep_until = Some(Instant::now() + duration.min(MAX_SLEEP));
+    }
+}
+
+pub(crate) trait Scheduled {
+    /// a restarted script replaces the thread running it
+    type Script: PartialEq;
+
+    fn id(&self) -> ThreadId;
+    fn script(&self) -> Self::Script;
+    fn is_finished(&self) -> bool;
+    fn stop(&mut self);
+    fn schedule(&mut self) -> &mut Schedule;
+}
+
+pub(crate) trait Scheduler {
+    type Thread: Scheduled;
+
+    /// The threads, only the ones started during the current round while it's running.
+    fn threads(&mut self) -> &mut Vec<Self::Thread>;
+    fn limits(&self) -> &Limits;
+    fn is_paused(&self) -> bool;
+    /// Runs the thread until it finishes, has to wait, reaches the end of a loop or runs out of
+    /// its budget for the tick.
+    fn run_turn(&mut self, thread: &mut Self::Thread);
+}
+
+/// Gives every thread turns until all of them are finished or waiting, for at most
+/// [`TICK_DURATION`]. Threads started in the meantime get their first turn in the next round.
+pub(crate) fn tick(scheduler: &mut impl Scheduler) {
+    for thread in scheduler.threads().iter_mut() {
+        thread.schedule().budget.next_tick();
+    }
+
+    let start = Instant::now();
+    while !scheduler.is_paused() {
+        let mut threads = std::mem::take(scheduler.threads());
+        let running: HashSet<ThreadId> = threads.iter().map(Scheduled::id).collect();
+        let limits = *scheduler.limits();
+        let mut progress = false;
+        for i in 0..threads.len() {
+            let thread = &mut threads[i];
+            let schedule = thread.schedule();
+            schedule.waiting.retain(|id| running.contains(id));
+            if schedule
+                .sleep_until
+                .is_some_and(|until| until <= Instant::now())
+            {
+                schedule.sleep_until = None;
+            }
+
+            if schedule.is_waiting()
+                || schedule.budget.is_spent(&limits)
+                || thread.is_finished()
+                || scheduler.is_paused()
+            {
+                continue;
+            }
+
+            progress = true;
+            scheduler.run_turn(thread);
+
+            if std::mem::take(&mut thread.schedule().stop_others) {
+                for (j, other) in threads.iter_mut().enumerate() {
+                    if j != i {
+                        other.stop();
+                    }
+                }
+            }
+        }
+
+        // restarted scripts replace their old threads
+        let started = std::mem::take(scheduler.threads());
+        threads.retain(|thread| {
+            !thread.is_finished()
+                && !started
+                    .iter()
+                    .any(|other| other.script() == thread.script())
+        });
+        threads.extend(started);
+        *scheduler.threads() = threads;
+
+        if !progress || start.elapsed() >= TICK_DURATION {
+            break;
+        }
+    }
+}
@@ -0,0 +1,662 @@
+//! Opt-in standard library of control, operator and text blocks, see [`register`].
+//!
+//! The operators report their values themselves, the control blocks are run by the editor
+//! since they decide which blocks run next.
+
+use crate::{
+    block::{Block, BlockFields, RunError},
+    field::DerivedBlock,
+    registry::BlockRegistry,
+    value::Value,
+};
+
+pub const IF: &str = "control_if";
+pub const IF_ELSE: &str = "control_if_else";
+pub const REPEAT: &str = "control_repeat";
+pub const REPEAT_UNTIL: &str = "control_repeat_until";
+pub const FOREVER: &str = "control_forever";
+pub const WAIT: &str = "control_wait";
+pub const STOP: &str = "control_stop";
+pub const NOT: &str = "operator_not";
+
+/// Options of the stop block.
+pub const STOP_ALL: &str = "all";
+pub const STOP_THIS_SCRIPT: &str = "this script";
+pub const STOP_OTHER_SCRIPTS: &str = "other scripts";
+
+/// The operators with two operands, which the [`vm`](crate::vm) runs without the blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    LessThan,
+    Equals,
+    GreaterThan,
+    And,
+    Or,
+    Join,
+}
+
+impl Operator {
+    pub(crate) fn from_opcode(opcode: &str) -> Option<Self> {
+        Some(match opcode {
+            "operator_add" => Operator::Add,
+            "operator_subtract" => Operator::Subtract,
+            "operator_multiply" => Operator::Multiply,
+            "operator_divide" => Operator::Divide,
+            "operator_mod" => Operator::Modulo,
+            "operator_lt" => Operator::LessThan,
+            "operator_equals" => Operator::Equals,
+            "operator_gt" => Operator::GreaterThan,
+            "operator_and" => Operator::And,
+            "operator_or" => Operator::Or,
+            "operator_join" => Operator::Join,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn apply(self, a: &Value, b: &Value) -> Value {
+        match self {
+            Operator::Add => (a.to_number() + b.to_number()).into(),
+            Operator::Subtract => (a.to_number() - b.to_number()).into(),
+            Operator::Multiply => (a.to_number() * b.to_number()).into(),
+            Operator::Divide => (a.to_number() / b.to_number()).into(),
+            Operator::Modulo => {
+                // the result has the sign of the divisor, like in Scratch
+                let (a, b) = (a.to_number(), b.to_number());
+                let mut result = a % b;
+                if result / b < 0.0 {
+                    result += b;
+                }
+
+                result.into()
+            }
+            Operator::LessThan => a.compare(b).is_lt().into(),
+            Operator::Equals => a.equals(b).into(),
+            Operator::GreaterThan => a.compare(b).is_gt().into(),
+            Operator::And => (a.to_bool() && b.to_bool()).into(),
+            Operator::Or => (a.to_bool() || b.to_bool()).into(),
+            Operator::Join => format!("{}{}", a.to_text(), b.to_text()).into(),
+        }
+    }
+}
+
+/// Registers every block of the standard library in `registry`.
+pub fn register(registry: &mut BlockRegistry) {
+    registry.register(If::default);
+    registry.register(IfElse::default);
+    registry.register(Repeat::default);
+    registry.register(RepeatUntil::default);
+    registry.register(Forever::default);
+    registry.register(Wait::default);
+    registry.register(Stop::default);
+    registry.register(Add::default);
+    registry.register(Subtract::default);
+    registry.register(Multiply::default);
+    registry.register(Divide::default);
+    registry.register(Modulo::default);
+    registry.register(LessThan::default);
+    registry.register(Equals::default);
+    registry.register(GreaterThan::default);
+    registry.register(And::default);
+    registry.register(Or::default);
+    registry.register(Not::default);
+    registry.register(Random::default);
+    registry.register(Round::default);
+    registry.register(MathOp::default);
+    registry.register(Join::default);
+    registry.register(LetterOf::default);
+    registry.register(Length::default);
+    registry.register(Contains::default);
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "control_if",
+    shape = "c",
+    category = "standard",
+    label = "if {condition} then",
+    label = "",
+    rust = "if %{condition:bool} {\n    %{0}\n}"
+)]
+pub struct If {
+    #[block(slot)]
+    pub condition: bool,
+}
+
+// run by the editor, like the other control blocks
+impl DerivedBlock for If {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "control_if_else",
+    shape = "c",
+    branches = 2,
+    category = "standard",
+    label = "if {condition} then",
+    label = "else",
+    label = "",
+    rust = "if %{condition:bool} {\n    %{0}\n} else {\n    %{1}\n}"
+)]
+pub struct IfElse {
+    #[block(slot)]
+    pub condition: bool,
+}
+
+impl DerivedBlock for IfElse {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "control_repeat",
+    shape = "c",
+    category = "standard",
+    label = "repeat {times}",
+    label = "",
+    rust = "for _ in 0..%{times:number}.round() as i64 {\n    %{0}\n}"
+)]
+pub struct Repeat {
+    #[block(default = 10.0, min = 0)]
+    pub times: f64,
+}
+
+impl DerivedBlock for Repeat {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "control_repeat_until",
+    shape = "c",
+    category = "standard",
+    label = "repeat until {condition}",
+    label = "",
+    rust = "loop {\n    if %{condition:bool} {\n        break;\n    }\n    %{0}\n}"
+)]
+pub struct RepeatUntil {
+    #[block(slot)]
+    pub condition: bool,
+}
+
+impl DerivedBlock for RepeatUntil {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "control_forever",
+    shape = "c",
+    category = "standard",
+    label = "forever",
+    label = "",
+    rust = "loop {\n    %{0}\n}"
+)]
+pub struct Forever {}
+
+impl DerivedBlock for Forever {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "control_wait",
+    category = "standard",
+    label = "wait {duration} seconds",
+    rust = "host.wait(%{duration:number}.max(0.0));"
+)]
+pub struct Wait {
+    #[block(default = 1.0, min = 0)]
+    pub duration: f64,
+}
+
+impl DerivedBlock for Wait {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "control_stop",
+    shape = "cap",
+    category = "standard",
+    label = "stop {option}",
+    rust = "host.stop(&%{option:text})?;\nreturn Ok(());"
+)]
+pub struct Stop {
+    #[block(options("all", "this script", "other scripts"))]
+    pub option: String,
+}
+
+impl DerivedBlock for Stop {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_add",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "{a} + {b}",
+    rust = "Value::from(%{a:number} + %{b:number})"
+)]
+pub struct Add {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl DerivedBlock for Add {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Add.apply(&self.a.into(), &self.b.into()))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_subtract",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "{a} - {b}",
+    rust = "Value::from(%{a:number} - %{b:number})"
+)]
+pub struct Subtract {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl DerivedBlock for Subtract {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Subtract.apply(&self.a.into(), &self.b.into()))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_multiply",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "{a} * {b}",
+    rust = "Value::from(%{a:number} * %{b:number})"
+)]
+pub struct Multiply {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl DerivedBlock for Multiply {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Multiply.apply(&self.a.into(), &self.b.into()))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_divide",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "{a} / {b}",
+    rust = "Value::from(%{a:number} / %{b:number})"
+)]
+pub struct Divide {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl DerivedBlock for Divide {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Divide.apply(&self.a.into(), &self.b.into()))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_mod",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "{a} mod {b}",
+    rust = "{ let (a, b) = (%{a:number}, %{b:number}); let result = a %% b; \
+            Value::from(if result / b < 0.0 { result + b } else { result }) }"
+)]
+pub struct Modulo {
+    pub a: f64,
+    pub b: f64,
+}
+
+impl DerivedBlock for Modulo {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Modulo.apply(&self.a.into(), &self.b.into()))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_lt",
+    shape = "boolean",
+    category = "standard",
+    label = "{a} < {b}",
+    rust = "Value::from(%{a}.compare(&%{b}).is_lt())"
+)]
+pub struct LessThan {
+    pub a: Value,
+    #[block(default = "50")]
+    pub b: Value,
+}
+
+impl DerivedBlock for LessThan {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::LessThan.apply(&self.a, &self.b))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_equals",
+    shape = "boolean",
+    category = "standard",
+    label = "{a} = {b}",
+    rust = "Value::from(%{a}.equals(&%{b}))"
+)]
+pub struct Equals {
+    pub a: Value,
+    #[block(default = "50")]
+    pub b: Value,
+}
+
+impl DerivedBlock for Equals {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Equals.apply(&self.a, &self.b))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_gt",
+    shape = "boolean",
+    category = "standard",
+    label = "{a} > {b}",
+    rust = "Value::from(%{a}.compare(&%{b}).is_gt())"
+)]
+pub struct GreaterThan {
+    pub a: Value,
+    #[block(default = "50")]
+    pub b: Value,
+}
+
+impl DerivedBlock for GreaterThan {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::GreaterThan.apply(&self.a, &self.b))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_and",
+    shape = "boolean",
+    category = "standard",
+    label = "{a} and {b}",
+    rust = "Value::from(%{a:bool} & %{b:bool})"
+)]
+pub struct And {
+    #[block(slot)]
+    pub a: bool,
+    #[block(slot)]
+    pub b: bool,
+}
+
+impl DerivedBlock for And {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::And.apply(&self.a.into(), &self.b.into()))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_or",
+    shape = "boolean",
+    category = "standard",
+    label = "{a} or {b}",
+    rust = "Value::from(%{a:bool} | %{b:bool})"
+)]
+pub struct Or {
+    #[block(slot)]
+    pub a: bool,
+    #[block(slot)]
+    pub b: bool,
+}
+
+impl DerivedBlock for Or {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Or.apply(&self.a.into(), &self.b.into()))
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_not",
+    shape = "boolean",
+    category = "standard",
+    label = "not {value}",
+    rust = "Value::from(!%{value:bool})"
+)]
+pub struct Not {
+    #[block(slot)]
+    pub value: bool,
+}
+
+impl DerivedBlock for Not {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok((!self.value).into())
+    }
+}
+
+/// Picks whole numbers unless one of the bounds has a decimal point.
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_random",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "pick random {from} to {to}",
+    rust = "Value::from(standard::pick_random(&%{from}, &%{to}, host.random()))"
+)]
+pub struct Random {
+    #[block(default = "1")]
+    pub from: Value,
+    #[block(default = "10")]
+    pub to: Value,
+}
+
+impl DerivedBlock for Random {
+    fn report(&mut self, fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(pick_random(&self.from, &self.to, fields.random()).into())
+    }
+}
+
+/// Number the random block picks with `random` in `0.0..1.0`, decimals if either bound has a
+/// decimal point and integers otherwise.
+pub fn pick_random(from: &Value, to: &Value, random: f64) -> f64 {
+    let decimal = |value: &Value| match value {
+        Value::Number(number) => number.fract() != 0.0,
+        value => value.to_text().contains('.'),
+    };
+
+    let (from_number, to_number) = (from.to_number(), to.to_number());
+    let (low, high) = (from_number.min(to_number), from_number.max(to_number));
+    if decimal(from) || decimal(to) {
+        low + random * (high - low)
+    } else {
+        let (low, high) = (low.round(), high.round());
+        low + (random * (high - low + 1.0)).floor()
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_round",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "round {value}",
+    rust = "Value::from((%{value:number} + 0.5).floor())"
+)]
+pub struct Round {
+    pub value: f64,
+}
+
+impl DerivedBlock for Round {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        // halves are rounded up, also for negative numbers
+        Ok((self.value + 0.5).floor().into())
+    }
+}
+
+/// Angles are in degrees.
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_mathop",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "{operator} of {value}",
+    rust = "Value::from(standard::math_op(&%{operator:text}, %{value:number}))"
+)]
+pub struct MathOp {
+    #[block(options(
+        "abs", "floor", "ceiling", "sqrt", "sin", "cos", "tan", "asin", "acos", "atan", "ln",
+        "log", "e ^", "10 ^"
+    ))]
+    pub operator: String,
+    pub value: f64,
+}
+
+impl DerivedBlock for MathOp {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(math_op(&self.operator, self.value).into())
+    }
+}
+
+/// Result of the math block, `operator` is one of its options.
+pub fn math_op(operator: &str, value: f64) -> f64 {
+    match operator {
+        "abs" => value.abs(),
+        "floor" => value.floor(),
+        "ceiling" => value.ceil(),
+        "sqrt" => value.sqrt(),
+        // rounded so that e.g. sin of 180 is 0
+        "sin" => round_trig(value.to_radians().sin()),
+        "cos" => round_trig(value.to_radians().cos()),
+        "tan" => {
+            let angle = value.rem_euclid(360.0);
+            if angle == 90.0 {
+                f64::INFINITY
+            } else if angle == 270.0 {
+                f64::NEG_INFINITY
+            } else {
+                round_trig(value.to_radians().tan())
+            }
+        }
+        "asin" => value.asin().to_degrees(),
+        "acos" => value.acos().to_degrees(),
+        "atan" => value.atan().to_degrees(),
+        "ln" => value.ln(),
+        "log" => value.log10(),
+        "e ^" => value.exp(),
+        "10 ^" => 10f64.powf(value),
+        _ => 0.0,
+    }
+}
+
+fn round_trig(value: f64) -> f64 {
+    (value * 1e10).round() / 1e10
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_join",
+    shape = "reporter",
+    category = "standard",
+    output = "text",
+    label = "join {a} {b}",
+    rust = "Value::from(format!(\"{}{}\", %{a:text}, %{b:text}))"
+)]
+pub struct Join {
+    #[block(default = "apple ")]
+    pub a: Value,
+    #[block(default = "banana")]
+    pub b: Value,
+}
+
+impl DerivedBlock for Join {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok(Operator::Join.apply(&self.a, &self.b))
+    }
+}
+
+/// Empty if the index is out of range, the first letter is 1.
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_letter_of",
+    shape = "reporter",
+    category = "standard",
+    output = "text",
+    label = "letter {index} of {text}",
+    rust = "{ let (index, text) = (%{index:number}.floor(), %{text:text}); \
+            Value::from(if index >= 1.0 { \
+            text.chars().nth(index as usize - 1).map(String::from).unwrap_or_default() \
+            } else { String::new() }) }"
+)]
+pub struct LetterOf {
+    #[block(default = 1.0)]
+    pub index: f64,
+    #[block(default = "apple")]
+    pub text: Value,
+}
+
+impl DerivedBlock for LetterOf {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        let index = self.index.floor();
+        let letter = (index >= 1.0)
+            .then(|| self.text.to_text().chars().nth(index as usize - 1))
+            .flatten();
+        Ok(letter.map(String::from).unwrap_or_default().into())
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_length",
+    shape = "reporter",
+    category = "standard",
+    output = "number",
+    label = "length of {text}",
+    rust = "Value::from(%{text:text}.chars().count() as f64)"
+)]
+pub struct Length {
+    #[block(default = "apple")]
+    pub text: Value,
+}
+
+impl DerivedBlock for Length {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        Ok((self.text.to_text().chars().count() as f64).into())
+    }
+}
+
+/// Ignores case, like in Scratch.
+#[derive(Block, Default)]
+#[block(
+    opcode = "operator_contains",
+    shape = "boolean",
+    category = "standard",
+    label = "{text} contains {item}?",
+    rust = "{ let (text, item) = (%{text:text}, %{item:text}); \
+            Value::from(text.to_lowercase().contains(&item.to_lowercase())) }"
+)]
+pub struct Contains {
+    #[block(default = "apple")]
+    pub text: Value,
+    #[block(default = "a")]
+    pub item: Value,
+}
+
+impl DerivedBlock for Contains {
+    fn report(&mut self, _fields: &BlockFields) -> Result<Value, RunError> {
+        let text = self.text.to_text().to_lowercase();
+        Ok(text.contains(&self.item.to_text().to_lowercase()).into())
+    }
+}
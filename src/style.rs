@@ -0,0 +1,96 @@
+use crate::{
+    block::{BlockCategory, BlockDescription},
+    geometry::BlockGeometry,
+};
+use eframe::{
+    egui::Visuals,
+    epaint::{ecolor::tint_color_towards, Color32, Stroke},
+};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct BlockEditorStyle {
+    /// fill of blocks without a category color
+    pub fill: Color32,
+    pub category_colors: HashMap<BlockCategory, Color32>,
+    /// the colors of blocks are moved towards it to keep the text readable
+    pub tint: Color32,
+    pub outline: Stroke,
+    /// outline of hovered and dragged blocks
+    pub outline_active: Stroke,
+    pub text_color: Color32,
+    /// highlights where a dragged block is going to snap to
+    pub selection: Stroke,
+    /// highlights connections refused by the [`ConnectionChecker`](crate::connection::ConnectionChecker)
+    pub rejected: Stroke,
+    /// fill of empty input slots
+    pub slot_fill: Color32,
+    /// outline of blocks being run
+    pub running: Stroke,
+    /// painted over blocks which have just been run, fading out
+    pub flash: Color32,
+    /// marker of blocks with a breakpoint
+    pub breakpoint: Color32,
+    /// badge of blocks which failed
+    pub error: Color32,
+    pub geometry: BlockGeometry,
+    /// horizontal space around the widgets of a part
+    pub padding: f32,
+}
+
+impl BlockEditorStyle {
+    pub fn from_visuals(visuals: &Visuals) -> Self {
+        let (fill, tint) = if visuals.dark_mode {
+            (Color32::from_rgb(0x22, 0x22, 0x22), Color32::BLACK)
+        } else {
+            (Color32::from_rgb(0xFF, 0xFF, 0xFF), Color32::WHITE)
+        };
+
+        // keep the text readable by moving the category colors towards the background
+        let category_colors = BlockCategory::ALL
+            .iter()
+            .filter_map(|&category| Some((category, category.color()?)))
+            .map(|(category, color)| (category, tint_color_towards(color, tint)))
+            .collect();
+
+        BlockEditorStyle {
+            fill,
+            category_colors,
+            tint,
+            outline: visuals.widgets.inactive.fg_stroke,
+            outline_active: visuals.widgets.active.fg_stroke,
+            text_color: visuals.strong_text_color(),
+            selection: visuals.selection.stroke,
+            rejected: Stroke::new(visuals.selection.stroke.width, visuals.error_fg_color),
+            slot_fill: visuals.extreme_bg_color,
+            // the glow of running scripts in Scratch
+            running: Stroke::new(3.0, Color32::from_rgb(0xFF, 0xF2, 0x00)),
+            flash: visuals.strong_text_color().gamma_multiply(0.4),
+            breakpoint: visuals.error_fg_color,
+            error: visuals.error_fg_color,
+            geometry: BlockGeometry::default(),
+            padding: 10.0,
+        }
+    }
+
+    pub fn category_fill(&self, category: BlockCategory) -> Color32 {
+        self.category_colors
+            .get(&category)
+            .copied()
+            .unwrap_or(self.fill)
+    }
+
+    /// The color of the block, or the fill of its category, tinted like the category colors.
+    pub fn block_fill(&self, description: &BlockDescription) -> Color32 {
+        match description.color {
+            Some(color) => tint_color_towards(color, self.tint),
+            None => self.category_fill(description.category),
+        }
+    }
+}
+
+impl Default for BlockEditorStyle {
+    fn default() -> Self {
+        BlockEditorStyle::from_visuals(&Visuals::default())
+    }
+}
@@ -0,0 +1,573 @@
+//! Builds the widgets of a block from template strings like
+//! `"move %{steps:number=10} steps toward %{target:dropdown}"`.
+//!
+//! Arguments are written as `%{key:type(parameters)=default}`, the parameters and the default
+//! are optional. Parameters are separated by commas, none of the parts may contain `}`.
+//!
+//! | type       | widget                          | parameters                          |
+//! |------------|---------------------------------|-------------------------------------|
+//! | `text`     | [`BlockWidget::TextEdit`]       |                                     |
+//! | `number`   | [`BlockWidget::NumberEdit`]     | `min=`, `max=`, `step=`, `prefix=`, `suffix=` |
+//! | `integer`  | integer-only `number`           | same as `number`                    |
+//! | `bool`     | [`BlockWidget::Checkbox`]       |                                     |
+//! | `color`    | [`BlockWidget::ColorPicker`]    |                                     |
+//! | `angle`    | [`BlockWidget::Angle`]          | `snap=`                             |
+//! | `slider`   | [`BlockWidget::Slider`]         | `min=`, `max=`, `step=`             |
+//! | `dropdown` | [`BlockWidget::Dropdown`]       | the options, dynamic if there are none |
+//! | `variable` | [`BlockWidget::Dropdown`] of the variables |                              |
+//! | `list`     | [`BlockWidget::Dropdown`] of the lists |                                  |
+//! | `slot`     | [`BlockWidget::Slot`]           | the accepted [`ValueType`], `any` if there is none |
+//!
+//! The shorthands `%s`, `%n`, `%b`, `%c` and `%a` create arguments without a default, keyed by
+//! their position in the block, starting at `"0"`. `%%` is a literal `%`.
+
+use crate::{
+    block::{BlockWidget, DropdownOptions, NumberFormat},
+    value::{parse_color, ValueType},
+};
+use eframe::epaint::Color32;
+use std::{collections::HashSet, error::Error, fmt};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplateErrorKind {
+    /// `%{` without a closing `}`
+    Unterminated,
+    /// `%` followed by something that isn't an argument
+    UnknownToken(char),
+    /// `%` at the end of the template
+    UnexpectedEnd,
+    InvalidKey(String),
+    UnknownType(String),
+    InvalidParameter(String),
+    InvalidDefault(String),
+    DuplicateKey(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateError {
+    pub template: String,
+    /// byte offset of the offending token
+    pub position: usize,
+    pub kind: TemplateErrorKind,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\" at {}: ", self.template, self.position)?;
+        match &self.kind {
+            TemplateErrorKind::Unterminated => write!(f, "unterminated argument"),
+            TemplateErrorKind::UnknownToken(token) => write!(f, "unknown token %{token}"),
+            TemplateErrorKind::UnexpectedEnd => write!(f, "unexpected end after %"),
+            TemplateErrorKind::InvalidKey(key) => write!(f, "invalid key \"{key}\""),
+            TemplateErrorKind::UnknownType(ty) => write!(f, "unknown type \"{ty}\""),
+            TemplateErrorKind::InvalidParameter(parameter) => {
+                write!(f, "invalid parameter \"{parameter}\"")
+            }
+            TemplateErrorKind::InvalidDefault(default) => {
+                write!(f, "invalid default \"{default}\"")
+            }
+            TemplateErrorKind::DuplicateKey(key) => write!(f, "duplicate key \"{key}\""),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+/// Collects the parts of a block, see the [module documentation](self) for the syntax.
+///
+/// ```ignore
+/// let content = ContentBuilder::new()
+///     .part("if %{condition:bool} then")
+///     .part("else")
+///     .build()?;
+/// ```
+#[derive(Default)]
+pub struct ContentBuilder {
+    parts: Vec<String>,
+}
+
+impl ContentBuilder {
+    pub fn new() -> Self {
+        ContentBuilder::default()
+    }
+
+    pub fn part(mut self, template: impl Into<String>) -> Self {
+        self.parts.push(template.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Vec<Vec<BlockWidget>>, TemplateError> {
+        parse_content(&self.parts)
+    }
+}
+
+pub fn parse_part(template: &str) -> Result<Vec<BlockWidget>, TemplateError> {
+    ContentBuilder::new()
+        .part(template)
+        .build()
+        .map(|mut parts| parts.remove(0))
+}
+
+pub(crate) fn parse_content(
+    parts: &[impl AsRef<str>],
+) -> Result<Vec<Vec<BlockWidget>>, TemplateError> {
+    let mut parser = Parser {
+        keys: HashSet::new(),
+        positional: 0,
+    };
+
+    parts
+        .iter()
+        .map(|template| parser.part(template.as_ref()))
+        .collect()
+}
+
+struct Parser {
+    keys: HashSet<String>,
+    /// next key of shorthand arguments
+    positional: usize,
+}
+
+impl Parser {
+    fn part(&mut self, template: &str) -> Result<Vec<BlockWidget>, TemplateError> {
+        let error = |position: usize, kind| TemplateError {
+            template: template.to_string(),
+            position,
+            kind,
+        };
+
+        let mut widgets = Vec::new();
+        let mut label = String::new();
+        let mut chars = template.char_indices().peekable();
+        while let Some((position, char)) = chars.next() {
+            if char != '%' {
+                label.push(char);
+                continue;
+            }
+
+            let widget = match chars.next() {
+                Some((_, '%')) => {
+                    label.push('%');
+                    continue;
+                }
+                Some((start, '{')) => {
+                    let start = start + 1;
+                    let length = template[start..]
+                        .find('}')
+                        .ok_or_else(|| error(position, TemplateErrorKind::Unterminated))?;
+                    let argument = &template[start..start + length];
+                    while chars.next_if(|&(i, _)| i <= start + length).is_some() {}
+
+                    let (key, ty) = argument.split_once(':').unwrap_or((argument, ""));
+                    let key = key.trim();
+                    if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                        return Err(error(
+                            position,
+                            TemplateErrorKind::InvalidKey(key.to_string()),
+                        ));
+                    }
+
+                    argument_widget(key.to_string(), ty).map_err(|kind| error(position, kind))?
+                }
+                Some((_, shorthand)) => {
+                    let ty = match shorthand {
+                        's' => "text",
+                        'n' => "number",
+                        'b' => "bool",
+                        'c' => "color",
+                        'a' => "angle",
+                        _ => {
+                            return Err(error(position, TemplateErrorKind::UnknownToken(shorthand)))
+                        }
+                    };
+
+                    let key = self.positional.to_string();
+                    argument_widget(key, ty).map_err(|kind| error(position, kind))?
+                }
+                None => return Err(error(position, TemplateErrorKind::UnexpectedEnd)),
+            };
+
+            let key = widget.key().unwrap().to_string();
+            if !self.keys.insert(key.clone()) {
+                return Err(error(position, TemplateErrorKind::DuplicateKey(key)));
+            }
+            self.positional += 1;
+
+            let text = label.trim();
+            if !text.is_empty() {
+                widgets.push(BlockWidget::Label {
+                    text: text.to_string().into(),
+                });
+            }
+            label.clear();
+
+            widgets.push(widget);
+        }
+
+        let text = label.trim();
+        if !text.is_empty() {
+            widgets.push(BlockWidget::Label {
+                text: text.to_string().into(),
+            });
+        }
+
+        Ok(widgets)
+    }
+}
+
+/// `ty` is everything after the colon, e.g. `number(min=0)=10`
+fn argument_widget(key: String, ty: &str) -> Result<BlockWidget, TemplateErrorKind> {
+    let (name, parameters, default) = match (ty.find('('), ty.find('=')) {
+        (Some(open), equals) if equals.is_none_or(|equals| open < equals) => {
+            let close = ty[open..]
+                .find(')')
+                .map(|close| open + close)
+                .ok_or_else(|| TemplateErrorKind::InvalidParameter(ty[open..].to_string()))?;
+            let default = match &ty[close + 1..] {
+                "" => None,
+                rest => Some(
+                    rest.strip_prefix('=')
+                        .ok_or_else(|| TemplateErrorKind::InvalidDefault(rest.to_string()))?,
+                ),
+            };
+            let parameters = ty[open + 1..close]
+                .split(',')
+                .map(str::trim)
+                .filter(|parameter| !parameter.is_empty())
+                .collect();
+
+            (ty[..open].trim(), parameters, default)
+        }
+        _ => match ty.split_once('=') {
+            Some((name, default)) => (name.trim(), Vec::new(), Some(default)),
+            None => (ty.trim(), Vec::new(), None),
+        },
+    };
+
+    let invalid_default = || TemplateErrorKind::InvalidDefault(default.unwrap_or("").to_string());
+    let number = |default: Option<&str>| match default {
+        None => Ok(None),
+        Some(default) => default
+            .trim()
+            .parse::<f64>()
+            .map(Some)
+            .map_err(|_| invalid_default()),
+    };
+    let named = |allowed: &[&str]| {
+        parameters
+            .iter()
+            .map(|parameter| {
+                parameter
+                    .split_once('=')
+                    .map(|(name, value)| (name.trim(), value.trim()))
+                    .filter(|(name, _)| allowed.contains(name))
+                    .ok_or_else(|| TemplateErrorKind::InvalidParameter(parameter.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let parse_parameter = |name: &str, value: &str| {
+        value
+            .parse::<f64>()
+            .map_err(|_| TemplateErrorKind::InvalidParameter(format!("{name}={value}")))
+    };
+    let key = key.into();
+
+    Ok(match name {
+        "text" | "" => {
+            named(&[])?;
+            BlockWidget::TextEdit {
+                key,
+                default: default.unwrap_or("").to_string().into(),
+            }
+        }
+        "number" | "integer" => {
+            let mut format = NumberFormat {
+                integer: name == "integer",
+                ..Default::default()
+            };
+            for (name, value) in named(&["min", "max", "step", "prefix", "suffix"])? {
+                match name {
+                    "min" => format.min = Some(parse_parameter(name, value)?),
+                    "max" => format.max = Some(parse_parameter(name, value)?),
+                    "step" => format.step = parse_parameter(name, value)?,
+                    "prefix" => format.prefix = value.to_string().into(),
+                    _ => format.suffix = value.to_string().into(),
+                }
+            }
+
+            if let (Some(min), Some(max)) = (format.min, format.max) {
+                if min > max {
+                    return Err(TemplateErrorKind::InvalidParameter(format!(
+                        "min={min}, max={max}"
+                    )));
+                }
+            }
+
+            BlockWidget::NumberEdit {
+                key,
+                default: number(default)?.unwrap_or(0.0),
+                format,
+            }
+        }
+        "bool" => {
+            named(&[])?;
+            BlockWidget::Checkbox {
+                key,
+                default: match default.map(str::trim) {
+                    None | Some("false") => false,
+                    Some("true") => true,
+                    Some(_) => return Err(invalid_default()),
+                },
+            }
+        }
+        "color" => {
+            named(&[])?;
+            BlockWidget::ColorPicker {
+                key,
+                default: match default {
+                    None => Color32::WHITE,
+                    Some(color) => parse_color(color.trim()).ok_or_else(invalid_default)?,
+                },
+            }
+        }
+        "angle" => {
+            let mut snap = 15.0;
+            for (name, value) in named(&["snap"])? {
+                snap = parse_parameter(name, value)? as f32;
+            }
+
+            BlockWidget::Angle {
+                key,
+                default: number(default)?.unwrap_or(90.0) as f32,
+                snap,
+            }
+        }
+        "slider" => {
+            let (mut min, mut max, mut step) = (0.0, 100.0, 0.0);
+            for (name, value) in named(&["min", "max", "step"])? {
+                match name {
+                    "min" => min = parse_parameter(name, value)?,
+                    "max" => max = parse_parameter(name, value)?,
+                    _ => step = parse_parameter(name, value)?,
+                }
+            }
+
+            if min > max {
+                return Err(TemplateErrorKind::InvalidParameter(format!(
+                    "min={min}, max={max}"
+                )));
+            }
+
+            BlockWidget::Slider {
+                key,
+                default: number(default)?.unwrap_or(min),
+                min,
+                max,
+                step,
+            }
+        }
+        "dropdown" => {
+            let default = default.map(str::trim);
+            if default
+                .is_some_and(|default| !parameters.is_empty() && !parameters.contains(&default))
+            {
+                return Err(invalid_default());
+            }
+
+            let options = if parameters.is_empty() {
+                DropdownOptions::Dynamic
+            } else {
+                DropdownOptions::Static(
+                    parameters
+                        .iter()
+                        .map(|option| option.to_string().into())
+                        .collect(),
+                )
+            };
+
+            BlockWidget::Dropdown {
+                key,
+                default: default
+                    .or(parameters.first().copied())
+                    .unwrap_or("")
+                    .to_string()
+                    .into(),
+                options,
+            }
+        }
+        "variable" | "list" => {
+            named(&[])?;
+            BlockWidget::Dropdown {
+                key,
+                options: if name == "variable" {
+                    DropdownOptions::Variables
+                } else {
+                    DropdownOptions::Lists
+                },
+                default: default.unwrap_or("").trim().to_string().into(),
+            }
+        }
+        "slot" => {
+            if default.is_some() {
+                return Err(invalid_default());
+            }
+
+            let accepts = match parameters.as_slice() {
+                [] => ValueType::Any,
+                [accepts] => ValueType::from(*accepts),
+                _ => {
+                    return Err(TemplateErrorKind::InvalidParameter(parameters.join(", ")));
+                }
+            };
+
+            BlockWidget::Slot { key, accepts }
+        }
+        _ => return Err(TemplateErrorKind::UnknownType(name.to_string())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(template: &str) -> TemplateErrorKind {
+        parse_part(template).err().expect("parsed").kind
+    }
+
+    #[test]
+    fn labels_and_arguments() {
+        let widgets = parse_part("move %{steps:number(min=0, suffix=px)=10} steps 100%%").unwrap();
+        assert_eq!(widgets.len(), 3);
+        assert!(matches!(&widgets[0], BlockWidget::Label { text } if text == "move"));
+        assert!(matches!(&widgets[2], BlockWidget::Label { text } if text == "steps 100%"));
+        let BlockWidget::NumberEdit {
+            key,
+            default,
+            format,
+        } = &widgets[1]
+        else {
+            panic!("not a number");
+        };
+        assert_eq!((key.as_ref(), *default), ("steps", 10.0));
+        assert_eq!((format.min, format.max), (Some(0.0), None));
+        assert_eq!(format.suffix, "px");
+    }
+
+    #[test]
+    fn types() {
+        let widgets = parse_part(
+            "%{a} %{b:integer} %{c:bool=true} %{d:color=#ff0000} %{e:angle(snap=5)} \
+             %{f:slider(min=1, max=5)} %{g:dropdown(up, down)} %{h:dropdown} %{i:slot(number)} \
+             %{j:variable} %{k:list}",
+        )
+        .unwrap();
+        let widgets: Vec<&BlockWidget> = widgets
+            .iter()
+            .filter(|widget| widget.key().is_some())
+            .collect();
+
+        assert!(matches!(widgets[0], BlockWidget::TextEdit { default, .. } if default.is_empty()));
+        assert!(matches!(widgets[1], BlockWidget::NumberEdit { format, .. } if format.integer));
+        assert!(matches!(
+            widgets[2],
+            BlockWidget::Checkbox { default: true, .. }
+        ));
+        assert!(
+            matches!(widgets[3], BlockWidget::ColorPicker { default, .. } if *default == Color32::RED)
+        );
+        assert!(
+            matches!(widgets[4], BlockWidget::Angle { snap, default, .. }
+            if *snap == 5.0 && *default == 90.0)
+        );
+        assert!(
+            matches!(widgets[5], BlockWidget::Slider { min, max, default, .. }
+            if (*min, *max, *default) == (1.0, 5.0, 1.0))
+        );
+        assert!(matches!(
+            widgets[6],
+            BlockWidget::Dropdown { default, options: DropdownOptions::Static(options), .. }
+                if default == "up" && options.len() == 2
+        ));
+        assert!(matches!(
+            widgets[7],
+            BlockWidget::Dropdown {
+                options: DropdownOptions::Dynamic,
+                ..
+            }
+        ));
+        assert!(matches!(
+            widgets[8],
+            BlockWidget::Slot {
+                accepts: ValueType::Number,
+                ..
+            }
+        ));
+        assert!(matches!(
+            widgets[9],
+            BlockWidget::Dropdown {
+                options: DropdownOptions::Variables,
+                ..
+            }
+        ));
+        assert!(matches!(
+            widgets[10],
+            BlockWidget::Dropdown {
+                options: DropdownOptions::Lists,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn shorthands() {
+        let content = ContentBuilder::new()
+            .part("%s and %n")
+            .part("%b")
+            .build()
+            .unwrap();
+        let keys: Vec<Vec<&str>> = content
+            .iter()
+            .map(|part| part.iter().filter_map(BlockWidget::key).collect())
+            .collect();
+        assert_eq!(keys, [vec!["0", "1"], vec!["2"]]);
+        assert!(matches!(content[1][0], BlockWidget::Checkbox { .. }));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("say %{text"), TemplateErrorKind::Unterminated);
+        assert_eq!(error("100%"), TemplateErrorKind::UnexpectedEnd);
+        assert_eq!(error("%x"), TemplateErrorKind::UnknownToken('x'));
+        assert_eq!(
+            error("%{a b}"),
+            TemplateErrorKind::InvalidKey("a b".to_string())
+        );
+        assert_eq!(
+            error("%{a:vector}"),
+            TemplateErrorKind::UnknownType("vector".to_string())
+        );
+        assert_eq!(
+            error("%{a:number(min=x)}"),
+            TemplateErrorKind::InvalidParameter("min=x".to_string())
+        );
+        assert_eq!(
+            error("%{a:number(min=5, max=1)}"),
+            TemplateErrorKind::InvalidParameter("min=5, max=1".to_string())
+        );
+        assert_eq!(
+            error("%{a:bool=maybe}"),
+            TemplateErrorKind::InvalidDefault("maybe".to_string())
+        );
+        assert_eq!(
+            error("%{a:dropdown(up, down)=left}"),
+            TemplateErrorKind::InvalidDefault("left".to_string())
+        );
+        assert_eq!(
+            error("%{a} %{a}"),
+            TemplateErrorKind::DuplicateKey("a".to_string())
+        );
+
+        let error = parse_part("go %{a:slot(number, text)}").err().unwrap();
+        assert_eq!(error.position, 3);
+    }
+}
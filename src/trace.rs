@@ -0,0 +1,345 @@
+//! Recording the blocks the threads of a [`BlockEditor`] run, and replaying them.
+
+use crate::{
+    block::BlockEditor,
+    interpreter::ThreadId,
+    registry::BlockRegistry,
+    save::{LoadError, SavedValue, SavedWorkspace},
+    value::Value,
+};
+use eframe::egui::{Button, Grid, Slider, Ui};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+use thunderdome::Index;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Trace {
+    /// the workspace when the recording started
+    pub workspace: SavedWorkspace,
+    /// in the order the blocks ran
+    pub steps: Vec<TraceStep>,
+}
+
+/// A block which ran or reported a value.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceStep {
+    /// index into the blocks of [`Trace::workspace`]
+    pub block: usize,
+    /// id of the thread which ran it
+    pub thread: u64,
+    /// values of the widgets and inputs of the block
+    #[serde(default)]
+    pub inputs: BTreeMap<String, SavedValue>,
+    /// the value reported by reporters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<SavedValue>,
+}
+
+pub(crate) struct Recording {
+    trace: Trace,
+    /// positions of the blocks in [`Trace::workspace`]
+    positions: HashMap<Index, usize>,
+}
+
+pub(crate) struct Replay {
+    steps: Vec<TraceStep>,
+    /// the blocks of the replayed workspace, in the order of [`Trace::workspace`]
+    indices: Vec<Index>,
+    position: usize,
+}
+
+/// Custom values are recorded as text.
+fn saved_value(value: &Value) -> SavedValue {
+    SavedValue::from_value(value).unwrap_or_else(|| SavedValue::Text(value.to_text().into_owned()))
+}
+
+impl BlockEditor {
+    /// Starts recording every block the threads run, replacing the current recording.
+    pub fn start_recording(&mut self) {
+        let positions = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(position, (index, _block))| (index, position))
+            .collect();
+
+        self.recording = Some(Recording {
+            trace: Trace {
+                workspace: self.save(),
+                steps: Vec::new(),
+            },
+            positions,
+        });
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Stops recording, `None` if the editor wasn't recording.
+    pub fn stop_recording(&mut self) -> Option<Trace> {
+        self.recording.take().map(|recording| recording.trace)
+    }
+
+    /// Called by the interpreter for every block it runs or evaluates.
+    pub(crate) fn record(
+        &mut self,
+        thread: ThreadId,
+        index: Index,
+        values: &HashMap<Cow<'static, str>, Value>,
+        output: Option<&Value>,
+    ) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        // added after the recording started
+        let Some(&block) = recording.positions.get(&index) else {
+            return;
+        };
+
+        recording.trace.steps.push(TraceStep {
+            block,
+            thread: thread.0,
+            inputs: values
+                .iter()
+                .map(|(key, value)| (key.to_string(), saved_value(value)))
+                .collect(),
+            output: output.map(saved_value),
+        });
+    }
+
+    /// An editor with the workspace of the trace, replaying it from the first step. Nothing
+    /// runs, the blocks are highlighted one step at a time with the values they saw.
+    pub fn replay(trace: &Trace, registry: &BlockRegistry) -> Result<BlockEditor, LoadError> {
+        let mut editor = BlockEditor::load(&trace.workspace, registry)?;
+        let indices = editor.blocks.iter().map(|(index, _block)| index).collect();
+        editor.replay = Some(Replay {
+            steps: trace.steps.clone(),
+            indices,
+            position: 0,
+        });
+        editor.show_replay_step();
+        Ok(editor)
+    }
+
+    /// Replaying a trace, no scripts run in the meantime.
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Leaves replay mode, the workspace stays as it is.
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
+
+    /// The step being shown and the number of steps.
+    pub fn replay_position(&self) -> Option<(usize, usize)> {
+        let replay = self.replay.as_ref()?;
+        Some((replay.position, replay.steps.len()))
+    }
+
+    pub fn set_replay_position(&mut self, position: usize) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+
+        replay.position = position.min(replay.steps.len().saturating_sub(1));
+        self.show_replay_step();
+    }
+
+    /// The step being shown with the block it ran, which might have been removed since.
+    pub fn replay_step(&self) -> Option<(&TraceStep, Option<Index>)> {
+        let replay = self.replay.as_ref()?;
+        let step = replay.steps.get(replay.position)?;
+        let index = replay
+            .indices
+            .get(step.block)
+            .copied()
+            .filter(|index| self.blocks.contains(*index));
+        Some((step, index))
+    }
+
+    /// Flashes the block of the current step.
+    fn show_replay_step(&mut self) {
+        if let Some((_step, Some(index))) = self.replay_step() {
+            self.evaluated.insert(index, std::time::Instant::now());
+        }
+    }
+}
+
+/// Buttons for stepping through the trace being replayed, with the values of the current step.
+pub fn replay_controls(ui: &mut Ui, editor: &mut BlockEditor) {
+    let Some((position, len)) = editor.replay_position() else {
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(position > 0, Button::new("◀ Back"))
+            .clicked()
+        {
+            editor.set_replay_position(position - 1);
+        }
+
+        if ui
+            .add_enabled(position + 1 < len, Button::new("Forward ▶"))
+            .clicked()
+        {
+            editor.set_replay_position(position + 1);
+        }
+
+        let mut step = position + 1;
+        if len > 1
+            && ui
+                .add(Slider::new(&mut step, 1..=len).text(format!("of {len}")))
+                .changed()
+        {
+            editor.set_replay_position(step - 1);
+        }
+
+        if ui.button("Stop replay").clicked() {
+            editor.stop_replay();
+        }
+    });
+
+    let Some((step, index)) = editor.replay_step() else {
+        ui.weak("the trace is empty");
+        return;
+    };
+
+    let opcode = index.map_or("removed block", |index| {
+        editor.blocks[index].implementation.opcode()
+    });
+    ui.label(format!("thread {}: {opcode}", step.thread));
+    Grid::new("replay values").num_columns(2).show(ui, |ui| {
+        for (key, value) in &step.inputs {
+            ui.label(key);
+            ui.label(value.to_value().to_text());
+            ui.end_row();
+        }
+
+        if let Some(output) = &step.output {
+            ui.strong("reports");
+            ui.label(output.to_value().to_text());
+            ui.end_row();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::{Event, WHEN_GAME_STARTS},
+        save::tests::{number, text, Workspace},
+        variable::{CHANGE_VARIABLE, SET_VARIABLE},
+        vm::Vm,
+    };
+
+    fn workspace() -> Workspace {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("x"))]);
+        let join = workspace.block("operator_join", &[("a", text("a")), ("b", text("b"))]);
+        workspace.input(set, "value", join);
+        let change = workspace.block(
+            CHANGE_VARIABLE,
+            &[("variable", text("x")), ("value", number(2.0))],
+        );
+        workspace.stack(&[hat, set, change]);
+        workspace
+    }
+
+    fn record(vm: bool) -> Trace {
+        let mut editor = workspace().load();
+        editor.start_recording();
+        if vm {
+            let mut vm = Vm::new(editor.compile(), &editor);
+            vm.emit(Event::GameStarts);
+            while vm.is_running() {
+                vm.tick(&mut editor);
+            }
+        } else {
+            editor.emit(Event::GameStarts);
+            while !editor.threads().is_empty() {
+                editor.tick();
+            }
+        }
+
+        assert!(editor.is_recording());
+        let trace = editor.stop_recording().unwrap();
+        assert!(!editor.is_recording());
+        trace
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let trace = record(false);
+        let steps: Vec<String> = trace
+            .steps
+            .iter()
+            .map(|step| format!("{} {:?} {:?}", step.block, step.inputs, step.output))
+            .collect();
+        // reporters are recorded before the blocks using their values
+        assert_eq!(
+            steps,
+            [
+                "0 {} None",
+                r#"2 {"a": Text("a"), "b": Text("b")} Some(Text("ab"))"#,
+                r#"1 {"value": Text("ab"), "variable": Text("x")} None"#,
+                r#"3 {"value": Float(2.0), "variable": Text("x")} None"#,
+            ]
+        );
+        // the workspace as it was before the script ran
+        assert_eq!(trace.workspace.variables[0].value, number(0.0));
+
+        let json = serde_json::to_string(&trace).unwrap();
+        let trace: Trace = serde_json::from_str(&json).unwrap();
+        let mut editor = BlockEditor::replay(&trace, &workspace().registry()).unwrap();
+        assert!(editor.is_replaying());
+        assert_eq!(editor.replay_position(), Some((0, 4)));
+        let (step, index) = editor.replay_step().unwrap();
+        assert_eq!(step.block, 0);
+        assert!(editor.last_evaluated(index.unwrap()).is_some());
+
+        editor.set_replay_position(1);
+        let (step, index) = editor.replay_step().unwrap();
+        assert_eq!(step.output, Some(text("ab")));
+        let index = index.unwrap();
+        assert_eq!(
+            editor.blocks[index].implementation.opcode(),
+            "operator_join"
+        );
+        assert!(editor.last_evaluated(index).is_some());
+        editor.set_replay_position(10);
+        assert_eq!(editor.replay_position(), Some((3, 4)));
+
+        // nothing runs while replaying
+        editor.emit(Event::GameStarts);
+        editor.tick();
+        assert_eq!(editor.variables().get("x").unwrap().to_number(), 0.0);
+        editor.stop_replay();
+        assert!(!editor.is_replaying());
+        assert_eq!(editor.replay_position(), None);
+    }
+
+    #[test]
+    fn record_vm() {
+        // the same blocks, without their values
+        let mut blocks: Vec<usize> = record(true)
+            .steps
+            .iter()
+            .map(|step| {
+                assert!(step.inputs.is_empty() && step.output.is_none());
+                step.block
+            })
+            .collect();
+        blocks.sort();
+        assert_eq!(blocks, [0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,537 @@
+//! Values passed between widgets, blocks, variables and the interpreter.
+//!
+//! Every value can be converted into every other type, following the rules of Scratch:
+//!
+//! | from \ to | number              | text                   | bool                              |
+//! |-----------|---------------------|------------------------|-----------------------------------|
+//! | number    |                     | `5`, `0.5`, `Infinity` | false if 0 or NaN                 |
+//! | text      | parsed, otherwise 0 |                        | false if `""`, `"0"` or `"false"` |
+//! | bool      | 1 or 0              | `true` or `false`      |                                   |
+//! | color     | `0xRRGGBB`          | `#rrggbb`              | true                              |
+//! | list      | the text, parsed    | items joined by spaces | the text                          |
+//!
+//! Items of lists are joined without spaces if they are all single characters.
+
+use eframe::epaint::Color32;
+use serde::{Deserialize, Serialize};
+use std::{any::Any, borrow::Cow, cmp::Ordering, fmt, rc::Rc};
+
+/// Type accepted by an input slot or reported by a block, written as e.g. `"number"` or
+/// `"Entity"` in data files.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ValueType {
+    /// only known at run time
+    #[default]
+    Any,
+    Number,
+    Text,
+    Bool,
+    Color,
+    List,
+    /// [`CustomValue::type_name`]
+    Custom(Cow<'static, str>),
+}
+
+impl ValueType {
+    pub fn name(&self) -> &str {
+        match self {
+            ValueType::Any => "any",
+            ValueType::Number => "number",
+            ValueType::Text => "text",
+            ValueType::Bool => "bool",
+            ValueType::Color => "color",
+            ValueType::List => "list",
+            ValueType::Custom(name) => name,
+        }
+    }
+
+    pub fn of(value: &Value) -> ValueType {
+        match value {
+            Value::Number(_) => ValueType::Number,
+            Value::Text(_) => ValueType::Text,
+            Value::Bool(_) => ValueType::Bool,
+            Value::Color(_) => ValueType::Color,
+            Value::List(_) => ValueType::List,
+            Value::Custom(custom) => ValueType::Custom(custom.type_name().to_string().into()),
+        }
+    }
+
+    /// Whether a slot of this type accepts blocks reporting `output`.
+    ///
+    /// Bool and custom slots only accept exactly their type, every other slot also accepts
+    /// blocks whose type is only known at run time, which is then converted.
+    pub fn accepts(&self, output: &ValueType) -> bool {
+        match (self, output) {
+            (ValueType::Any, _) => true,
+            (ValueType::Bool | ValueType::Custom(_), ValueType::Any) => false,
+            (_, ValueType::Any) => true,
+            _ => self == output,
+        }
+    }
+
+    /// value of empty slots
+    pub fn default_value(&self) -> Value {
+        match self {
+            ValueType::Number => Value::Number(0.0),
+            ValueType::Bool => Value::Bool(false),
+            ValueType::Color => Value::Color(Color32::BLACK),
+            ValueType::List => Value::List(Vec::new()),
+            _ => Value::default(),
+        }
+    }
+}
+
+impl From<&str> for ValueType {
+    fn from(name: &str) -> Self {
+        match name {
+            "any" => ValueType::Any,
+            "number" => ValueType::Number,
+            "text" => ValueType::Text,
+            "bool" => ValueType::Bool,
+            "color" => ValueType::Color,
+            "list" => ValueType::List,
+            _ => ValueType::Custom(name.to_string().into()),
+        }
+    }
+}
+
+impl From<String> for ValueType {
+    fn from(name: String) -> Self {
+        ValueType::from(name.as_str())
+    }
+}
+
+impl From<ValueType> for String {
+    fn from(ty: ValueType) -> Self {
+        ty.name().to_string()
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Value of a game-specific type, e.g. an entity.
+pub trait CustomValue: fmt::Debug {
+    /// e.g. `"Entity"`
+    fn type_name(&self) -> &str;
+
+    fn to_text(&self) -> String;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Color(Color32),
+    List(Vec<Value>),
+    Custom(Rc<dyn CustomValue>),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Text(String::new())
+    }
+}
+
+/// Parses numbers the way JavaScript's `Number()` does, `None` if `text` isn't a number.
+fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0.0);
+    }
+
+    let radix = |prefix: &str, radix| {
+        let digits = text.strip_prefix(prefix)?;
+        u64::from_str_radix(digits, radix).ok().map(|n| n as f64)
+    };
+    if let Some(number) = radix("0x", 16).or(radix("0b", 2)).or(radix("0o", 8)) {
+        return Some(number);
+    }
+
+    match text {
+        "Infinity" | "+Infinity" => Some(f64::INFINITY),
+        "-Infinity" => Some(f64::NEG_INFINITY),
+        // Rust also accepts "inf" and "nan"
+        _ if text
+            .chars()
+            .any(|c| c.is_alphabetic() && c != 'e' && c != 'E') =>
+        {
+            None
+        }
+        _ => text.parse().ok(),
+    }
+}
+
+pub(crate) fn format_number(number: f64) -> String {
+    if number.is_nan() {
+        "NaN".to_string()
+    } else if number.is_infinite() {
+        if number > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .to_string()
+    } else if number == 0.0 {
+        // no negative zero
+        "0".to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+/// `#RRGGBB`, or `#RRGGBBAA` with alpha
+pub(crate) fn parse_color(color: &str) -> Option<Color32> {
+    let hex = color.strip_prefix('#')?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let [r, g, b, a] = match hex.len() {
+        6 => (value << 8 | 0xFF).to_be_bytes(),
+        8 => value.to_be_bytes(),
+        _ => return None,
+    };
+
+    Some(Color32::from_rgba_unmultiplied(r, g, b, a))
+}
+
+impl Value {
+    /// e.g. `"number"`, or [`CustomValue::type_name`]
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Text(_) => "text",
+            Value::Bool(_) => "bool",
+            Value::Color(_) => "color",
+            Value::List(_) => "list",
+            Value::Custom(custom) => custom.type_name(),
+        }
+    }
+
+    pub fn to_number(&self) -> f64 {
+        let number = match self {
+            Value::Number(number) => *number,
+            Value::Bool(bool) => *bool as u8 as f64,
+            Value::Color(color) => {
+                let [r, g, b, _] = color.to_srgba_unmultiplied();
+                u32::from_be_bytes([0, r, g, b]) as f64
+            }
+            _ => parse_number(&self.to_text()).unwrap_or(0.0),
+        };
+
+        if number.is_nan() {
+            0.0
+        } else {
+            number
+        }
+    }
+
+    pub fn to_text(&self) -> Cow<'_, str> {
+        match self {
+            Value::Number(number) => format_number(*number).into(),
+            Value::Text(text) => text.into(),
+            Value::Bool(bool) => bool.to_string().into(),
+            Value::Color(color) => {
+                let [r, g, b, a] = color.to_srgba_unmultiplied();
+                if a == u8::MAX {
+                    format!("#{r:02x}{g:02x}{b:02x}").into()
+                } else {
+                    format!("#{r:02x}{g:02x}{b:02x}{a:02x}").into()
+                }
+            }
+            Value::List(items) => {
+                let items: Vec<_> = items.iter().map(Value::to_text).collect();
+                let separator = if items.iter().all(|item| item.chars().count() == 1) {
+                    ""
+                } else {
+                    " "
+                };
+
+                items.join(separator).into()
+            }
+            Value::Custom(custom) => custom.to_text().into(),
+        }
+    }
+
+    pub fn to_bool(&self) -> bool {
+        match self {
+            Value::Number(number) => *number != 0.0 && !number.is_nan(),
+            Value::Bool(bool) => *bool,
+            Value::Color(_) => true,
+            _ => {
+                let text = self.to_text();
+                !(text.is_empty() || text == "0" || text.eq_ignore_ascii_case("false"))
+            }
+        }
+    }
+
+    /// Numbers are `0xRRGGBB` or `0xAARRGGBB` if the alpha isn't 0, text is `#RRGGBB`.
+    pub fn to_color(&self) -> Color32 {
+        match self {
+            Value::Color(color) => *color,
+            Value::Text(text) if text.starts_with('#') => {
+                parse_color(text).unwrap_or(Color32::BLACK)
+            }
+            _ => {
+                let [a, r, g, b] = (self.to_number() as i64 as u32).to_be_bytes();
+                let a = if a == 0 { u8::MAX } else { a };
+                Color32::from_rgba_unmultiplied(r, g, b, a)
+            }
+        }
+    }
+
+    /// Lists stay lists, everything else becomes a list with a single item.
+    pub fn to_list(&self) -> Vec<Value> {
+        match self {
+            Value::List(items) => items.clone(),
+            _ => vec![self.clone()],
+        }
+    }
+
+    /// Numeric if both values are numbers, otherwise case-insensitive by their text.
+    pub fn compare(&self, other: &Value) -> Ordering {
+        let number = |value: &Value| match value {
+            Value::Number(number) => Some(*number),
+            Value::Bool(_) => Some(value.to_number()),
+            // whitespace isn't 0 when comparing
+            Value::Text(text) if text.trim().is_empty() => None,
+            Value::Text(text) => parse_number(text),
+            _ => None,
+        };
+
+        match (number(self), number(other)) {
+            (Some(a), Some(b)) if !a.is_nan() && !b.is_nan() => a.total_cmp(&b),
+            _ => {
+                let a = self.to_text().to_lowercase();
+                let b = other.to_text().to_lowercase();
+                a.cmp(&b)
+            }
+        }
+    }
+
+    /// Scratch's `=`, custom values are only equal to themselves.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Custom(a), Value::Custom(b)) => Rc::ptr_eq(a, b),
+            (Value::Custom(_), _) | (_, Value::Custom(_)) => false,
+            _ => self.compare(other) == Ordering::Equal,
+        }
+    }
+
+    /// Converts the value to text in place, for widgets editing it.
+    pub(crate) fn text_mut(&mut self) -> &mut String {
+        if !matches!(self, Value::Text(_)) {
+            *self = Value::Text(self.to_text().into_owned());
+        }
+
+        match self {
+            Value::Text(text) => text,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Converts the value to a number in place, for widgets editing it.
+    pub(crate) fn number_mut(&mut self) -> &mut f64 {
+        if !matches!(self, Value::Number(_)) {
+            *self = Value::Number(self.to_number());
+        }
+
+        match self {
+            Value::Number(number) => number,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Converts the value to a bool in place, for widgets editing it.
+    pub(crate) fn bool_mut(&mut self) -> &mut bool {
+        if !matches!(self, Value::Bool(_)) {
+            *self = Value::Bool(self.to_bool());
+        }
+
+        match self {
+            Value::Bool(bool) => bool,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Converts the value to a color in place, for widgets editing it.
+    pub(crate) fn color_mut(&mut self) -> &mut Color32 {
+        if !matches!(self, Value::Color(_)) {
+            *self = Value::Color(self.to_color());
+        }
+
+        match self {
+            Value::Color(color) => color,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Value::Number(number)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(number: i64) -> Self {
+        Value::Number(number as f64)
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Value::Bool(bool)
+    }
+}
+
+impl From<Color32> for Value {
+    fn from(color: Color32) -> Self {
+        Value::Color(color)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::List(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_number() {
+        let numbers = [
+            (Value::from("12.5"), 12.5),
+            (Value::from(" 3 "), 3.0),
+            (Value::from(""), 0.0),
+            (Value::from("0x1f"), 31.0),
+            (Value::from("0b101"), 5.0),
+            (Value::from("1e3"), 1000.0),
+            (Value::from("-Infinity"), f64::NEG_INFINITY),
+            (Value::from("inf"), 0.0),
+            (Value::from("nan"), 0.0),
+            (Value::from("abc"), 0.0),
+            (Value::Number(f64::NAN), 0.0),
+            (Value::from(true), 1.0),
+            (Value::from(false), 0.0),
+            (Value::from(Color32::from_rgb(1, 2, 3)), 0x010203 as f64),
+            (Value::from(vec![Value::from(4.0), Value::from(2.0)]), 42.0),
+        ];
+        for (value, number) in numbers {
+            assert_eq!(value.to_number(), number, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn to_text() {
+        let texts = [
+            (Value::from(5.0), "5"),
+            (Value::from(0.5), "0.5"),
+            (Value::from(-0.0), "0"),
+            (Value::Number(f64::NAN), "NaN"),
+            (Value::Number(f64::INFINITY), "Infinity"),
+            (Value::from(true), "true"),
+            (Value::from(Color32::from_rgb(255, 0, 16)), "#ff0010"),
+            (Value::from(vec![Value::from("a"), Value::from(1.0)]), "a1"),
+            (
+                Value::from(vec![Value::from("ab"), Value::from(1.0)]),
+                "ab 1",
+            ),
+        ];
+        for (value, text) in texts {
+            assert_eq!(value.to_text(), text, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn to_bool() {
+        let bools = [
+            (Value::from(0.0), false),
+            (Value::Number(f64::NAN), false),
+            (Value::from(-1.0), true),
+            (Value::from(""), false),
+            (Value::from("0"), false),
+            (Value::from("FALSE"), false),
+            (Value::from("no"), true),
+            (Value::from(Color32::BLACK), true),
+            (Value::from(Vec::new()), false),
+        ];
+        for (value, bool) in bools {
+            assert_eq!(value.to_bool(), bool, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn to_color() {
+        assert_eq!(
+            Value::from("#102030").to_color(),
+            Color32::from_rgb(16, 32, 48)
+        );
+        assert_eq!(Value::from(0xff0000 as f64).to_color(), Color32::RED);
+        assert_eq!(Value::from("red").to_color(), Color32::BLACK);
+    }
+
+    #[test]
+    fn compare() {
+        assert!(Value::from("10").equals(&Value::from(10.0)));
+        assert!(Value::from("ABC").equals(&Value::from("abc")));
+        assert!(!Value::from(" ").equals(&Value::from(0.0)));
+        assert!(Value::from(true).equals(&Value::from(1.0)));
+        assert_eq!(Value::from("9").compare(&Value::from("10")), Ordering::Less);
+        assert_eq!(
+            Value::from("b").compare(&Value::from("A")),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn accepts() {
+        let entity = ValueType::from("Entity");
+        assert_eq!(entity, ValueType::Custom("Entity".into()));
+        assert_eq!(ValueType::from("number"), ValueType::Number);
+
+        let accepted = [
+            (ValueType::Any, ValueType::Bool, true),
+            (ValueType::Any, entity.clone(), true),
+            (ValueType::Number, ValueType::Number, true),
+            (ValueType::Number, ValueType::Any, true),
+            (ValueType::Number, ValueType::Text, false),
+            (ValueType::Text, ValueType::Number, false),
+            (ValueType::Bool, ValueType::Bool, true),
+            (ValueType::Bool, ValueType::Any, false),
+            (ValueType::Bool, ValueType::Number, false),
+            (entity.clone(), entity.clone(), true),
+            (entity.clone(), ValueType::Any, false),
+            (entity, ValueType::Custom("Item".into()), false),
+        ];
+        for (slot, output, accepts) in accepted {
+            assert_eq!(slot.accepts(&output), accepts, "{slot} accepts {output}");
+        }
+    }
+}
@@ -0,0 +1,774 @@
+//! Variables and lists of a workspace and the built-in blocks using them.
+//!
+//! A [`BlockEditor`](crate::block::BlockEditor) holds the scripts of a single object, its
+//! variables are either shared with every other object or only visible to that object.
+//!
+//! Items of lists are numbered starting at 1, indices may also be `"last"`, and `"all"` when
+//! deleting.
+
+use crate::{
+    block::{Block, BlockFields},
+    field::DerivedBlock,
+    value::Value,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error::Error, fmt};
+
+pub const SET_VARIABLE: &str = "data_setvariableto";
+pub const CHANGE_VARIABLE: &str = "data_changevariableby";
+pub const VARIABLE: &str = "data_variable";
+pub const ADD_TO_LIST: &str = "data_addtolist";
+pub const DELETE_OF_LIST: &str = "data_deleteoflist";
+pub const DELETE_ALL_OF_LIST: &str = "data_deletealloflist";
+pub const INSERT_AT_LIST: &str = "data_insertatlist";
+pub const REPLACE_ITEM_OF_LIST: &str = "data_replaceitemoflist";
+pub const ITEM_OF_LIST: &str = "data_itemoflist";
+pub const ITEM_NUM_OF_LIST: &str = "data_itemnumoflist";
+pub const LENGTH_OF_LIST: &str = "data_lengthoflist";
+pub const LIST_CONTAINS_ITEM: &str = "data_listcontainsitem";
+pub const LIST_CONTENTS: &str = "data_listcontents";
+
+pub(crate) const LIST_BLOCKS: [&str; 10] = [
+    ADD_TO_LIST,
+    DELETE_OF_LIST,
+    DELETE_ALL_OF_LIST,
+    INSERT_AT_LIST,
+    REPLACE_ITEM_OF_LIST,
+    ITEM_OF_LIST,
+    ITEM_NUM_OF_LIST,
+    LENGTH_OF_LIST,
+    LIST_CONTAINS_ITEM,
+    LIST_CONTENTS,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableScope {
+    /// shared by all objects
+    Global,
+    /// only visible to the object the editor belongs to
+    Object,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableError {
+    EmptyName,
+    /// a variable or list with this name already exists in either scope
+    Exists(String),
+    Unknown(String),
+}
+
+impl fmt::Display for VariableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableError::EmptyName => write!(f, "names can't be empty"),
+            VariableError::Exists(name) => write!(f, "\"{name}\" already exists"),
+            VariableError::Unknown(name) => write!(f, "\"{name}\" doesn't exist"),
+        }
+    }
+}
+
+impl Error for VariableError {}
+
+/// Whether a name refers to a variable or a list, they're kept apart like in Scratch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableKind {
+    Variable,
+    List,
+}
+
+/// Entries of both scopes, names are unique across them.
+#[derive(Clone, Debug)]
+struct Table<T> {
+    global: BTreeMap<String, T>,
+    object: BTreeMap<String, T>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table {
+            global: BTreeMap::new(),
+            object: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> Table<T> {
+    fn get(&self, name: &str) -> Option<&T> {
+        self.object.get(name).or_else(|| self.global.get(name))
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut T> {
+        self.object
+            .get_mut(name)
+            .or_else(|| self.global.get_mut(name))
+    }
+
+    fn scope(&self, name: &str) -> Option<VariableScope> {
+        if self.object.contains_key(name) {
+            Some(VariableScope::Object)
+        } else if self.global.contains_key(name) {
+            Some(VariableScope::Global)
+        } else {
+            None
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&str, VariableScope, &T)> {
+        let global = self
+            .global
+            .iter()
+            .map(|(name, value)| (name.as_str(), VariableScope::Global, value));
+        let object = self
+            .object
+            .iter()
+            .map(|(name, value)| (name.as_str(), VariableScope::Object, value));
+        global.chain(object)
+    }
+
+    fn insert(&mut self, name: &str, scope: VariableScope, value: T) -> Result<(), VariableError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(VariableError::EmptyName);
+        }
+
+        if self.scope(name).is_some() {
+            return Err(VariableError::Exists(name.to_string()));
+        }
+
+        self.scope_mut(scope).insert(name.to_string(), value);
+        Ok(())
+    }
+
+    fn rename(&mut self, name: &str, new_name: &str) -> Result<(), VariableError> {
+        let scope = self
+            .scope(name)
+            .ok_or_else(|| VariableError::Unknown(name.to_string()))?;
+        let new_name = new_name.trim();
+        if new_name == name {
+            return Ok(());
+        }
+
+        if new_name.is_empty() {
+            return Err(VariableError::EmptyName);
+        }
+
+        if self.scope(new_name).is_some() {
+            return Err(VariableError::Exists(new_name.to_string()));
+        }
+
+        let entries = self.scope_mut(scope);
+        let value = entries.remove(name).unwrap();
+        entries.insert(new_name.to_string(), value);
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) -> Result<T, VariableError> {
+        let scope = self
+            .scope(name)
+            .ok_or_else(|| VariableError::Unknown(name.to_string()))?;
+        Ok(self.scope_mut(scope).remove(name).unwrap())
+    }
+
+    fn scope_mut(&mut self, scope: VariableScope) -> &mut BTreeMap<String, T> {
+        match scope {
+            VariableScope::Global => &mut self.global,
+            VariableScope::Object => &mut self.object,
+        }
+    }
+}
+
+/// Names and current values of the variables and lists.
+#[derive(Clone, Debug, Default)]
+pub struct Variables {
+    variables: Table<Value>,
+    lists: Table<Vec<Value>>,
+}
+
+impl Variables {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.variables.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Value> {
+        self.variables.get_mut(name)
+    }
+
+    /// Sets the value of an existing variable.
+    pub fn set(&mut self, name: &str, value: Value) -> Result<(), VariableError> {
+        let variable = self
+            .get_mut(name)
+            .ok_or_else(|| VariableError::Unknown(name.to_string()))?;
+        *variable = value;
+        Ok(())
+    }
+
+    pub fn scope(&self, name: &str) -> Option<VariableScope> {
+        self.variables.scope(name)
+    }
+
+    /// global variables first, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, VariableScope, &Value)> {
+        self.variables.iter()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(name, _scope, _value)| name)
+    }
+
+    /// Creates a variable with the value 0.
+    pub fn create(&mut self, name: &str, scope: VariableScope) -> Result<(), VariableError> {
+        self.insert(name, scope, Value::Number(0.0))
+    }
+
+    pub fn insert(
+        &mut self,
+        name: &str,
+        scope: VariableScope,
+        value: Value,
+    ) -> Result<(), VariableError> {
+        self.variables.insert(name, scope, value)
+    }
+
+    pub fn list(&self, name: &str) -> Option<&Vec<Value>> {
+        self.lists.get(name)
+    }
+
+    pub fn list_mut(&mut self, name: &str) -> Option<&mut Vec<Value>> {
+        self.lists.get_mut(name)
+    }
+
+    pub fn list_scope(&self, name: &str) -> Option<VariableScope> {
+        self.lists.scope(name)
+    }
+
+    /// global lists first, sorted by name
+    pub fn lists(&self) -> impl Iterator<Item = (&str, VariableScope, &Vec<Value>)> {
+        self.lists.iter()
+    }
+
+    pub fn list_names(&self) -> impl Iterator<Item = &str> {
+        self.lists().map(|(name, _scope, _items)| name)
+    }
+
+    /// Creates an empty list.
+    pub fn create_list(&mut self, name: &str, scope: VariableScope) -> Result<(), VariableError> {
+        self.insert_list(name, scope, Vec::new())
+    }
+
+    pub fn insert_list(
+        &mut self,
+        name: &str,
+        scope: VariableScope,
+        items: Vec<Value>,
+    ) -> Result<(), VariableError> {
+        self.lists.insert(name, scope, items)
+    }
+
+    /// Only renames the variable or list, use
+    /// [`BlockEditor::rename_variable`](crate::block::BlockEditor::rename_variable) to also
+    /// update the blocks using it.
+    pub(crate) fn rename(
+        &mut self,
+        kind: VariableKind,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), VariableError> {
+        match kind {
+            VariableKind::Variable => self.variables.rename(name, new_name),
+            VariableKind::List => self.lists.rename(name, new_name),
+        }
+    }
+
+    pub(crate) fn remove(&mut self, kind: VariableKind, name: &str) -> Result<(), VariableError> {
+        match kind {
+            VariableKind::Variable => self.variables.remove(name).map(drop),
+            VariableKind::List => self.lists.remove(name).map(drop),
+        }
+    }
+}
+
+/// Index of the list blocks, see [`list_index`].
+pub enum ListIndex {
+    /// 0-based
+    Item(usize),
+    All,
+    Invalid,
+}
+
+/// Resolves the index of a list block, which is 1-based or `"last"` or `"all"`. `length` is
+/// the highest valid index.
+pub fn list_index(index: &Value, length: usize) -> ListIndex {
+    match index.to_text().as_ref() {
+        "all" => return ListIndex::All,
+        "last" if length > 0 => return ListIndex::Item(length - 1),
+        _ => (),
+    }
+
+    let index = index.to_number().floor();
+    if index < 1.0 || index > length as f64 {
+        ListIndex::Invalid
+    } else {
+        ListIndex::Item(index as usize - 1)
+    }
+}
+
+/// Runs the variable and list blocks, `None` if `opcode` isn't one of them.
+pub(crate) fn evaluate(
+    opcode: &str,
+    fields: &BlockFields,
+    variables: &mut Variables,
+) -> Option<Value> {
+    Some(match opcode {
+        SET_VARIABLE => {
+            if let Some(variable) = variables.get_mut(&fields.text("variable")) {
+                *variable = fields.value("value").clone();
+            }
+
+            Value::default()
+        }
+        CHANGE_VARIABLE => {
+            if let Some(variable) = variables.get_mut(&fields.text("variable")) {
+                *variable = Value::Number(variable.to_number() + fields.number("value"));
+            }
+
+            Value::default()
+        }
+        VARIABLE => variables
+            .get(&fields.text("variable"))
+            .cloned()
+            .unwrap_or_default(),
+        _ if LIST_BLOCKS.contains(&opcode) => {
+            // lists which don't exist behave like empty ones
+            let mut empty = Vec::new();
+            let list = variables
+                .list_mut(&fields.text("list"))
+                .unwrap_or(&mut empty);
+            let value = |key| fields.values.get(key).cloned().unwrap_or_default();
+            evaluate_list(opcode, list, value("item"), &value("index"))
+        }
+        _ => return None,
+    })
+}
+
+/// Runs a list block, `item` and `index` are the values of its widgets if it has them.
+pub(crate) fn evaluate_list(
+    opcode: &str,
+    list: &mut Vec<Value>,
+    item: Value,
+    index: &Value,
+) -> Value {
+    let index = |length| list_index(index, length);
+
+    match opcode {
+        ADD_TO_LIST => {
+            list.push(item);
+            Value::default()
+        }
+        DELETE_OF_LIST => {
+            match index(list.len()) {
+                ListIndex::Item(index) => {
+                    list.remove(index);
+                }
+                ListIndex::All => list.clear(),
+                ListIndex::Invalid => (),
+            }
+
+            Value::default()
+        }
+        DELETE_ALL_OF_LIST => {
+            list.clear();
+            Value::default()
+        }
+        INSERT_AT_LIST => {
+            // one past the end appends
+            if let ListIndex::Item(index) = index(list.len() + 1) {
+                list.insert(index, item);
+            }
+
+            Value::default()
+        }
+        REPLACE_ITEM_OF_LIST => {
+            if let ListIndex::Item(index) = index(list.len()) {
+                list[index] = item;
+            }
+
+            Value::default()
+        }
+        ITEM_OF_LIST => match index(list.len()) {
+            ListIndex::Item(index) => list[index].clone(),
+            _ => Value::default(),
+        },
+        ITEM_NUM_OF_LIST => {
+            let position = list.iter().position(|other| other.equals(&item));
+            Value::Number(position.map_or(0, |position| position + 1) as f64)
+        }
+        LENGTH_OF_LIST => Value::Number(list.len() as f64),
+        LIST_CONTAINS_ITEM => Value::Bool(list.iter().any(|other| other.equals(&item))),
+        LIST_CONTENTS => Value::List(list.clone()),
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_setvariableto",
+    category = "variables",
+    label = "set {variable} to {value}",
+    rust = "%{variable} = %{value};"
+)]
+pub struct SetVariable {
+    #[block(variable)]
+    pub variable: String,
+    #[block(default = "0")]
+    pub value: Value,
+}
+
+// run by the editor, which owns the variables
+impl DerivedBlock for SetVariable {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_changevariableby",
+    category = "variables",
+    label = "change {variable} by {value}",
+    rust = "%{variable} = Value::from(%{variable}.to_number() + %{value:number});"
+)]
+pub struct ChangeVariable {
+    #[block(variable)]
+    pub variable: String,
+    #[block(default = 1.0)]
+    pub value: f64,
+}
+
+impl DerivedBlock for ChangeVariable {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_variable",
+    shape = "reporter",
+    category = "variables",
+    label = "{variable}",
+    rust = "%{variable}.clone()"
+)]
+pub struct VariableReporter {
+    #[block(variable)]
+    pub variable: String,
+}
+
+impl DerivedBlock for VariableReporter {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_addtolist",
+    category = "lists",
+    label = "add {item} to {list}",
+    rust = "%{list}.push(%{item});"
+)]
+pub struct AddToList {
+    #[block(default = "thing")]
+    pub item: Value,
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for AddToList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_deleteoflist",
+    category = "lists",
+    label = "delete {index} of {list}",
+    rust = "match variable::list_index(&%{index}, %{list}.len()) {\n    \
+                ListIndex::Item(index) => {\n        \
+                    %{list}.remove(index);\n    \
+                }\n    \
+                ListIndex::All => %{list}.clear(),\n    \
+                ListIndex::Invalid => (),\n\
+            }"
+)]
+pub struct DeleteOfList {
+    #[block(default = "1")]
+    pub index: Value,
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for DeleteOfList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_deletealloflist",
+    category = "lists",
+    label = "delete all of {list}",
+    rust = "%{list}.clear();"
+)]
+pub struct DeleteAllOfList {
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for DeleteAllOfList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_insertatlist",
+    category = "lists",
+    label = "insert {item} at {index} of {list}",
+    rust = "{\n    \
+                let (item, index) = (%{item}, %{index});\n    \
+                if let ListIndex::Item(index) = variable::list_index(&index, %{list}.len() + 1) {\n        \
+                    %{list}.insert(index, item);\n    \
+                }\n\
+            }"
+)]
+pub struct InsertAtList {
+    #[block(default = "thing")]
+    pub item: Value,
+    #[block(default = "1")]
+    pub index: Value,
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for InsertAtList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_replaceitemoflist",
+    category = "lists",
+    label = "replace item {index} of {list} with {item}",
+    rust = "{\n    \
+                let (index, item) = (%{index}, %{item});\n    \
+                if let ListIndex::Item(index) = variable::list_index(&index, %{list}.len()) {\n        \
+                    %{list}[index] = item;\n    \
+                }\n\
+            }"
+)]
+pub struct ReplaceItemOfList {
+    #[block(default = "1")]
+    pub index: Value,
+    #[block(list)]
+    pub list: String,
+    #[block(default = "thing")]
+    pub item: Value,
+}
+
+impl DerivedBlock for ReplaceItemOfList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_itemoflist",
+    shape = "reporter",
+    category = "lists",
+    label = "item {index} of {list}",
+    rust = "match variable::list_index(&%{index}, %{list}.len()) { \
+            ListIndex::Item(index) => %{list}[index].clone(), _ => Value::default() }"
+)]
+pub struct ItemOfList {
+    #[block(default = "1")]
+    pub index: Value,
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for ItemOfList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_itemnumoflist",
+    shape = "reporter",
+    category = "lists",
+    output = "number",
+    label = "item # of {item} in {list}",
+    rust = "{ let item = %{item}; Value::from(%{list}.iter().position(|other| other.equals(&item))\
+            .map_or(0.0, |position| position as f64 + 1.0)) }"
+)]
+pub struct ItemNumOfList {
+    #[block(default = "thing")]
+    pub item: Value,
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for ItemNumOfList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_lengthoflist",
+    shape = "reporter",
+    category = "lists",
+    output = "number",
+    label = "length of {list}",
+    rust = "Value::from(%{list}.len() as f64)"
+)]
+pub struct LengthOfList {
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for LengthOfList {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_listcontainsitem",
+    shape = "boolean",
+    category = "lists",
+    label = "{list} contains {item} ?",
+    rust = "{ let item = %{item}; Value::from(%{list}.iter().any(|other| other.equals(&item))) }"
+)]
+pub struct ListContainsItem {
+    #[block(list)]
+    pub list: String,
+    #[block(default = "thing")]
+    pub item: Value,
+}
+
+impl DerivedBlock for ListContainsItem {}
+
+#[derive(Block, Default)]
+#[block(
+    opcode = "data_listcontents",
+    shape = "reporter",
+    category = "lists",
+    output = "list",
+    label = "{list}",
+    rust = "Value::List(%{list}.clone())"
+)]
+pub struct ListContents {
+    #[block(list)]
+    pub list: String,
+}
+
+impl DerivedBlock for ListContents {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockEditor,
+        event::WHEN_GAME_STARTS,
+        save::tests::{number, text, Workspace},
+    };
+
+    #[test]
+    fn names() {
+        let mut variables = Variables::default();
+        assert!(variables.create(" x ", VariableScope::Global).is_ok());
+        assert!(matches!(variables.get("x"), Some(Value::Number(0.0))));
+        assert_eq!(
+            variables.create("x", VariableScope::Object),
+            Err(VariableError::Exists("x".to_string()))
+        );
+        assert_eq!(
+            variables.create("  ", VariableScope::Object),
+            Err(VariableError::EmptyName)
+        );
+        // lists are kept apart from variables
+        assert!(variables.create_list("x", VariableScope::Object).is_ok());
+
+        assert!(variables.create("y", VariableScope::Object).is_ok());
+        assert_eq!(
+            variables.rename(VariableKind::Variable, "y", "x"),
+            Err(VariableError::Exists("x".to_string()))
+        );
+        assert!(variables.rename(VariableKind::Variable, "y", "z").is_ok());
+        assert_eq!(variables.scope("z"), Some(VariableScope::Object));
+        assert_eq!(variables.names().collect::<Vec<_>>(), ["x", "z"]);
+
+        assert!(variables.set("z", Value::from("a")).is_ok());
+        assert_eq!(
+            variables.set("y", Value::from("a")),
+            Err(VariableError::Unknown("y".to_string()))
+        );
+        assert!(variables.remove(VariableKind::Variable, "z").is_ok());
+        assert!(variables.get("z").is_none());
+        assert_eq!(variables.list_scope("x"), Some(VariableScope::Object));
+    }
+
+    #[test]
+    fn rename_and_delete() {
+        let mut workspace = Workspace::default();
+        workspace.variable("x", number(0.0));
+        workspace.variable("other", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("other"))]);
+        let reporter = workspace.block(VARIABLE, &[("variable", text("x"))]);
+        workspace.input(set, "value", reporter);
+        let change = workspace.block(CHANGE_VARIABLE, &[("variable", text("x"))]);
+        workspace.stack(&[hat, set, change]);
+
+        let mut editor = workspace.load();
+        let indices: Vec<_> = editor.blocks.iter().map(|(index, _block)| index).collect();
+        assert!(editor.rename_variable("x", "y").is_ok());
+        let variable = |editor: &BlockEditor, block: usize| {
+            editor.blocks[indices[block]].values["variable"]
+                .to_text()
+                .into_owned()
+        };
+        assert_eq!(variable(&editor, reporter), "y");
+        assert_eq!(variable(&editor, change), "y");
+        assert_eq!(variable(&editor, set), "other");
+
+        // blocks using the variable are deleted with it
+        assert!(editor.delete_variable("y").is_ok());
+        assert!(editor.blocks.get(indices[reporter]).is_none());
+        assert!(editor.blocks.get(indices[change]).is_none());
+        assert!(editor.blocks.get(indices[set]).is_some());
+        assert_eq!(
+            editor.delete_variable("y"),
+            Err(VariableError::Unknown("y".to_string()))
+        );
+    }
+
+    #[test]
+    fn list_indices() {
+        let index = |index: Value, length| match list_index(&index, length) {
+            ListIndex::Item(index) => Some(index),
+            ListIndex::All => None,
+            ListIndex::Invalid => panic!("{index:?} is invalid"),
+        };
+        let invalid =
+            |index: Value, length| matches!(list_index(&index, length), ListIndex::Invalid);
+
+        assert_eq!(index(Value::from(1.0), 3), Some(0));
+        assert_eq!(index(Value::from("3"), 3), Some(2));
+        assert_eq!(index(Value::from(2.9), 3), Some(1));
+        assert_eq!(index(Value::from("last"), 3), Some(2));
+        assert_eq!(index(Value::from("all"), 3), None);
+        assert_eq!(index(Value::from("all"), 0), None);
+        assert!(invalid(Value::from("last"), 0));
+        assert!(invalid(Value::from(0.0), 3));
+        assert!(invalid(Value::from(-1.0), 3));
+        assert!(invalid(Value::from(4.0), 3));
+        assert!(invalid(Value::from("first"), 3));
+    }
+
+    #[test]
+    fn list_blocks() {
+        let mut list: Vec<Value> = vec!["a".into(), "b".into()];
+        let mut run = |opcode, item: &str, index: Value| {
+            evaluate_list(opcode, &mut list, item.into(), &index)
+                .to_text()
+                .into_owned()
+        };
+
+        // one past the end appends, further ones are ignored
+        run(INSERT_AT_LIST, "c", 3.0.into());
+        run(INSERT_AT_LIST, "x", 5.0.into());
+        // "last" also appends when inserting, like in Scratch
+        run(INSERT_AT_LIST, "d", "last".into());
+        run(REPLACE_ITEM_OF_LIST, "x", 0.0.into());
+        run(REPLACE_ITEM_OF_LIST, "e", 1.0.into());
+        run(DELETE_OF_LIST, "", 10.0.into());
+        assert_eq!(run(ITEM_OF_LIST, "", "last".into()), "d");
+        assert_eq!(run(ITEM_OF_LIST, "", 0.0.into()), "");
+        assert_eq!(run(ITEM_NUM_OF_LIST, "D", Value::default()), "4");
+        assert_eq!(run(ITEM_NUM_OF_LIST, "x", Value::default()), "0");
+        assert_eq!(run(LIST_CONTENTS, "", Value::default()), "ebcd");
+
+        run(DELETE_OF_LIST, "", "all".into());
+        assert_eq!(run(LENGTH_OF_LIST, "", Value::default()), "0");
+    }
+}
@@ -0,0 +1,1150 @@
+//! Runs a compiled [`Program`] on a stack machine, scheduled like [`BlockEditor::tick`].
+
+use crate::{
+    block::{BlockEditor, BlockFields, RunError},
+    compiler::{Instruction, Program, StopOption},
+    event::Event,
+    interpreter::{Flow, ScriptError, ThreadId},
+    limits::{LimitError, Limits},
+    random::Random,
+    scheduler::{self, Schedule, Scheduled, Scheduler},
+    value::Value,
+    variable,
+};
+use std::{cell::RefCell, collections::HashMap, time::Instant};
+use thunderdome::Index;
+
+struct CallFrame {
+    /// the call block
+    call: Index,
+    return_to: usize,
+    arguments: Vec<Value>,
+    /// height of the operand stack when the procedure was called, the loop counters it left
+    /// behind are dropped when it returns
+    base: usize,
+}
+
+struct VmThread {
+    id: ThreadId,
+    /// one of [`Program::scripts`]
+    script: usize,
+    /// next instruction, `None` once the thread is finished
+    pc: Option<usize>,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    schedule: Schedule,
+    /// breakpoint the thread paused at, which doesn't pause it again when it continues
+    resume_past: Option<Index>,
+}
+
+impl VmThread {
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap()
+    }
+}
+
+impl Scheduled for VmThread {
+    type Script = usize;
+
+    fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    fn script(&self) -> usize {
+        self.script
+    }
+
+    fn is_finished(&self) -> bool {
+        self.pc.is_none()
+    }
+
+    fn stop(&mut self) {
+        self.pc = None;
+    }
+
+    fn schedule(&mut self) -> &mut Schedule {
+        &mut self.schedule
+    }
+}
+
+/// Keeps its own copy of the variables and lists, [`Vm::store_variables`] copies them back into
+/// an editor. The limits, breakpoints and recording of the editor apply, but threads can't be
+/// stepped through and recorded blocks have no values.
+pub struct Vm {
+    program: Program,
+    variables: Vec<Value>,
+    lists: Vec<Vec<Value>>,
+    threads: Vec<VmThread>,
+    next_thread: u64,
+    errors: Vec<ScriptError>,
+}
+
+impl Vm {
+    /// A VM running `program` with the current variables and lists of `editor`.
+    pub fn new(program: Program, editor: &BlockEditor) -> Self {
+        let variables = program
+            .variables
+            .iter()
+            .map(|name| editor.variables.get(name).cloned().unwrap_or_default())
+            .collect();
+        let lists = program
+            .lists
+            .iter()
+            .map(|name| editor.variables.list(name).cloned().unwrap_or_default())
+            .collect();
+
+        Vm {
+            program,
+            variables,
+            lists,
+            threads: Vec::new(),
+            next_thread: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        let slot = self
+            .program
+            .variables
+            .iter()
+            .position(|other| other == name)?;
+        Some(&self.variables[slot])
+    }
+
+    pub fn list(&self, name: &str) -> Option<&Vec<Value>> {
+        let slot = self.program.lists.iter().position(|other| other == name)?;
+        Some(&self.lists[slot])
+    }
+
+    /// Copies the variables and lists into the editor the program was compiled from.
+    pub fn store_variables(&self, editor: &mut BlockEditor) {
+        for (name, value) in self.program.variables.iter().zip(&self.variables) {
+            if let Some(variable) = editor.variables.get_mut(name) {
+                *variable = value.clone();
+            }
+        }
+
+        for (name, items) in self.program.lists.iter().zip(&self.lists) {
+            if let Some(list) = editor.variables.list_mut(name) {
+                list.clone_from(items);
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.threads.is_empty()
+    }
+
+    /// See [`BlockEditor::errors`].
+    pub fn errors(&self) -> &[ScriptError] {
+        &self.errors
+    }
+
+    /// Starts the scripts below the hats matching `event`, like [`BlockEditor::emit`].
+    pub fn emit(&mut self, event: Event) -> Vec<ThreadId> {
+        // hats don't pick random numbers
+        let random = RefCell::new(Random::default());
+        let scripts: Vec<usize> = self
+            .program
+            .scripts
+            .iter()
+            .enumerate()
+            .filter(|(_i, script)| {
+                let fields = BlockFields {
+                    values: &script.values,
+                    random: &random,
+                };
+                event.triggers(&script.opcode, &fields)
+            })
+            .map(|(i, _script)| i)
+            .collect();
+
+        scripts
+            .into_iter()
+            .map(|script| self.start_script(script))
+            .collect()
+    }
+
+    fn start_script(&mut self, script: usize) -> ThreadId {
+        let id = ThreadId(self.next_thread);
+        self.next_thread += 1;
+        self.threads.retain(|thread| thread.script != script);
+        self.threads.push(VmThread {
+            id,
+            script,
+            pc: Some(self.program.scripts[script].start),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            schedule: Schedule::default(),
+            resume_past: None,
+        });
+        let hat = self.program.scripts[script].hat;
+        self.errors.retain(|error| error.script != hat);
+        id
+    }
+
+    pub fn stop_all(&mut self) {
+        self.threads.clear();
+    }
+
+    /// Like [`BlockEditor::tick`], `editor` runs the blocks which aren't built in.
+    pub fn tick(&mut self, editor: &mut BlockEditor) {
+        scheduler::tick(&mut Turns { vm: self, editor });
+    }
+
+    fn run_turn(&mut self, thread: &mut VmThread, editor: &mut BlockEditor) {
+        while let Some(pc) = thread.pc {
+            if thread.schedule.is_waiting() || editor.debugger.paused {
+                break;
+            }
+
+            match self.execute(thread, pc, editor) {
+                Ok(Flow::Continue) => (),
+                Ok(Flow::Yield) => break,
+                Err(error) => {
+                    thread.pc = None;
+                    self.errors.push(error);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn error(&self, thread: &VmThread, pc: usize, error: RunError) -> ScriptError {
+        ScriptError {
+            block: self.program.origins[pc],
+            script: self.program.scripts[thread.script].hat,
+            thread: thread.id,
+            call_stack: thread.frames.iter().map(|frame| frame.call).collect(),
+            error,
+        }
+    }
+
+    /// Runs the instruction at `pc`.
+    fn execute(
+        &mut self,
+        thread: &mut VmThread,
+        pc: usize,
+        editor: &mut BlockEditor,
+    ) -> Result<Flow, ScriptError> {
+        let jump = |offset: i32| Some((pc as isize + offset as isize) as usize);
+        let limits = editor.limits;
+        thread.pc = Some(pc + 1);
+        match self.program.code[pc] {
+            Instruction::Constant(constant) => {
+                let value = self.program.constants[constant as usize].clone();
+                thread.stack.push(value);
+            }
+            Instruction::Pop => {
+                thread.pop();
+            }
+            Instruction::Variable(slot) => {
+                let value = self.variables[slot as usize].clone();
+                thread.stack.push(value);
+            }
+            Instruction::SetVariable(slot) => {
+                let value = thread.pop();
+                self.check_length(thread, pc, &limits, &value)?;
+                self.variables[slot as usize] = value;
+            }
+            Instruction::ChangeVariable(slot) => {
+                let variable = &mut self.variables[slot as usize];
+                *variable = Value::Number(variable.to_number() + thread.pop().to_number());
+            }
+            Instruction::List {
+                opcode,
+                list,
+                index_first,
+            } => {
+                let (mut item, mut index) = (thread.pop(), thread.pop());
+                if !index_first {
+                    std::mem::swap(&mut item, &mut index);
+                }
+
+                // lists which don't exist behave like empty ones
+                let mut empty = Vec::new();
+                let list = match list {
+                    Some(slot) => &mut self.lists[slot as usize],
+                    None => &mut empty,
+                };
+                let value = variable::evaluate_list(opcode, list, item, &index);
+                if limits.max_length.is_some_and(|max| list.len() > max) {
+                    return Err(self.length_error(thread, pc, &limits));
+                }
+
+                self.check_length(thread, pc, &limits, &value)?;
+                thread.stack.push(value);
+            }
+            Instruction::Argument(argument) => {
+                let value = thread.frames.last().map_or(Value::Number(0.0), |frame| {
+                    frame.arguments[argument as usize].clone()
+                });
+                thread.stack.push(value);
+            }
+            Instruction::Operator { operator, b_first } => {
+                let (mut a, mut b) = (thread.pop(), thread.pop());
+                if !b_first {
+                    std::mem::swap(&mut a, &mut b);
+                }
+
+                let value = operator.apply(&a, &b);
+                self.check_length(thread, pc, &limits, &value)?;
+                thread.stack.push(value);
+            }
+            Instruction::Not => {
+                let value = !thread.pop().to_bool();
+                thread.stack.push(value.into());
+            }
+            Instruction::Jump(offset) => thread.pc = jump(offset),
+            Instruction::JumpIfFalse(offset) => {
+                if !thread.pop().to_bool() {
+                    thread.pc = jump(offset);
+                }
+            }
+            Instruction::JumpIfTrue(offset) => {
+                if thread.pop().to_bool() {
+                    thread.pc = jump(offset);
+                }
+            }
+            Instruction::RepeatStart(offset) => {
+                let times = thread.pop().to_number().round();
+                if times >= 1.0 {
+                    thread.stack.push(Value::Number(times));
+                } else {
+                    thread.pc = jump(offset);
+                }
+            }
+            Instruction::RepeatNext(offset) => {
+                let times = thread.pop().to_number() - 1.0;
+                if times >= 1.0 {
+                    self.spend(thread, pc, &limits)?;
+                    thread.stack.push(Value::Number(times));
+                    thread.pc = jump(offset);
+                    return Ok(Flow::Yield);
+                }
+            }
+            Instruction::Yield => {
+                self.spend(thread, pc, &limits)?;
+                return Ok(Flow::Yield);
+            }
+            Instruction::Step => {
+                let index = self.program.origins[pc];
+                if editor.debugger.breakpoints.contains(&index) && thread.resume_past != Some(index)
+                {
+                    thread.pc = Some(pc);
+                    thread.resume_past = Some(index);
+                    editor.debugger.paused = true;
+                    editor.debugger.thread = Some(thread.id);
+                    return Ok(Flow::Yield);
+                }
+
+                thread.resume_past = None;
+                if thread.schedule.budget.is_spent(&limits) {
+                    thread.pc = Some(pc);
+                    return Ok(Flow::Yield);
+                }
+
+                self.spend(thread, pc, &limits)?;
+                editor.evaluated.insert(index, Instant::now());
+                editor.record(thread.id, index, &HashMap::new(), None);
+            }
+            Instruction::Spend => {
+                let index = self.program.origins[pc];
+                self.spend(thread, pc, &limits)?;
+                editor.evaluated.insert(index, Instant::now());
+                editor.record(thread.id, index, &HashMap::new(), None);
+            }
+            Instruction::Wait => {
+                let seconds = thread.pop().to_number();
+                thread.schedule.sleep(seconds);
+                return Ok(Flow::Yield);
+            }
+            Instruction::Stop(option) => match option {
+                StopOption::All => {
+                    thread.pc = None;
+                    thread.schedule.stop_others = true;
+                    self.threads.clear();
+                }
+                StopOption::OtherScripts => {
+                    thread.schedule.stop_others = true;
+                    self.threads.clear();
+                }
+                // returns from the innermost procedure, like in Scratch
+                StopOption::ThisScript => return Ok(self.return_from(thread)),
+            },
+            Instruction::Broadcast => {
+                let message = thread.pop().to_text().into_owned();
+                self.emit(Event::Message(message));
+            }
+            Instruction::BroadcastAndWait => {
+                let message = thread.pop().to_text().into_owned();
+                thread.schedule.waiting = self.emit(Event::Message(message));
+            }
+            Instruction::Call(procedure) => {
+                if let Some(max) = limits.max_depth {
+                    if thread.frames.len() >= max {
+                        let error = LimitError::Depth(max).to_string();
+                        return Err(self.error(thread, pc, error.into()));
+                    }
+                }
+
+                let procedure = &self.program.procedures[procedure as usize];
+                let arguments = thread
+                    .stack
+                    .split_off(thread.stack.len() - procedure.arguments);
+                thread.frames.push(CallFrame {
+                    call: self.program.origins[pc],
+                    return_to: pc + 1,
+                    arguments,
+                    base: thread.stack.len(),
+                });
+                thread.pc = Some(procedure.start);
+            }
+            Instruction::Return => return Ok(self.return_from(thread)),
+            Instruction::End => thread.pc = None,
+            Instruction::Run(block) | Instruction::Report(block) => {
+                let compiled = &self.program.blocks[block as usize];
+                let mut values = compiled.values.clone();
+                for key in compiled.inputs.iter().rev() {
+                    values.insert(key.clone(), thread.pop());
+                }
+
+                // removed since the program was compiled, reporters still report something
+                let Some(instance) = editor.blocks.get_mut(compiled.index) else {
+                    if let Instruction::Report(_) = self.program.code[pc] {
+                        thread.stack.push(Value::default());
+                    }
+
+                    return Ok(Flow::Continue);
+                };
+
+                let fields = BlockFields {
+                    values: &values,
+                    random: &editor.random,
+                };
+                if let Instruction::Report(_) = self.program.code[pc] {
+                    let value = instance
+                        .implementation
+                        .report(&fields)
+                        .map_err(|error| self.error(thread, pc, error))?;
+                    self.check_length(thread, pc, &limits, &value)?;
+                    thread.stack.push(value);
+                } else {
+                    instance
+                        .implementation
+                        .run(&fields)
+                        .map_err(|error| self.error(thread, pc, error))?;
+                }
+            }
+            Instruction::Fail(constant) => {
+                let message = self.program.constants[constant as usize].to_text();
+                return Err(self.error(thread, pc, RunError::new(message)));
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Counts the block of the instruction at `pc` against the instruction limits.
+    fn spend(&self, thread: &mut VmThread, pc: usize, limits: &Limits) -> Result<(), ScriptError> {
+        thread
+            .schedule
+            .budget
+            .spend(limits)
+            .map_err(|error| self.error(thread, pc, error.to_string().into()))
+    }
+
+    fn check_length(
+        &self,
+        thread: &VmThread,
+        pc: usize,
+        limits: &Limits,
+        value: &Value,
+    ) -> Result<(), ScriptError> {
+        if limits.too_long(value) {
+            return Err(self.length_error(thread, pc, limits));
+        }
+
+        Ok(())
+    }
+
+    fn length_error(&self, thread: &VmThread, pc: usize, limits: &Limits) -> ScriptError {
+        let error = LimitError::Length(limits.max_length.unwrap()).to_string();
+        self.error(thread, pc, error.into())
+    }
+
+    /// Continues after the innermost procedure call, finishes the thread outside of procedures.
+    fn return_from(&self, thread: &mut VmThread) -> Flow {
+        match thread.frames.pop() {
+            Some(frame) => {
+                thread.stack.truncate(frame.base);
+                thread.pc = Some(frame.return_to);
+            }
+            None => {
+                thread.stack.clear();
+                thread.pc = None;
+            }
+        }
+
+        Flow::Continue
+    }
+}
+
+/// A tick of the VM, with the editor running the blocks which aren't built in.
+struct Turns<'a> {
+    vm: &'a mut Vm,
+    editor: &'a mut BlockEditor,
+}
+
+impl Scheduler for Turns<'_> {
+    type Thread = VmThread;
+
+    fn threads(&mut self) -> &mut Vec<VmThread> {
+        &mut self.vm.threads
+    }
+
+    fn limits(&self) -> &Limits {
+        &self.editor.limits
+    }
+
+    fn is_paused(&self) -> bool {
+        self.editor.debugger.paused
+    }
+
+    fn run_turn(&mut self, thread: &mut VmThread) {
+        self.vm.run_turn(thread, self.editor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::{BROADCAST_AND_WAIT, WHEN_GAME_STARTS, WHEN_I_RECEIVE},
+        limits::Limits,
+        procedure::{BOOLEAN_PARAMETER, CALL, DEFINITION, PARAMETER},
+        save::{
+            tests::{number, text, Workspace},
+            SavedValue,
+        },
+        standard::{FOREVER, IF, IF_ELSE, NOT, REPEAT, REPEAT_UNTIL, STOP, STOP_THIS_SCRIPT, WAIT},
+        variable::{
+            ADD_TO_LIST, CHANGE_VARIABLE, DELETE_OF_LIST, INSERT_AT_LIST, ITEM_NUM_OF_LIST,
+            ITEM_OF_LIST, LENGTH_OF_LIST, LIST_CONTAINS_ITEM, LIST_CONTENTS, REPLACE_ITEM_OF_LIST,
+            SET_VARIABLE, VARIABLE,
+        },
+    };
+
+    /// ticks after which a workspace is considered to hang
+    const MAX_TICKS: usize = 1000;
+
+    /// Variables, lists and errors once every thread finished.
+    #[derive(Debug, PartialEq)]
+    struct Outcome {
+        variables: Vec<String>,
+        errors: Vec<String>,
+    }
+
+    impl Outcome {
+        fn new(editor: &BlockEditor, errors: &[ScriptError]) -> Self {
+            let variables = editor
+                .variables
+                .iter()
+                .map(|(name, _scope, value)| format!("{name} = {value:?}"))
+                .chain(
+                    editor
+                        .variables
+                        .lists()
+                        .map(|(name, _scope, items)| format!("{name} = {items:?}")),
+                )
+                .collect();
+            let errors = errors
+                .iter()
+                .map(|error| {
+                    let opcode = editor.blocks[error.block].implementation.opcode();
+                    format!("{opcode}: {error}")
+                })
+                .collect();
+            Outcome { variables, errors }
+        }
+    }
+
+    fn interpret(workspace: &Workspace, limits: Limits) -> Outcome {
+        let mut editor = workspace.load();
+        editor.set_limits(limits);
+        editor.emit(Event::GameStarts);
+        for _ in 0..MAX_TICKS {
+            if editor.threads().is_empty() {
+                let errors = editor.errors().to_vec();
+                return Outcome::new(&editor, &errors);
+            }
+
+            editor.tick();
+        }
+
+        panic!("the interpreter didn't finish");
+    }
+
+    fn run_vm(workspace: &Workspace, limits: Limits) -> Outcome {
+        let mut editor = workspace.load();
+        editor.set_limits(limits);
+        let mut vm = Vm::new(editor.compile(), &editor);
+        vm.emit(Event::GameStarts);
+        for _ in 0..MAX_TICKS {
+            if !vm.is_running() {
+                vm.store_variables(&mut editor);
+                return Outcome::new(&editor, vm.errors());
+            }
+
+            vm.tick(&mut editor);
+        }
+
+        panic!("the VM didn't finish");
+    }
+
+    /// Runs the workspace with both engines, which have to give the same outcome.
+    fn run(workspace: &Workspace, limits: Limits) -> Outcome {
+        let outcome = interpret(workspace, limits);
+        assert_eq!(outcome, run_vm(workspace, limits));
+        outcome
+    }
+
+    fn variable(workspace: &mut Workspace, name: &str) -> usize {
+        workspace.block(VARIABLE, &[("variable", text(name))])
+    }
+
+    fn operator(workspace: &mut Workspace, opcode: &str, a: usize, b: f64) -> usize {
+        let block = workspace.block(opcode, &[("b", number(b))]);
+        workspace.input(block, "a", a);
+        block
+    }
+
+    fn change(workspace: &mut Workspace, name: &str, by: f64) -> usize {
+        workspace.block(
+            CHANGE_VARIABLE,
+            &[("variable", text(name)), ("value", number(by))],
+        )
+    }
+
+    #[test]
+    fn loops() {
+        let mut workspace = Workspace::default();
+        workspace.variable("i", number(0.0));
+        workspace.variable("j", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let change_i = change(&mut workspace, "i", 1.0);
+        let change_j = change(&mut workspace, "j", 2.0);
+        let j = variable(&mut workspace, "j");
+        let until = operator(&mut workspace, "operator_gt", j, 10.0);
+        let repeat_until = workspace.c_block(REPEAT_UNTIL, &[], &[&[change_j]]);
+        workspace.input(repeat_until, "condition", until);
+        let repeat = workspace.c_block(
+            REPEAT,
+            &[("times", number(3.0))],
+            &[&[change_i, repeat_until]],
+        );
+        let stop = workspace.block(STOP, &[("option", text(STOP_THIS_SCRIPT))]);
+        let i = variable(&mut workspace, "i");
+        let done = operator(&mut workspace, "operator_gt", i, 20.0);
+        let check = workspace.c_block(IF, &[], &[&[stop]]);
+        workspace.input(check, "condition", done);
+        let change_i = change(&mut workspace, "i", 1.0);
+        let forever = workspace.c_block(FOREVER, &[], &[&[check, change_i]]);
+        workspace.stack(&[hat, repeat, forever]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(outcome.variables, ["i = Number(21.0)", "j = Number(12.0)"]);
+    }
+
+    #[test]
+    fn control() {
+        let mut workspace = Workspace::default();
+        for name in ["else", "if", "not", "repeat"] {
+            workspace.variable(name, number(0.0));
+        }
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        // an empty condition is false
+        let branches = [change(&mut workspace, "if", 1.0)];
+        let otherwise = [change(&mut workspace, "else", 1.0)];
+        let if_else = workspace.c_block(IF_ELSE, &[], &[&branches, &otherwise]);
+        // the number of repetitions is rounded
+        let body = change(&mut workspace, "repeat", 1.0);
+        let repeat = workspace.c_block(REPEAT, &[("times", number(2.5))], &[&[body]]);
+        let body = change(&mut workspace, "repeat", 100.0);
+        let never = workspace.c_block(REPEAT, &[("times", number(-1.0))], &[&[body]]);
+        let not = workspace.block(NOT, &[]);
+        let body = change(&mut workspace, "not", 1.0);
+        let check = workspace.c_block(IF, &[], &[&[body]]);
+        workspace.input(check, "condition", not);
+        workspace.stack(&[hat, if_else, repeat, never, check]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(
+            outcome.variables,
+            [
+                "else = Number(1.0)",
+                "if = Number(0.0)",
+                "not = Number(1.0)",
+                "repeat = Number(3.0)",
+            ]
+        );
+    }
+
+    /// What a reporter with the given fields reports, with both engines.
+    fn report(opcode: &str, fields: &[(&str, SavedValue)]) -> String {
+        let mut workspace = Workspace::default();
+        workspace.variable("result", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("result"))]);
+        let reporter = workspace.block(opcode, fields);
+        workspace.input(set, "value", reporter);
+        workspace.stack(&[hat, set]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(outcome.errors, Vec::<String>::new());
+        outcome.variables[0]["result = ".len()..].to_string()
+    }
+
+    fn numbers(a: f64, b: f64) -> [(&'static str, SavedValue); 2] {
+        [("a", number(a)), ("b", number(b))]
+    }
+
+    #[test]
+    fn operators() {
+        // the result has the sign of the divisor
+        assert_eq!(report("operator_mod", &numbers(7.0, 3.0)), "Number(1.0)");
+        assert_eq!(report("operator_mod", &numbers(-7.0, 3.0)), "Number(2.0)");
+        assert_eq!(report("operator_mod", &numbers(7.0, -3.0)), "Number(-2.0)");
+        assert_eq!(report("operator_mod", &numbers(7.0, 0.0)), "Number(NaN)");
+
+        assert_eq!(report("operator_divide", &numbers(1.0, 0.0)), "Number(inf)");
+        assert_eq!(
+            report("operator_divide", &numbers(-1.0, 0.0)),
+            "Number(-inf)"
+        );
+        assert_eq!(report("operator_divide", &numbers(0.0, 0.0)), "Number(NaN)");
+        assert_eq!(
+            report("operator_subtract", &numbers(0.5, 2.0)),
+            "Number(-1.5)"
+        );
+        assert_eq!(
+            report("operator_multiply", &numbers(-2.0, 3.0)),
+            "Number(-6.0)"
+        );
+
+        // halves are rounded up, also for negative numbers
+        let round = |value| report("operator_round", &[("value", number(value))]);
+        assert_eq!(round(2.5), "Number(3.0)");
+        assert_eq!(round(-2.5), "Number(-2.0)");
+        assert_eq!(round(-2.6), "Number(-3.0)");
+
+        let math = |operator, value| {
+            report(
+                "operator_mathop",
+                &[("operator", text(operator)), ("value", number(value))],
+            )
+        };
+        assert_eq!(math("sin", 180.0), "Number(0.0)");
+        assert_eq!(math("tan", -90.0), "Number(-inf)");
+        assert_eq!(math("sqrt", -1.0), "Number(NaN)");
+        assert_eq!(math("10 ^", 2.0), "Number(100.0)");
+
+        let random = [("from", number(3.0)), ("to", number(3.0))];
+        assert_eq!(report("operator_random", &random), "Number(3.0)");
+
+        let compare = [("a", text("10")), ("b", text("9"))];
+        assert_eq!(report("operator_lt", &compare), "Bool(false)");
+        let compare = [("a", text("ABC")), ("b", text("abc"))];
+        assert_eq!(report("operator_equals", &compare), "Bool(true)");
+    }
+
+    #[test]
+    fn text_operators() {
+        let join = [("a", text("1")), ("b", number(2.0))];
+        assert_eq!(report("operator_join", &join), r#"Text("12")"#);
+
+        // the first letter is 1, others are empty
+        let letter = |index| {
+            report(
+                "operator_letter_of",
+                &[("index", number(index)), ("text", text("héllo"))],
+            )
+        };
+        assert_eq!(letter(2.0), r#"Text("é")"#);
+        assert_eq!(letter(1.9), r#"Text("h")"#);
+        assert_eq!(letter(0.0), r#"Text("")"#);
+        assert_eq!(letter(6.0), r#"Text("")"#);
+
+        let length = [("text", text("héllo"))];
+        assert_eq!(report("operator_length", &length), "Number(5.0)");
+
+        let contains = [("text", text("Apple")), ("item", text("PP"))];
+        assert_eq!(report("operator_contains", &contains), "Bool(true)");
+        let contains = [("text", text("Apple")), ("item", text("x"))];
+        assert_eq!(report("operator_contains", &contains), "Bool(false)");
+    }
+
+    #[test]
+    fn stop_returns_from_procedures() {
+        let mut workspace = Workspace::default();
+        workspace.variable("count", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let call = workspace.block(CALL, &[]);
+        workspace.mutation(call, "p");
+        let repeat = workspace.c_block(REPEAT, &[("times", number(3.0))], &[&[call]]);
+        workspace.stack(&[hat, repeat]);
+
+        let definition = workspace.block(DEFINITION, &[]);
+        workspace.mutation(definition, "p");
+        let change_count = change(&mut workspace, "count", 1.0);
+        let stop = workspace.block(STOP, &[("option", text(STOP_THIS_SCRIPT))]);
+        let repeat = workspace.c_block(REPEAT, &[("times", number(5.0))], &[&[stop]]);
+        workspace.stack(&[definition, change_count, repeat]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(outcome.variables, ["count = Number(3.0)"]);
+    }
+
+    /// Sums 1 to n recursively.
+    fn recursion(n: f64) -> Workspace {
+        let mut workspace = Workspace::default();
+        workspace.variable("sum", number(0.0));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let call = workspace.block(CALL, &[("n", number(n))]);
+        workspace.mutation(call, "sum %n[n]");
+        workspace.stack(&[hat, call]);
+
+        let definition = workspace.block(DEFINITION, &[]);
+        workspace.mutation(definition, "sum %n[n]");
+        let n = workspace.block(PARAMETER, &[]);
+        workspace.mutation(n, "n");
+        let add = workspace.block(SET_VARIABLE, &[("variable", text("sum"))]);
+        let sum = variable(&mut workspace, "sum");
+        let plus = operator(&mut workspace, "operator_add", sum, 0.0);
+        workspace.input(plus, "b", n);
+        workspace.input(add, "value", plus);
+        let recurse = workspace.block(CALL, &[]);
+        workspace.mutation(recurse, "sum %n[n]");
+        let n = workspace.block(PARAMETER, &[]);
+        workspace.mutation(n, "n");
+        let minus = operator(&mut workspace, "operator_subtract", n, 1.0);
+        workspace.input(recurse, "n", minus);
+        let n = workspace.block(PARAMETER, &[]);
+        workspace.mutation(n, "n");
+        let positive = operator(&mut workspace, "operator_gt", n, 1.0);
+        let check = workspace.c_block(IF, &[], &[&[recurse]]);
+        workspace.input(check, "condition", positive);
+        workspace.stack(&[definition, add, check]);
+        workspace
+    }
+
+    #[test]
+    fn procedures() {
+        let outcome = run(&recursion(100.0), Limits::default());
+        assert_eq!(outcome.variables, ["sum = Number(5050.0)"]);
+    }
+
+    fn parameter(workspace: &mut Workspace, opcode: &str, name: &str) -> usize {
+        let parameter = workspace.block(opcode, &[]);
+        workspace.mutation(parameter, name);
+        parameter
+    }
+
+    fn join(workspace: &mut Workspace, a: usize, b: usize) -> usize {
+        let join = workspace.block("operator_join", &[]);
+        workspace.input(join, "a", a);
+        workspace.input(join, "b", b);
+        join
+    }
+
+    fn log(workspace: &mut Workspace, item: usize) -> usize {
+        let add = workspace.block(ADD_TO_LIST, &[("list", text("log"))]);
+        workspace.input(add, "item", item);
+        add
+    }
+
+    #[test]
+    fn parameters() {
+        const PROTOTYPE: &str = "log %s[text] %n[n] %b[flag]";
+        let mut workspace = Workspace::default();
+        workspace.list("log", Vec::new());
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let call = workspace.block(CALL, &[("text", text("a")), ("n", number(2.0))]);
+        workspace.mutation(call, PROTOTYPE);
+        // not of an empty slot is true
+        let not = workspace.block(NOT, &[]);
+        workspace.input(call, "flag", not);
+        // parameters outside of their procedure are 0, like in Scratch
+        let n = parameter(&mut workspace, PARAMETER, "n");
+        let outside = log(&mut workspace, n);
+        workspace.stack(&[hat, call, outside]);
+
+        let definition = workspace.block(DEFINITION, &[]);
+        workspace.mutation(definition, PROTOTYPE);
+        let text_parameter = parameter(&mut workspace, PARAMETER, "text");
+        let n = parameter(&mut workspace, PARAMETER, "n");
+        let flag = parameter(&mut workspace, BOOLEAN_PARAMETER, "flag");
+        let joined = join(&mut workspace, text_parameter, n);
+        let joined = join(&mut workspace, joined, flag);
+        let before = log(&mut workspace, joined);
+        let recurse = workspace.block(CALL, &[]);
+        workspace.mutation(recurse, PROTOTYPE);
+        let text_parameter = parameter(&mut workspace, PARAMETER, "text");
+        workspace.input(recurse, "text", text_parameter);
+        let n = parameter(&mut workspace, PARAMETER, "n");
+        let minus = operator(&mut workspace, "operator_subtract", n, 1.0);
+        workspace.input(recurse, "n", minus);
+        let flag = parameter(&mut workspace, BOOLEAN_PARAMETER, "flag");
+        let not = workspace.block(NOT, &[]);
+        workspace.input(not, "value", flag);
+        workspace.input(recurse, "flag", not);
+        let n = parameter(&mut workspace, PARAMETER, "n");
+        let positive = operator(&mut workspace, "operator_gt", n, 1.0);
+        let check = workspace.c_block(IF, &[], &[&[recurse]]);
+        workspace.input(check, "condition", positive);
+        // the arguments are the same after the call returns
+        let n = parameter(&mut workspace, PARAMETER, "n");
+        let after = log(&mut workspace, n);
+        workspace.stack(&[definition, before, check, after]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(
+            outcome.variables,
+            [concat!(
+                r#"log = [Text("a2true"), Text("a1false"), Number(1.0), Number(2.0), "#,
+                "Number(0.0)]"
+            )]
+        );
+    }
+
+    #[test]
+    fn recursion_limit() {
+        // summing 1 to 100 takes 100 nested calls
+        let limits = Limits {
+            max_depth: Some(100),
+            ..Limits::default()
+        };
+        let outcome = run(&recursion(100.0), limits);
+        assert_eq!(outcome.variables, ["sum = Number(5050.0)"]);
+        assert!(outcome.errors.is_empty());
+
+        let limits = Limits {
+            max_depth: Some(99),
+            ..Limits::default()
+        };
+        let outcome = run(&recursion(100.0), limits);
+        assert_eq!(
+            outcome.errors,
+            ["procedures_call: script terminated: more than 99 nested procedure calls"]
+        );
+        // the calls before the limit was reached ran
+        assert_eq!(outcome.variables, ["sum = Number(5049.0)"]);
+    }
+
+    #[test]
+    fn lists() {
+        let mut workspace = Workspace::default();
+        workspace.variable("result", number(0.0));
+        workspace.list("items", vec![text("a"), text("b")]);
+        let list = || ("list", text("items"));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let add = workspace.block(ADD_TO_LIST, &[list(), ("item", text("c"))]);
+        let insert = workspace.block(
+            INSERT_AT_LIST,
+            &[list(), ("item", text("d")), ("index", number(1.0))],
+        );
+        let replace = workspace.block(
+            REPLACE_ITEM_OF_LIST,
+            &[list(), ("index", number(2.0)), ("item", text("e"))],
+        );
+        let delete = workspace.block(DELETE_OF_LIST, &[list(), ("index", number(3.0))]);
+
+        // "result" becomes every reporter joined
+        let mut result = workspace.block(LIST_CONTENTS, &[list()]);
+        let reporters = [
+            workspace.block(ITEM_OF_LIST, &[list(), ("index", number(1.0))]),
+            workspace.block(ITEM_NUM_OF_LIST, &[list(), ("item", text("c"))]),
+            workspace.block(LENGTH_OF_LIST, &[list()]),
+            workspace.block(LIST_CONTAINS_ITEM, &[list(), ("item", text("E"))]),
+        ];
+        for reporter in reporters {
+            let join = workspace.block("operator_join", &[]);
+            workspace.input(join, "a", result);
+            workspace.input(join, "b", reporter);
+            result = join;
+        }
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("result"))]);
+        workspace.input(set, "value", result);
+        workspace.stack(&[hat, add, insert, replace, delete, set]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(
+            outcome.variables,
+            [
+                r#"result = Text("decd33true")"#,
+                r#"items = [Text("d"), Text("e"), Text("c")]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn broadcast_and_wait() {
+        let mut workspace = Workspace::default();
+        workspace.list("log", Vec::new());
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let broadcast = workspace.block(BROADCAST_AND_WAIT, &[("message", text("go"))]);
+        let add = workspace.block(
+            ADD_TO_LIST,
+            &[("list", text("log")), ("item", text("done"))],
+        );
+        workspace.stack(&[hat, broadcast, add]);
+
+        let receive = workspace.block(WHEN_I_RECEIVE, &[("message", text("GO"))]);
+        let add = workspace.block(
+            ADD_TO_LIST,
+            &[("list", text("log")), ("item", text("received"))],
+        );
+        let wait = workspace.block(crate::standard::WAIT, &[("duration", number(0.0))]);
+        let repeat = workspace.c_block(REPEAT, &[("times", number(3.0))], &[&[add, wait]]);
+        workspace.stack(&[receive, repeat]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(
+            outcome.variables,
+            [r#"log = [Text("received"), Text("received"), Text("received"), Text("done")]"#]
+        );
+    }
+
+    #[test]
+    fn unknown_procedure() {
+        let mut workspace = Workspace::default();
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let call = workspace.block(CALL, &[]);
+        workspace.mutation(call, "missing");
+        workspace.stack(&[hat, call]);
+
+        let outcome = run(&workspace, Limits::default());
+        assert_eq!(
+            outcome.errors,
+            [r#"procedures_call: unknown block "missing""#]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_depth: Some(10),
+            ..Limits::default()
+        };
+        let outcome = run(&recursion(100.0), limits);
+        assert_eq!(
+            outcome.errors,
+            ["procedures_call: script terminated: more than 10 nested procedure calls"]
+        );
+
+        let limits = Limits {
+            total_instructions: Some(50),
+            ..Limits::default()
+        };
+        let outcome = run(&recursion(100.0), limits);
+        assert_eq!(outcome.errors.len(), 1);
+        assert!(outcome.errors[0].ends_with("script terminated: budget exceeded"));
+
+        // the thread continues in the next ticks
+        let limits = Limits {
+            instructions_per_tick: Some(5),
+            ..Limits::default()
+        };
+        let outcome = run(&recursion(100.0), limits);
+        assert_eq!(outcome.variables, ["sum = Number(5050.0)"]);
+    }
+
+    #[test]
+    fn text_length() {
+        let mut workspace = Workspace::default();
+        workspace.variable("text", text("ab"));
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(SET_VARIABLE, &[("variable", text("text"))]);
+        let join = workspace.block("operator_join", &[]);
+        let a = variable(&mut workspace, "text");
+        let b = variable(&mut workspace, "text");
+        workspace.input(join, "a", a);
+        workspace.input(join, "b", b);
+        workspace.input(set, "value", join);
+        let forever = workspace.c_block(FOREVER, &[], &[&[set]]);
+        workspace.stack(&[hat, forever]);
+
+        let limits = Limits {
+            max_length: Some(100),
+            ..Limits::default()
+        };
+        let outcome = run(&workspace, limits);
+        assert_eq!(
+            outcome.errors,
+            ["operator_join: script terminated: list or text longer than 100 items or characters"]
+        );
+    }
+
+    #[test]
+    fn only_written_values_are_checked() {
+        let mut workspace = Workspace::default();
+        workspace.variable("long", text(&"a".repeat(20)));
+        workspace.variable("x", number(0.0));
+        workspace.list("items", vec![number(1.0); 10]);
+        let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+        let set = workspace.block(
+            SET_VARIABLE,
+            &[("variable", text("x")), ("value", text("b"))],
+        );
+        let add = workspace.block(
+            ADD_TO_LIST,
+            &[("list", text("items")), ("item", number(2.0))],
+        );
+        workspace.stack(&[hat, set, add]);
+
+        // the variable which was already too long doesn't stop the script
+        let limits = Limits {
+            max_length: Some(10),
+            ..Limits::default()
+        };
+        let outcome = run(&workspace, limits);
+        assert_eq!(
+            outcome.errors,
+            ["data_addtolist: script terminated: list or text longer than 10 items or characters"]
+        );
+        assert_eq!(outcome.variables[1], r#"x = Text("b")"#);
+    }
+
+    #[test]
+    fn long_waits() {
+        let mut workspace = Workspace::default();
+        workspace.variable("count", number(0.0));
+        for seconds in [1e30, f64::INFINITY] {
+            let hat = workspace.block(WHEN_GAME_STARTS, &[]);
+            let before = change(&mut workspace, "count", 1.0);
+            let wait = workspace.block(WAIT, &[("duration", number(seconds))]);
+            let after = change(&mut workspace, "count", 10.0);
+            workspace.stack(&[hat, before, wait, after]);
+        }
+
+        let mut editor = workspace.load();
+        editor.emit(Event::GameStarts);
+        for _ in 0..10 {
+            editor.tick();
+        }
+        assert_eq!(editor.threads().len(), 2);
+        let outcome = Outcome::new(&editor, editor.errors());
+        assert_eq!(outcome.variables, ["count = Number(2.0)"]);
+
+        let mut editor = workspace.load();
+        let mut vm = Vm::new(editor.compile(), &editor);
+        vm.emit(Event::GameStarts);
+        for _ in 0..10 {
+            vm.tick(&mut editor);
+        }
+        assert!(vm.is_running());
+        vm.store_variables(&mut editor);
+        assert_eq!(Outcome::new(&editor, vm.errors()), outcome);
+    }
+}
@@ -0,0 +1,62 @@
+use eframe::{
+    egui::{DragValue, Response, Sense, Ui},
+    epaint::Vec2,
+};
+
+const DIAL_RADIUS: f32 = 9.0;
+
+/// Snaps `degrees` to a multiple of `snap` and wraps it into `-180..=180`.
+pub(crate) fn normalize_angle(degrees: f32, snap: f32) -> f32 {
+    let snapped = if snap > 0.0 {
+        (degrees / snap).round() * snap
+    } else {
+        degrees
+    };
+
+    let wrapped = snapped.rem_euclid(360.0);
+    if wrapped > 180.0 {
+        wrapped - 360.0
+    } else {
+        wrapped
+    }
+}
+
+/// Dial for directions in degrees, 0 pointing up and 90 pointing right.
+pub fn angle_dial(ui: &mut Ui, degrees: &mut f32, snap: f32) -> Response {
+    let (rect, mut response) =
+        ui.allocate_exact_size(Vec2::splat(2.0 * DIAL_RADIUS), Sense::click_and_drag());
+
+    if let Some(pointer) = response.interact_pointer_pos() {
+        let direction = pointer - rect.center();
+        if direction != Vec2::ZERO {
+            let angle = direction.x.atan2(-direction.y).to_degrees();
+            let angle = normalize_angle(angle, snap);
+            if angle != *degrees {
+                *degrees = angle;
+                response.mark_changed();
+            }
+        }
+    }
+
+    if ui.is_rect_visible(rect) {
+        let visuals = ui.style().interact(&response);
+        let painter = ui.painter();
+        painter.circle(
+            rect.center(),
+            DIAL_RADIUS,
+            visuals.bg_fill,
+            visuals.bg_stroke,
+        );
+
+        let radians = degrees.to_radians();
+        let needle = Vec2::new(radians.sin(), -radians.cos()) * (DIAL_RADIUS - 2.0);
+        painter.line_segment([rect.center(), rect.center() + needle], visuals.fg_stroke);
+    }
+
+    let drag = ui.add(DragValue::new(degrees).suffix("°").speed(snap.max(1.0)));
+    if drag.changed() {
+        *degrees = normalize_angle(*degrees, snap);
+    }
+
+    response | drag
+}
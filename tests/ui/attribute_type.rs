@@ -0,0 +1,12 @@
+use egui_block_programming::{block::Block, field::DerivedBlock};
+
+#[derive(Block, Default)]
+#[block(label = "pen down {down}")]
+struct PenDown {
+    #[block(slider)]
+    down: bool,
+}
+
+impl DerivedBlock for PenDown {}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use egui_block_programming::{block::Block, field::DerivedBlock};
+
+#[derive(Block, Default)]
+#[block(color = "red", label = "stop")]
+struct Stop {}
+
+impl DerivedBlock for Stop {}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use egui_block_programming::{block::Block, field::DerivedBlock};
+
+#[derive(Block, Default)]
+#[block(label = "show {name}")]
+struct Show {
+    #[block(options("a", "b"), variable)]
+    name: String,
+}
+
+impl DerivedBlock for Show {}
+
+fn main() {}
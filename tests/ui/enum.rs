@@ -0,0 +1,10 @@
+use egui_block_programming::block::Block;
+
+#[derive(Block)]
+#[block(label = "stop")]
+enum Stop {
+    All,
+    ThisScript,
+}
+
+fn main() {}
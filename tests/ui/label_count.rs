@@ -0,0 +1,9 @@
+use egui_block_programming::{block::Block, field::DerivedBlock};
+
+#[derive(Block, Default)]
+#[block(shape = "c", label = "forever")]
+struct Forever {}
+
+impl DerivedBlock for Forever {}
+
+fn main() {}
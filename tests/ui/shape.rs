@@ -0,0 +1,9 @@
+use egui_block_programming::{block::Block, field::DerivedBlock};
+
+#[derive(Block, Default)]
+#[block(shape = "round", label = "x position")]
+struct XPosition {}
+
+impl DerivedBlock for XPosition {}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use egui_block_programming::{block::Block, field::DerivedBlock};
+
+#[derive(Block, Default)]
+#[block(label = "move {steps} steps")]
+struct Move {
+    distance: f64,
+}
+
+impl DerivedBlock for Move {}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use egui_block_programming::{block::Block, field::DerivedBlock};
+
+#[derive(Block, Default)]
+#[block(label = "move {steps} steps")]
+struct Move {
+    steps: f64,
+    speed: f64,
+}
+
+impl DerivedBlock for Move {}
+
+fn main() {}